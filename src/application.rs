@@ -1,33 +1,48 @@
 use std::borrow::Borrow;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use adw::{
-    prelude::*, HeaderBar, OverlaySplitView, PreferencesPage, StatusPage
+    prelude::*, subclass::prelude::ObjectSubclassIsExt, HeaderBar, OverlaySplitView, SplitButton, StatusPage, Toast,
+    ToastOverlay
 };
 use aperture::{DeviceProvider, Viewfinder};
-use gtk::{ApplicationWindow, Button, Orientation, Revealer, ToggleButton};
+use gtk::{ApplicationWindow, Button, Orientation, Revealer, Stack, ToggleButton};
 use gtk::{
     gio, glib,
     ScrolledWindow,
 };
-use crate::widgets::CapsPanel;
+use crate::camera::{find_camera, find_camera_device, list_cameras, CameraDevice, CameraSource};
+use crate::capture_settings::CaptureSettings;
+use crate::files::get_device_id;
+use crate::preview_frame::FrameSampler;
+use crate::profiles::load_profile;
+use crate::quirks::DeviceQuirks;
+use crate::startup_options::StartupOptions;
+use crate::still_capture::{capture_still, finish_still, save_still};
+use crate::video_recording::start_recording;
+use crate::window_state::WindowState;
+use crate::widgets::{
+    create_capture_settings_popover, present_media_topology_dialog, present_profile_diff_dialog, present_save_profile_dialog,
+    CameraSelector, CapsPanel, ControlsPanel, FrameComparison, NativePreview, PreviewOverlay, RecordingIndicator, ScopesPanel, StatisticsPanel
+};
 use log::debug;
+use v4l::Device;
 
 const APP_ID: &str = "de.pixelgerecht.CameraSettings";
 
-const WINDOW_WIDTH: i32 = 1280;
-const WINDOW_HEIGHT: i32 = 720;
-
 mod imp {
-    use crate::widgets::ControlsPanel;
-
     use super::*;
     use adw::subclass::prelude::*;
 
-    #[derive(Debug, Default)]
-    pub struct Application;
-    
+    #[derive(Default)]
+    pub struct Application {
+        pub options: RefCell<StartupOptions>,
+        // Set, when the window is presented, to apply forwarded options
+        pub controls_panel: RefCell<Option<Rc<RefCell<ControlsPanel>>>>,
+        pub camera_selector: RefCell<Option<Rc<CameraSelector>>>,
+    }
+
     #[glib::object_subclass]
     impl ObjectSubclass for Application {
         const NAME: &'static str = "Application";
@@ -52,47 +67,118 @@ mod imp {
             let device_provider = DeviceProvider::instance();
 
             // TODO Maybe in background check, with spinner in GUI?
-            match device_provider.start() {
-                Ok(_) => {}, // Just continue
-                Err(e) => {
-                    present_window_with_error(
-                        app.as_ref(),
-                        "Error starting device provider".to_string(),
-                        e.to_string()
-                    );
-                    return;
+            // Without the device provider, controls are still available
+            let provider_error = device_provider.start().err();
+            let source = match &provider_error {
+                None => CameraSource::DeviceProvider,
+                Some(e) => {
+                    eprintln!("Error starting device provider, continuing with the direct preview: {}", e);
+                    CameraSource::DeviceNodes
                 }
             };
 
-            let page = Rc::new(PreferencesPage::builder()
-                .height_request(800)
-                .hexpand(false)
-                .vexpand(true)
-                .width_request(400) 
-                .build());
+            let options = self.options.take();
+            let cameras = list_cameras(source);
 
-            let default_camera = device_provider.camera(0);
+            let state = WindowState::load();
 
-            let controls_panel = match &default_camera {
-                Some(c) => {
-                    let cp = ControlsPanel::new(crate::camera::get_path(&c));
-                    Rc::new(RefCell::new(cp))
-                },
-                None => {
-                    present_window_with_error(
-                        app.as_ref(),
-                        "No camera-device found".to_string(),
-                        "Connect a camera and restart this app".to_string()
-                    );
-                    return;
-                },
+            // The last camera is silently skipped, when it is missing
+            let selected_camera = match &options.device {
+                Some(d) => find_camera_device(&cameras, d).or_else(|| {
+                    eprintln!("No camera found for {}, using the first one.", d);
+                    cameras.first().cloned()
+                }),
+                None => state
+                    .device
+                    .as_ref()
+                    .and_then(|d| find_camera_device(&cameras, d))
+                    .or_else(|| cameras.first().cloned()),
             };
 
-            let groups: &RefCell<ControlsPanel> = controls_panel.borrow();
-            for group in groups.borrow().get_pref_groups().iter() {
-                page.add(group);
+            // Without a camera, the window waits for one to be plugged in
+            let selected_path = selected_camera
+                .as_ref()
+                .map(|c| c.path.clone())
+                .unwrap_or_default();
+
+            if let Some(profile) = &options.profile {
+                match &selected_camera {
+                    Some(camera) => apply_profile(profile, &camera.path),
+                    None => eprintln!("No camera to apply profile {} to", profile),
+                }
             }
 
+            // Without PipeWire, the preview streams directly from the device.
+            // Otherwise, it can be switched with the direct-preview action.
+            let direct_preview = Rc::new(Cell::new(options.native_preview || provider_error.is_some()));
+
+            let camera_view = if options.no_preview || provider_error.is_some() {
+                None
+            } else {
+                let viewfinder = Viewfinder::new();
+                // Set, when it is shown, as it would stream meanwhile
+                if !direct_preview.get() {
+                    viewfinder.set_camera(find_camera(&selected_path));
+                }
+                Some(Rc::new(viewfinder))
+            };
+
+            let native_preview = if options.no_preview {
+                None
+            } else {
+                Some(NativePreview::new(&selected_path))
+            };
+            if let Some(native) = native_preview.as_ref().filter(|_| direct_preview.get()) {
+                native.start_stream();
+            }
+
+            // Only the shown page is realized, so the hidden viewfinder does
+            // not stream
+            let preview_stack = camera_view.as_ref().zip(native_preview.as_ref()).map(|(view, native)| {
+                let stack = Stack::builder()
+                    .hexpand(true)
+                    .build();
+                stack.add_named(view.as_ref(), Some("viewfinder"));
+                stack.add_named(native.get_widget(), Some("direct"));
+                stack.set_visible_child_name(match direct_preview.get() {
+                    true => "direct",
+                    false => "viewfinder",
+                });
+                stack
+            });
+
+            let preview: Option<gtk::Widget> = match (&preview_stack, &native_preview) {
+                (Some(stack), _) => Some(stack.clone().upcast()),
+                (None, Some(native)) => Some(native.get_widget().clone().upcast()),
+                (None, None) => None,
+            };
+
+            // The format of a device cannot be changed while it streams
+            let camera_view_for_mode = camera_view.clone();
+            let native_preview_for_mode = native_preview.clone();
+            let direct_preview_for_mode = direct_preview.clone();
+            let set_preview_streaming: Rc<Box<dyn Fn(bool)>> = Rc::new(Box::new(move |streaming| {
+                let direct = direct_preview_for_mode.get();
+                match (&camera_view_for_mode, streaming && !direct) {
+                    (Some(view), true) => view.start_stream(),
+                    (Some(view), false) => view.stop_stream(),
+                    (None, _) => {}
+                };
+                match (&native_preview_for_mode, streaming && direct) {
+                    (Some(native), true) => native.start_stream(),
+                    (Some(native), false) => native.stop_stream(),
+                    (None, _) => {}
+                };
+            }));
+
+            let controls_panel = Rc::new(RefCell::new(ControlsPanel::new(
+                selected_path.clone(),
+                set_preview_streaming,
+            )));
+
+            let panel: &RefCell<ControlsPanel> = controls_panel.borrow();
+            let page = panel.borrow().get_panel();
+
             let controls_sidebar = ScrolledWindow::builder()
                 .hscrollbar_policy(gtk::PolicyType::Never)
                 .min_content_height(600)
@@ -101,9 +187,7 @@ mod imp {
 
             controls_sidebar.set_child(Some(page.as_ref()));
 
-            let camera_view = Rc::new(Viewfinder::new());
-
-            let info_panel = Rc::new(RefCell::new(CapsPanel::new(&default_camera.unwrap())));
+            let info_panel = Rc::new(RefCell::new(CapsPanel::new(&selected_path)));
             let info_panel_ref = info_panel.as_ref().borrow();
 
             let info_sidebar = ScrolledWindow::builder()
@@ -114,11 +198,10 @@ mod imp {
 
             let info_revealer = Revealer::builder()
                 .child(&info_sidebar)
-                .reveal_child(false)
+                .reveal_child(state.info_revealed)
                 .transition_type(gtk::RevealerTransitionType::SlideLeft)
                 .build();
 
-
             let content = gtk::Box::builder()
                 .orientation(Orientation::Horizontal)
                 .margin_end(12)
@@ -128,17 +211,98 @@ mod imp {
                 .spacing(12)
                 .build();
 
-            content.append(camera_view.as_ref());
+            let frame_sampler = preview.as_ref().map(|preview| FrameSampler::new(preview.clone()));
+            let scopes_panel = frame_sampler.as_ref().map(|sampler| ScopesPanel::new(sampler.clone()));
+            let preview_overlay = preview
+                .as_ref()
+                .zip(frame_sampler.as_ref())
+                .map(|(preview, sampler)| PreviewOverlay::new(preview.clone(), sampler.clone()));
+            let frame_comparison = preview.as_ref().zip(preview_overlay.as_ref()).map(|(preview, overlay)| {
+                FrameComparison::new(preview.clone(), overlay.get_widget().clone().upcast())
+            });
+
+            let statistics_panel = frame_sampler.as_ref().map(|sampler| {
+                let controls_panel_for_statistics = controls_panel.clone();
+                let device_path: Box<dyn Fn() -> String> = Box::new(move || {
+                    controls_panel_for_statistics.as_ref().borrow().get_device_path()
+                });
+                StatisticsPanel::new(sampler.clone(), device_path)
+            });
+
+            match &preview {
+                Some(_) => {
+                    let preview_box = gtk::Box::builder()
+                        .orientation(Orientation::Horizontal)
+                        .build();
+                    if let Some(comparison) = &frame_comparison {
+                        preview_box.append(comparison.get_widget());
+                    }
+                    if let Some(scopes) = &scopes_panel {
+                        preview_box.append(scopes.get_widget());
+                    }
+                    if let Some(statistics) = &statistics_panel {
+                        preview_box.append(statistics.get_widget());
+                    }
+                    content.append(&preview_box);
+                }
+                None => {
+                    let preview_disabled = StatusPage::builder()
+                        .description("Started with --no-preview")
+                        .hexpand(true)
+                        .icon_name("camera-disabled-symbolic")
+                        .title("Preview disabled")
+                        .build();
+                    content.append(&preview_disabled);
+                }
+            };
+
             content.append(&info_revealer);
 
-            let device_selection_box = crate::camera::get_camera_selection_box(
-                controls_panel.clone(),
-                camera_view.clone(),
-                info_panel.clone(),
-            );
+            let unplugged_page = StatusPage::builder()
+                .description("Waiting for a camera to be connected")
+                .icon_name("camera-disabled-symbolic")
+                .title("No camera detected")
+                .build();
+
+            let content_stack = Stack::builder()
+                .transition_type(gtk::StackTransitionType::Crossfade)
+                .build();
+
+            let controls_panel_for_selection = controls_panel.clone();
+            let info_panel_for_selection = info_panel.clone();
+            let camera_view_for_selection = camera_view.clone();
+            let native_preview_for_selection = native_preview.clone();
+            let direct_preview_for_selection = direct_preview.clone();
+            let content_stack_for_selection = content_stack.clone();
+            let on_selected: Box<dyn Fn(&CameraDevice)> = Box::new(move |camera| {
+                controls_panel_for_selection
+                    .as_ref()
+                    .borrow_mut()
+                    .switch_device(camera.path.clone());
+
+                info_panel_for_selection.borrow_mut().update(&camera.path);
+                if let Some(view) = camera_view_for_selection.as_ref().filter(|_| !direct_preview_for_selection.get()) {
+                    view.set_camera(find_camera(&camera.path));
+                }
+                if let Some(native) = &native_preview_for_selection {
+                    native.set_device(&camera.path);
+                }
+
+                content_stack_for_selection.set_visible_child_name("camera");
+            });
+
+            let unplugged_page_for_unplug = unplugged_page.clone();
+            let content_stack_for_unplug = content_stack.clone();
+            let on_unplugged: Box<dyn Fn(&str)> = Box::new(move |name| {
+                unplugged_page_for_unplug.set_title(&format!("{} disconnected", name));
+                unplugged_page_for_unplug.set_description(Some("Waiting for the camera to be connected again"));
+                content_stack_for_unplug.set_visible_child_name("unplugged");
+            });
+
+            let camera_selector = CameraSelector::new(source, selected_camera.as_ref(), on_selected, on_unplugged);
 
             let header_bar = HeaderBar::builder()
-                .title_widget(&device_selection_box)
+                .title_widget(camera_selector.get_widget())
                 .build();
 
             let reset_defaults_button = Button::builder()
@@ -155,20 +319,142 @@ mod imp {
                     .reset_defaults();
             });
 
+            let save_profile_button = Button::builder()
+                .css_classes(["flat"])
+                .icon_name("document-save-symbolic")
+                .tooltip_text("Save settings as profile")
+                .build();
+
+            let controls_panel_for_profile = controls_panel.clone();
+            save_profile_button.connect_clicked(move |button| {
+                let device_path = controls_panel_for_profile
+                    .as_ref()
+                    .borrow()
+                    .get_device_path();
+                present_save_profile_dialog(button, device_path);
+            });
+
+            let compare_button = Button::builder()
+                .css_classes(["flat"])
+                .icon_name("view-dual-symbolic")
+                .tooltip_text("Compare with profile or camera")
+                .build();
+
+            let controls_panel_for_compare = controls_panel.clone();
+            compare_button.connect_clicked(move |button| {
+                let device_path = controls_panel_for_compare
+                    .as_ref()
+                    .borrow()
+                    .get_device_path();
+
+                let controls_panel_for_refresh = controls_panel_for_compare.clone();
+                let on_applied: Rc<Box<dyn Fn() + 'static>> = Rc::new(Box::new(move || {
+                    controls_panel_for_refresh.as_ref().borrow().refresh();
+                }));
+
+                present_profile_diff_dialog(button, device_path, source, on_applied);
+            });
+
+            let topology_button = Button::builder()
+                .css_classes(["flat"])
+                .icon_name("network-workgroup-symbolic")
+                .tooltip_text("Show media topology")
+                .build();
+
+            let controls_panel_for_topology = controls_panel.clone();
+            topology_button.connect_clicked(move |button| {
+                let device_path = controls_panel_for_topology
+                    .as_ref()
+                    .borrow()
+                    .get_device_path();
+                present_media_topology_dialog(button, device_path);
+            });
+
+            let capture_settings = Rc::new(RefCell::new(CaptureSettings::load()));
+
+            let capture_button = SplitButton::builder()
+                .action_name("win.capture-still")
+                .dropdown_tooltip("Capture settings")
+                .icon_name("camera-photo-symbolic")
+                .popover(&create_capture_settings_popover(capture_settings.clone()))
+                .tooltip_text("Capture still (Ctrl+Shift+S)")
+                .build();
+
+            let record_button = Button::builder()
+                .action_name("win.toggle-recording")
+                .css_classes(["flat"])
+                .icon_name("media-record-symbolic")
+                .tooltip_text("Start recording (Ctrl+R)")
+                // Recording is done by the PipeWire preview
+                .visible(camera_view.is_some())
+                .build();
+
+            let recording_indicator = RecordingIndicator::new();
+
+            let direct_preview_button = ToggleButton::builder()
+                .action_name("win.direct-preview")
+                .css_classes(["flat"])
+                .icon_name("video-display-symbolic")
+                .tooltip_text("Stream directly from the device, without PipeWire")
+                // Without PipeWire, there is only the direct preview
+                .visible(preview_stack.is_some())
+                .build();
+
+            let scopes_button = ToggleButton::builder()
+                .css_classes(["flat"])
+                .icon_name("utilities-system-monitor-symbolic")
+                .sensitive(scopes_panel.is_some())
+                .tooltip_text("Show scopes")
+                .build();
+
+            scopes_button.connect_toggled(move |button| {
+                if let Some(scopes) = &scopes_panel {
+                    scopes.set_active(button.is_active());
+                }
+            });
+
+            let statistics_button = ToggleButton::builder()
+                .css_classes(["flat"])
+                .icon_name("document-open-recent-symbolic")
+                .sensitive(statistics_panel.is_some())
+                .tooltip_text("Show frame statistics")
+                .build();
+
+            statistics_button.connect_toggled(move |button| {
+                if let Some(statistics) = &statistics_panel {
+                    statistics.set_active(button.is_active());
+                }
+            });
+
             let caps_reveal_button = ToggleButton::builder()
+                .active(state.info_revealed)
                 .css_classes(["flat"])
                 .icon_name("info-outline-symbolic")
                 .tooltip_text("Show camera details")
                 .build();
 
-            caps_reveal_button.connect_clicked(move |_| {
-                info_revealer.set_reveal_child(
-                    !info_revealer.reveals_child()
-                );
+            let info_revealer_for_reveal = info_revealer.clone();
+            caps_reveal_button.connect_toggled(move |button| {
+                info_revealer_for_reveal.set_reveal_child(button.is_active());
             });
 
             header_bar.pack_start(&reset_defaults_button);
+            header_bar.pack_start(&save_profile_button);
+            header_bar.pack_start(&compare_button);
             header_bar.pack_end(&caps_reveal_button);
+            header_bar.pack_end(&topology_button);
+            header_bar.pack_end(&scopes_button);
+            header_bar.pack_end(&statistics_button);
+            if let Some(overlay) = &preview_overlay {
+                header_bar.pack_end(&overlay.create_menu_button());
+            }
+            if let Some(comparison) = &frame_comparison {
+                header_bar.pack_end(&comparison.create_menu_button());
+            }
+            header_bar.pack_end(&direct_preview_button);
+            header_bar.pack_end(&capture_button);
+            header_bar.pack_end(&record_button);
+            header_bar.pack_end(recording_indicator.get_widget());
 
             let split_view = OverlaySplitView::builder()
                 .content(&content)
@@ -176,20 +462,228 @@ mod imp {
                 .max_sidebar_width(800.0)
                 .min_sidebar_width(600.0)
                 .pin_sidebar(true)
+                .sidebar_width_fraction(state.sidebar_width_fraction)
+                .build();
+
+            content_stack.add_named(&split_view, Some("camera"));
+            content_stack.add_named(&unplugged_page, Some("unplugged"));
+            if selected_camera.is_none() {
+                content_stack.set_visible_child_name("unplugged");
+            }
+
+            let toast_overlay = ToastOverlay::builder()
+                .child(&content_stack)
                 .build();
 
             // Create a window and set the title
             let window = ApplicationWindow::builder()
                 .application(app.as_ref())
-                .child(&split_view)
-                .height_request(WINDOW_HEIGHT)
+                .child(&toast_overlay)
+                .default_height(state.height)
+                .default_width(state.width)
+                .maximized(state.maximized)
                 .titlebar(&header_bar)
-                .width_request(WINDOW_WIDTH)
                 .build();
 
+            // Without preview, there is no frame to capture
+            let capture_action = gio::SimpleAction::new("capture-still", None);
+            capture_action.set_enabled(preview.is_some());
+
+            let camera_view_for_capture = camera_view.clone();
+            let native_preview_for_capture = native_preview.clone();
+            let direct_preview_for_capture = direct_preview.clone();
+            let capture_settings_for_capture = capture_settings.clone();
+            let toast_overlay_for_capture = toast_overlay.clone();
+            let controls_panel_for_capture = controls_panel.clone();
+            capture_action.connect_activate(move |_, _| {
+                let device_path = controls_panel_for_capture.as_ref().borrow().get_device_path();
+                let settings = capture_settings_for_capture.borrow();
+
+                let direct = direct_preview_for_capture.get();
+                let result = match (&camera_view_for_capture, &native_preview_for_capture) {
+                    // Reported, when the picture is done
+                    (Some(view), _) if !direct => capture_still(view, &device_path, &settings).map(|_| None),
+                    (_, Some(native)) => match native.current_frame() {
+                        Some(frame) => save_still(&frame, &device_path, &settings).map(Some),
+                        None => Err("The preview shows no frame".to_string()),
+                    },
+                    _ => return,
+                };
+
+                let toast = match result {
+                    Ok(Some(path)) => Toast::new(&format!("Saved {}", path.file_name().unwrap_or_default().to_string_lossy())),
+                    Ok(None) => return,
+                    Err(e) => {
+                        eprintln!("Error capturing still: {}", e);
+                        Toast::new(&format!("Capture failed: {}", e))
+                    }
+                };
+                toast_overlay_for_capture.add_toast(toast);
+            });
+            window.add_action(&capture_action);
+
+            let recording_action = gio::SimpleAction::new("toggle-recording", None);
+            recording_action.set_enabled(camera_view.is_some() && !direct_preview.get());
+
+            let camera_view_for_recording = camera_view.clone();
+            let toast_overlay_for_recording = toast_overlay.clone();
+            let controls_panel_for_recording = controls_panel.clone();
+            recording_action.connect_activate(move |_, _| {
+                let view = match &camera_view_for_recording {
+                    Some(v) => v,
+                    None => return,
+                };
+
+                if view.is_recording() {
+                    if let Err(e) = view.stop_recording() {
+                        eprintln!("Error stopping recording: {}", e);
+                    }
+                    return;
+                }
+
+                let device_path = controls_panel_for_recording.as_ref().borrow().get_device_path();
+                if let Err(e) = start_recording(view, &device_path, &capture_settings.borrow()) {
+                    eprintln!("Error starting recording: {}", e);
+                    toast_overlay_for_recording.add_toast(Toast::new(&format!("Recording failed: {}", e)));
+                }
+            });
+            window.add_action(&recording_action);
+
+            let direct_preview_action =
+                gio::SimpleAction::new_stateful("direct-preview", None, &direct_preview.get().to_variant());
+            direct_preview_action.set_enabled(preview_stack.is_some());
+
+            let camera_view_for_switch = camera_view.clone();
+            let native_preview_for_switch = native_preview.clone();
+            let controls_panel_for_switch = controls_panel.clone();
+            direct_preview_action.connect_change_state(move |action, state| {
+                let direct = match state.and_then(|s| s.get::<bool>()) {
+                    Some(d) => d,
+                    None => return,
+                };
+                let (view, native, stack) = match (&camera_view_for_switch, &native_preview_for_switch, &preview_stack) {
+                    (Some(view), Some(native), Some(stack)) => (view, native, stack),
+                    _ => return,
+                };
+                if !action.is_enabled() || direct == direct_preview.get() {
+                    return;
+                }
+
+                action.set_state(&direct.to_variant());
+                direct_preview.set(direct);
+                recording_action.set_enabled(!direct);
+
+                if direct {
+                    view.stop_stream();
+                    native.start_stream();
+                    stack.set_visible_child_name("direct");
+                    return;
+                }
+
+                // PipeWire cannot open the device, before the direct stream
+                // closed it
+                native.stop_stream();
+                let device_path = controls_panel_for_switch.as_ref().borrow().get_device_path();
+                let view = view.clone();
+                let stack = stack.clone();
+                native.when_released(move || {
+                    stack.set_visible_child_name("viewfinder");
+                    view.set_camera(find_camera(&device_path));
+                    view.start_stream();
+                });
+            });
+            window.add_action(&direct_preview_action);
+
+            if let Some(view) = &camera_view {
+                // Also stopped by aperture, e.g. when the camera is unplugged
+                view.connect_notify_local(Some("is-recording"), move |view, _| {
+                    // The recording is done by the viewfinder
+                    direct_preview_action.set_enabled(!view.is_recording());
+
+                    if view.is_recording() {
+                        record_button.set_icon_name("media-playback-stop-symbolic");
+                        record_button.set_tooltip_text(Some("Stop recording (Ctrl+R)"));
+                        recording_indicator.start();
+                    } else {
+                        record_button.set_icon_name("media-record-symbolic");
+                        record_button.set_tooltip_text(Some("Start recording (Ctrl+R)"));
+                        recording_indicator.stop();
+                    }
+                });
+
+                let toast_overlay_for_picture = toast_overlay.clone();
+                view.connect_picture_done(move |_, file| {
+                    let result = match file {
+                        Some(f) => finish_still(f).map(|_| f.basename()),
+                        None => Err("No picture was taken".to_string()),
+                    };
+
+                    let toast = match result {
+                        Ok(name) => Toast::new(&format!("Saved {}", name.unwrap_or_default().display())),
+                        Err(e) => {
+                            eprintln!("Error capturing still: {}", e);
+                            Toast::new(&format!("Capture failed: {}", e))
+                        }
+                    };
+                    toast_overlay_for_picture.add_toast(toast);
+                });
+
+                view.connect_recording_done(move |_, file| {
+                    let toast = match file.and_then(|f| f.basename()) {
+                        Some(name) => Toast::new(&format!("Saved {}", name.display())),
+                        None => Toast::new("Recording failed"),
+                    };
+                    toast_overlay.add_toast(toast);
+                });
+            }
+
+            let controls_panel_for_state = controls_panel.clone();
+            let last_device = state.device.clone();
+            window.connect_close_request(move |window| {
+                let device_path = controls_panel_for_state.as_ref().borrow().get_device_path();
+                let (width, height) = window.default_size();
+
+                let state = WindowState {
+                    // The last camera is kept, if none was connected since
+                    device: match device_path.is_empty() {
+                        true => last_device.clone(),
+                        false => Some(get_device_id(&device_path)),
+                    },
+                    width,
+                    height,
+                    maximized: window.is_maximized(),
+                    sidebar_width_fraction: split_view.sidebar_width_fraction(),
+                    info_revealed: info_revealer.reveals_child(),
+                };
+                state.save();
+
+                glib::Propagation::Proceed
+            });
+
+            self.controls_panel.replace(Some(controls_panel));
+            self.camera_selector.replace(Some(camera_selector));
+
             window.present();
         }
 
+        fn command_line(&self, command_line: &gio::ApplicationCommandLine) -> glib::ExitCode {
+            let options = StartupOptions::from_dict(&command_line.options_dict());
+            let app = self.obj();
+
+            match app.active_window() {
+                Some(window) => {
+                    app.apply_forwarded_options(&options);
+                    window.present();
+                }
+                None => {
+                    self.options.replace(options);
+                    app.activate();
+                }
+            };
+
+            glib::ExitCode::SUCCESS
+        }
+
         fn startup(&self) {
             self.parent_startup();
             aperture::init(APP_ID);
@@ -219,6 +713,7 @@ impl Default for Application {
     fn default() -> Self {
         glib::Object::builder()
             .property("application-id", APP_ID)
+            .property("flags", gio::ApplicationFlags::HANDLES_COMMAND_LINE)
             .property("resource-base-path", "/de/pixelgerecht/CameraSettings/")
             .build()
     }
@@ -226,7 +721,39 @@ impl Default for Application {
 
 impl Application {
     pub fn new() -> Self {
-        Self::default()
+        let app = Self::default();
+        StartupOptions::register(&app);
+        app
+    }
+
+    /// Applies options given to another instance to the presented window.
+    fn apply_forwarded_options(&self, options: &StartupOptions) {
+        let imp = self.imp();
+
+        if let Some(device) = &options.device {
+            if let Some(selector) = imp.camera_selector.borrow().as_ref() {
+                if !selector.select_device(device) {
+                    eprintln!("No camera found for {}", device);
+                }
+            }
+        }
+
+        if let Some(profile) = &options.profile {
+            if let Some(controls_panel) = imp.controls_panel.borrow().as_ref() {
+                let panel = controls_panel.as_ref().borrow();
+                apply_profile(profile, &panel.get_device_path());
+                panel.refresh();
+            }
+        }
+
+        if options.no_preview {
+            eprintln!("--no-preview is ignored for the already running window");
+        }
+        if options.native_preview {
+            if let Some(window) = self.active_window().and_downcast::<ApplicationWindow>() {
+                window.change_action_state("direct-preview", &true.to_variant());
+            }
+        }
     }
 
     fn setup_gactions(&self) {
@@ -240,23 +767,31 @@ impl Application {
     fn setup_accels(&self) {
         self.set_accels_for_action("app.quit", &["<Control>q"]);
         self.set_accels_for_action("window.close", &["<Ctrl>w"]);
+        self.set_accels_for_action("win.capture-still", &["<Ctrl><Shift>s"]);
+        self.set_accels_for_action("win.toggle-recording", &["<Ctrl>r"]);
     }
 }
 
-fn present_window_with_error(app: &Application, title: String, description: String) {
-    let status_page = StatusPage::builder()
-        .description(description)
-        .icon_name("unreadable-symbolic")
-        .title(title)
-        .build();
-
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .child(&status_page)
-        .height_request(WINDOW_HEIGHT)
-        .title("Camera Settings")
-        .width_request(WINDOW_WIDTH)
-        .build();
-
-    window.present();
+fn apply_profile(name_or_path: &str, device_path: &str) {
+    let profile = match load_profile(name_or_path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error loading profile: {}", e);
+            return;
+        }
+    };
+
+    let device = match Device::with_path(device_path) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error opening {} to apply profile: {}", device_path, e);
+            return;
+        }
+    };
+
+    let quirks = DeviceQuirks::for_device(device_path, &device);
+    for error in profile.apply(&device, &quirks) {
+        eprintln!("Profile {}: {}", profile.name, error);
+    }
 }
+
@@ -1,110 +1,181 @@
-use std::{cell::RefCell, rc::Rc};
-
-use aperture::{Camera, DeviceProvider, Viewfinder};
-use gtk::{Align, Box, DropDown, Label, ListItem, SignalListItemFactory};
+use aperture::{Camera, DeviceProvider};
 use adw::prelude::*;
 
-use crate::{components::create_hbox, widgets::{CapsPanel, ControlsPanel}};
+use crate::{
+    device_discovery::{discover_devices, get_location, get_physical_device, DeviceLocation},
+    files::resolve_device_id,
+};
 
+/// A camera, as it can be selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraDevice {
+    pub path: String,
+    pub name: String,
+}
 
-pub fn get_camera_selection_box(
-    pref_groups: Rc<RefCell<ControlsPanel>>,
-    camera_view: Rc<Viewfinder>,
-    caps_panel: Rc<RefCell<CapsPanel>>,
-    ) -> Box {
-    let device_selection_box = create_hbox();
+impl CameraDevice {
+    pub fn from_camera(camera: &Camera) -> Self {
+        CameraDevice {
+            path: get_path(camera),
+            name: get_name(camera),
+        }
+    }
+}
 
-    let device_provider = DeviceProvider::instance();
-    let _ = device_provider.start();
-    
-    let camera_count = device_provider.n_items();
-    
-    if camera_count == 0 {
-        let label = Label::builder()
-            .label("No camera detected")
-            .halign(Align::Center)
-            .xalign(0.5)
-            .build();
-
-        device_selection_box.append(&label);
-        return device_selection_box;
+/// Where cameras are listed from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraSource {
+    /// Cameras of the aperture device provider, which can be previewed
+    DeviceProvider,
+    /// Device-nodes found directly, when the device provider is unavailable
+    DeviceNodes,
+}
+
+/// The capture nodes of one physical camera. Most cameras have one, capture
+/// cards may have one per input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraGroup {
+    pub name: String,
+    /// Named like the group
+    pub nodes: Vec<CameraDevice>,
+}
+
+/// Lists each capture node on its own, with distinct names.
+pub fn list_cameras(source: CameraSource) -> Vec<CameraDevice> {
+    let mut cameras = list_nodes(source);
+
+    disambiguate_names(&mut cameras, get_location);
+    cameras
+}
+
+/// Lists the cameras with their capture nodes. Identically named cameras are
+/// told apart, not the nodes of a camera.
+pub fn list_camera_groups(source: CameraSource) -> Vec<CameraGroup> {
+    let mut groups = group_cameras(list_nodes(source), get_physical_device);
+
+    let mut firsts: Vec<CameraDevice> = groups
+        .iter()
+        .map(|g| CameraDevice {
+            path: g.nodes[0].path.clone(),
+            name: g.name.clone(),
+        })
+        .collect();
+    disambiguate_names(&mut firsts, get_location);
+
+    for (group, first) in groups.iter_mut().zip(firsts) {
+        group.name = first.name;
+        for node in group.nodes.iter_mut() {
+            node.name = group.name.clone();
+        }
     }
 
-    if camera_count == 1 {
-        let only_cam = device_provider.camera(0).unwrap();
-        let camera_label = get_name(&only_cam);
+    groups
+}
+
+fn list_nodes(source: CameraSource) -> Vec<CameraDevice> {
+    match source {
+        CameraSource::DeviceProvider => {
+            let device_provider = DeviceProvider::instance();
+            (0..device_provider.n_items())
+                .filter_map(|i| device_provider.camera(i))
+                .map(|c| CameraDevice::from_camera(&c))
+                .collect()
+        }
+        CameraSource::DeviceNodes => discover_devices(),
+    }
+}
 
-        let label = Label::builder()
-            .label(&camera_label)
-            .halign(Align::Center)
-            .xalign(0.5)
-            .build();
+/// Groups the nodes by their physical device, in the order of their first
+/// nodes. Groups are named after their first node.
+fn group_cameras(cameras: Vec<CameraDevice>, physical_device: impl Fn(&str) -> String) -> Vec<CameraGroup> {
+    let mut groups: Vec<(String, CameraGroup)> = vec![];
 
-        device_selection_box.append(&label);
-        return device_selection_box;
+    for camera in cameras {
+        let device = physical_device(&camera.path);
+        match groups.iter_mut().find(|(d, _)| *d == device) {
+            Some((_, group)) => group.nodes.push(camera),
+            None => groups.push((device, CameraGroup {
+                name: camera.name.clone(),
+                nodes: vec![camera],
+            })),
+        }
     }
 
-    let factory = SignalListItemFactory::new();
-
-    factory.connect_setup(|_, list_item| {
-        let label = Label::builder()
-            .halign(Align::Start)
-            .build();
-        list_item
-            .downcast_ref::<ListItem>()
-            .expect("Needs to be ListItem")
-            .set_child(Some(&label));
-    });
-
-    factory.connect_bind(|_, list_item| {
-        let cam = list_item
-            .downcast_ref::<ListItem>()
-            .expect("Needs to be ListItem")
-            .item()
-            .and_downcast::<Camera>()
-            .expect("The item has to be a `Camera`.");
-
-        let label = list_item
-            .downcast_ref::<ListItem>()
-            .expect("Needs to be ListItem")
-            .child()
-            .and_downcast::<Label>()
-            .expect("The child has to be a `Label`.");
-
-        label.set_label(&get_name(&cam));
-    });
-
-    let device_selection_dropdown = DropDown::builder()
-        .factory(&factory)
-        .model(device_provider)
-        .show_arrow(true)
-        .build();
-
-    device_selection_dropdown.connect_selected_item_notify(move |cb| {
-        let selected = cb.selected();
-        let dp = DeviceProvider::instance();
-        let camera = match dp.camera(selected) {
-            Some(c) => c,
-            None => { 
-                eprintln!("Device provider knows no camera at position {}", selected);
-                return;
-            }
+    groups.into_iter().map(|(_, g)| g).collect()
+}
+
+/// Adds where identically named cameras are attached to their names: The
+/// serial, the bus position or finally the device-node, whichever tells all
+/// of them apart.
+fn disambiguate_names(cameras: &mut [CameraDevice], locate: impl Fn(&str) -> DeviceLocation) {
+    let mut names: Vec<String> = cameras.iter().map(|c| c.name.clone()).collect();
+    names.sort();
+    names.dedup();
+
+    for name in names {
+        let indices: Vec<usize> = (0..cameras.len()).filter(|i| cameras[*i].name == name).collect();
+        if indices.len() < 2 {
+            continue;
+        }
+
+        let locations: Vec<DeviceLocation> = indices.iter().map(|i| locate(&cameras[*i].path)).collect();
+
+        let serials: Vec<Option<String>> = locations
+            .iter()
+            .map(|l| l.serial.as_ref().map(|s| format!("Serial {}", s)))
+            .collect();
+        let buses: Vec<Option<String>> = locations
+            .iter()
+            .map(|l| l.bus.as_ref().map(|b| format!("Bus {}", b)))
+            .collect();
+
+        let suffixes = if are_distinct(&serials) {
+            serials
+        } else if are_distinct(&buses) {
+            buses
+        } else {
+            locations.iter().map(|l| Some(l.node.clone())).collect()
         };
-        let path = get_path(&camera);
 
-        pref_groups
-            .as_ref()
-            .borrow_mut()
-            .switch_device(path);
+        for (i, suffix) in indices.iter().zip(suffixes) {
+            if let Some(suffix) = suffix {
+                cameras[*i].name = format!("{} ({})", name, suffix);
+            }
+        }
+    }
+}
 
-        caps_panel.borrow_mut().update(&camera);
-        camera_view.set_camera(Some(camera));
-    });
+fn are_distinct(labels: &[Option<String>]) -> bool {
+    if labels.iter().any(|l| l.is_none()) {
+        return false;
+    }
 
-    device_selection_box.append(&Label::new(Some("Select camera: ")));
-    device_selection_box.append(&device_selection_dropdown);
+    let mut sorted = labels.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    sorted.len() == labels.len()
+}
 
-    return device_selection_box;
+/// Finds the camera by its device path or stable id in the list.
+pub fn find_camera_device(cameras: &[CameraDevice], device: &str) -> Option<CameraDevice> {
+    let device_path = resolve_device_id(device)?;
+    cameras.iter().find(|c| c.path == device_path).cloned()
+}
+
+/// Finds the camera of the device provider by its device path or stable id.
+pub fn find_camera(device: &str) -> Option<Camera> {
+    let device_path = resolve_device_id(device)?;
+    let device_provider = DeviceProvider::instance();
+
+    for i in 0..device_provider.n_items() {
+        if let Some(camera) = device_provider.camera(i) {
+            if get_path(&camera) == device_path {
+                return Some(camera);
+            }
+        }
+    }
+
+    None
 }
 
 pub fn get_path(camera: &Camera) -> String {
@@ -146,3 +217,65 @@ pub fn get_name(camera: &Camera) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(path: &str, name: &str) -> CameraDevice {
+        CameraDevice {
+            path: path.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_disambiguate_names() {
+        let mut cameras = vec![
+            camera("/dev/video0", "Webcam"),
+            camera("/dev/video2", "Webcam"),
+            camera("/dev/video4", "Capture Card"),
+            camera("/dev/video5", "Capture Card"),
+            camera("/dev/video6", "Other"),
+        ];
+
+        disambiguate_names(&mut cameras, |path| match path {
+            "/dev/video0" => DeviceLocation { serial: Some("A1".to_string()), bus: Some("1-1".to_string()), node: "video0".to_string() },
+            "/dev/video2" => DeviceLocation { serial: None, bus: Some("1-2".to_string()), node: "video2".to_string() },
+            "/dev/video4" => DeviceLocation { serial: None, bus: Some("2-1".to_string()), node: "video4".to_string() },
+            "/dev/video5" => DeviceLocation { serial: None, bus: Some("2-1".to_string()), node: "video5".to_string() },
+            _ => DeviceLocation::default(),
+        });
+
+        let names: Vec<&str> = cameras.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Webcam (Bus 1-1)", "Webcam (Bus 1-2)", "Capture Card (video4)", "Capture Card (video5)", "Other"]
+        );
+    }
+
+    #[test]
+    fn test_group_cameras() {
+        let cameras = vec![
+            camera("/dev/video0", "Webcam"),
+            camera("/dev/video4", "Capture Card"),
+            camera("/dev/video2", "Webcam"),
+            camera("/dev/video5", "Capture Card"),
+        ];
+
+        let groups = group_cameras(cameras, |path| match path {
+            "/dev/video4" | "/dev/video5" => "pci-0000:03:00.0".to_string(),
+            p => p.to_string(),
+        });
+
+        let nodes: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|g| (g.name.as_str(), g.nodes.iter().map(|n| n.path.as_str()).collect()))
+            .collect();
+        assert_eq!(nodes, vec![
+            ("Webcam", vec!["/dev/video0"]),
+            ("Capture Card", vec!["/dev/video4", "/dev/video5"]),
+            ("Webcam", vec!["/dev/video2"]),
+        ]);
+    }
+}
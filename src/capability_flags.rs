@@ -0,0 +1,192 @@
+use std::{io, mem, os::raw::c_int};
+
+use v4l::v4l2;
+use v4l::v4l_sys::v4l2_capability;
+
+/// A decoded flag with an explanation for users.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flag {
+    pub name: String,
+    pub description: String,
+    /// Whether the flag matters to users, e.g. for choosing a mode
+    pub highlight: bool,
+}
+
+struct FlagDefinition {
+    bit: u32,
+    name: &'static str,
+    description: &'static str,
+    highlight: bool,
+}
+
+const fn flag(bit: u32, name: &'static str, description: &'static str, highlight: bool) -> FlagDefinition {
+    FlagDefinition {
+        bit,
+        name,
+        description,
+        highlight,
+    }
+}
+
+// See V4L2_CAP_* in linux/videodev2.h
+const CAPABILITIES: [FlagDefinition; 30] = [
+    flag(0x0000_0001, "Video capture", "Captures video frames", true),
+    flag(0x0000_0002, "Video output", "Outputs video frames", false),
+    flag(0x0000_0004, "Video overlay", "Overlays video onto the screen", false),
+    flag(0x0000_0010, "VBI capture", "Captures raw vertical blanking data, e.g. teletext", false),
+    flag(0x0000_0020, "VBI output", "Outputs raw vertical blanking data", false),
+    flag(0x0000_0040, "Sliced VBI capture", "Captures decoded vertical blanking data", false),
+    flag(0x0000_0080, "Sliced VBI output", "Outputs decoded vertical blanking data", false),
+    flag(0x0000_0100, "RDS capture", "Captures radio data system information", false),
+    flag(0x0000_0200, "Output overlay", "Overlays onto the video output", false),
+    flag(0x0000_0400, "Hardware seek", "Seeks radio frequencies in hardware", false),
+    flag(0x0000_0800, "RDS output", "Outputs radio data system information", false),
+    flag(0x0000_1000, "Multi-planar capture", "Captures frames with separate planes", true),
+    flag(0x0000_2000, "Multi-planar output", "Outputs frames with separate planes", false),
+    flag(0x0000_4000, "Multi-planar memory-to-memory", "Processes frames with separate planes, e.g. a codec", false),
+    flag(0x0000_8000, "Memory-to-memory", "Processes frames, e.g. a codec or scaler", false),
+    flag(0x0001_0000, "Tuner", "Has a tuner for receiving signals", false),
+    flag(0x0002_0000, "Audio", "Has audio inputs or outputs", false),
+    flag(0x0004_0000, "Radio", "Receives or transmits radio", false),
+    flag(0x0008_0000, "Modulator", "Has a modulator for transmitting signals", false),
+    flag(0x0010_0000, "SDR capture", "Captures software defined radio data", false),
+    flag(0x0020_0000, "Extended pixel format", "Supports colorspace and quantization in the format", false),
+    flag(0x0040_0000, "SDR output", "Outputs software defined radio data", false),
+    flag(0x0080_0000, "Metadata capture", "Captures metadata of frames, e.g. UVC timestamps", false),
+    flag(0x0100_0000, "Read/write I/O", "Frames can be read like a file", false),
+    flag(0x0200_0000, "Asynchronous I/O", "Frames can be read asynchronously", false),
+    flag(0x0400_0000, "Streaming I/O", "Frames are exchanged through buffers, as used by most applications", true),
+    flag(0x0800_0000, "Metadata output", "Outputs metadata of frames", false),
+    flag(0x1000_0000, "Touch", "Captures touch sensor data", false),
+    flag(0x2000_0000, "Media controller I/O", "Inputs and outputs are configured through the media controller", false),
+    flag(0x8000_0000, "Per-node capabilities", "The driver reports capabilities per device-node", false),
+];
+
+// See V4L2_FMT_FLAG_* in linux/videodev2.h
+const FORMAT_FLAGS: [FlagDefinition; 4] = [
+    flag(0x0001, "Compressed", "Frames are compressed, e.g. as JPEG, and need to be decoded", true),
+    flag(0x0002, "Emulated format", "Converted in software by libv4l, which costs CPU time", true),
+    flag(0x0004, "Continuous bitstream", "The data is not split into frames", false),
+    flag(0x0008, "Dynamic resolution", "The resolution may change while streaming", false),
+];
+
+// See V4L2_CAP_TIMEPERFRAME and V4L2_MODE_HIGHQUALITY in linux/videodev2.h
+const STREAM_CAPABILITIES: [FlagDefinition; 1] = [flag(
+    0x1000,
+    "Time per frame",
+    "The frame rate can be selected",
+    true,
+)];
+const STREAM_MODES: [FlagDefinition; 1] = [flag(
+    0x0001,
+    "High quality",
+    "Captures in high quality, e.g. for still images",
+    false,
+)];
+
+/// Capabilities of the whole driver and of the opened device-node, as
+/// reported by `VIDIOC_QUERYCAP`.
+pub struct CapabilityFlags {
+    pub driver: u32,
+    pub device: u32,
+}
+
+/// The v4l-crate only reports the capabilities of the device-node.
+pub fn query_capability_flags(fd: c_int) -> io::Result<CapabilityFlags> {
+    let mut caps: v4l2_capability = unsafe { mem::zeroed() };
+
+    unsafe {
+        v4l2::ioctl(
+            fd,
+            v4l2::vidioc::VIDIOC_QUERYCAP,
+            &mut caps as *mut _ as *mut std::os::raw::c_void,
+        )?;
+    }
+
+    // Drivers without per-node capabilities report the same for both
+    let device = match caps.capabilities & 0x8000_0000 {
+        0 => caps.capabilities,
+        _ => caps.device_caps,
+    };
+
+    Ok(CapabilityFlags {
+        driver: caps.capabilities,
+        device,
+    })
+}
+
+fn decode(bits: u32, definitions: &[FlagDefinition]) -> Vec<Flag> {
+    let mut flags: Vec<Flag> = definitions
+        .iter()
+        .filter(|d| bits & d.bit != 0)
+        .map(|d| Flag {
+            name: d.name.to_string(),
+            description: d.description.to_string(),
+            highlight: d.highlight,
+        })
+        .collect();
+
+    let known = definitions.iter().fold(0, |known, d| known | d.bit);
+    if bits & !known != 0 {
+        flags.push(Flag {
+            name: format!("Unknown 0x{:08x}", bits & !known),
+            description: "Not known to this application".to_string(),
+            highlight: false,
+        });
+    }
+
+    flags
+}
+
+pub fn decode_capabilities(bits: u32) -> Vec<Flag> {
+    decode(bits, &CAPABILITIES)
+}
+
+pub fn decode_format_flags(bits: u32) -> Vec<Flag> {
+    decode(bits, &FORMAT_FLAGS)
+}
+
+/// Includes a note, if the frame rate cannot be selected, as users would
+/// expect otherwise.
+pub fn decode_stream_capabilities(bits: u32) -> Vec<Flag> {
+    let mut flags = decode(bits, &STREAM_CAPABILITIES);
+
+    if bits & STREAM_CAPABILITIES[0].bit == 0 {
+        flags.insert(
+            0,
+            Flag {
+                name: "Fixed frame rate".to_string(),
+                description: "The frame rate cannot be selected, it depends on the format".to_string(),
+                highlight: true,
+            },
+        );
+    }
+
+    flags
+}
+
+pub fn decode_stream_modes(bits: u32) -> Vec<Flag> {
+    decode(bits, &STREAM_MODES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(flags: Vec<Flag>) -> Vec<String> {
+        flags.into_iter().map(|f| f.name).collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            names(decode_capabilities(0x8420_0001)),
+            ["Video capture", "Extended pixel format", "Streaming I/O", "Per-node capabilities"]
+        );
+        assert_eq!(names(decode_capabilities(0x2000_0000)), ["Media controller I/O"]);
+        assert_eq!(names(decode_format_flags(0x0012)), ["Emulated format", "Unknown 0x00000010"]);
+        assert_eq!(names(decode_stream_capabilities(0)), ["Fixed frame rate"]);
+        assert_eq!(names(decode_stream_capabilities(0x1000)), ["Time per frame"]);
+        assert!(decode_stream_modes(0).is_empty());
+    }
+}
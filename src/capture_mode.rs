@@ -0,0 +1,299 @@
+use std::{fmt, io};
+
+use v4l::{
+    format::FourCC,
+    frameinterval::FrameIntervalEnum,
+    framesize::FrameSizeEnum,
+    fraction::Fraction,
+    video::{capture::Parameters, Capture},
+    Device,
+};
+
+// Offered within the range of stepwise and continuous frame sizes
+const COMMON_RESOLUTIONS: [(u32, u32); 12] = [
+    (160, 120),
+    (320, 240),
+    (640, 360),
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1280, 960),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+    (4096, 2160),
+];
+
+// Offered within the range of stepwise and continuous frame intervals
+const COMMON_FRAME_RATES: [u32; 10] = [5, 10, 15, 20, 24, 25, 30, 50, 60, 120];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}×{}", self.width, self.height)
+    }
+}
+
+/// Time between two frames in seconds, as a fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl Interval {
+    pub fn from_fps(fps: u32) -> Self {
+        Interval {
+            numerator: 1,
+            denominator: fps,
+        }
+    }
+
+    pub fn fps(&self) -> f64 {
+        if self.numerator == 0 {
+            return 0.0;
+        }
+
+        self.denominator as f64 / self.numerator as f64
+    }
+}
+
+impl From<Fraction> for Interval {
+    fn from(fraction: Fraction) -> Self {
+        Interval {
+            numerator: fraction.numerator,
+            denominator: fraction.denominator,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fps = self.fps();
+        if fps.fract() == 0.0 {
+            write!(f, "{} fps", fps)
+        } else {
+            write!(f, "{:.2} fps", fps)
+        }
+    }
+}
+
+/// A pixel format with its frame sizes and their frame intervals.
+#[derive(Debug, Clone)]
+pub struct FormatModes {
+    pub fourcc: FourCC,
+    pub description: String,
+    pub sizes: Vec<SizeModes>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SizeModes {
+    pub resolution: Resolution,
+    pub intervals: Vec<Interval>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureMode {
+    pub fourcc: FourCC,
+    pub resolution: Resolution,
+    /// Not every driver supports setting the frame interval
+    pub interval: Option<Interval>,
+}
+
+impl fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.fourcc, self.resolution)?;
+        if let Some(interval) = self.interval {
+            write!(f, " at {}", interval)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists all capture modes of the device by pixel format and frame size.
+pub fn list_capture_modes(device: &Device) -> io::Result<Vec<FormatModes>> {
+    let mut formats = vec![];
+
+    for description in device.enum_formats()? {
+        let mut sizes: Vec<SizeModes> = list_resolutions(device, description.fourcc)
+            .into_iter()
+            .map(|resolution| SizeModes {
+                resolution,
+                intervals: list_intervals(device, description.fourcc, resolution),
+            })
+            .collect();
+
+        // Largest first, like most applications
+        sizes.sort_by_key(|s| std::cmp::Reverse(s.resolution));
+
+        formats.push(FormatModes {
+            fourcc: description.fourcc,
+            description: description.description,
+            sizes,
+        });
+    }
+
+    Ok(formats)
+}
+
+fn list_resolutions(device: &Device, fourcc: FourCC) -> Vec<Resolution> {
+    let frame_sizes = match device.enum_framesizes(fourcc) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error listing frame sizes of {}: {}", fourcc, e);
+            return vec![];
+        }
+    };
+
+    let mut resolutions = vec![];
+    for frame_size in frame_sizes {
+        match frame_size.size {
+            FrameSizeEnum::Discrete(d) => resolutions.push(Resolution {
+                width: d.width,
+                height: d.height,
+            }),
+            FrameSizeEnum::Stepwise(s) => resolutions.append(&mut stepwise_resolutions(
+                (s.min_width, s.max_width, s.step_width),
+                (s.min_height, s.max_height, s.step_height),
+            )),
+        };
+    }
+
+    resolutions.sort();
+    resolutions.dedup();
+    resolutions
+}
+
+fn list_intervals(device: &Device, fourcc: FourCC, resolution: Resolution) -> Vec<Interval> {
+    let frame_intervals = match device.enum_frameintervals(fourcc, resolution.width, resolution.height) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("Error listing frame intervals of {} {}: {}", fourcc, resolution, e);
+            return vec![];
+        }
+    };
+
+    let mut intervals = vec![];
+    for frame_interval in frame_intervals {
+        match frame_interval.interval {
+            FrameIntervalEnum::Discrete(f) => intervals.push(Interval::from(f)),
+            FrameIntervalEnum::Stepwise(s) => {
+                intervals.append(&mut stepwise_intervals(Interval::from(s.min), Interval::from(s.max)))
+            }
+        };
+    }
+
+    // Highest frame rate first
+    intervals.sort_by(|a, b| b.fps().total_cmp(&a.fps()));
+    intervals.dedup();
+    intervals
+}
+
+/// The bounds and the common resolutions in between, which fit the steps.
+fn stepwise_resolutions(width: (u32, u32, u32), height: (u32, u32, u32)) -> Vec<Resolution> {
+    let fits = |value: u32, (min, max, step): (u32, u32, u32)| {
+        value >= min && value <= max && (value - min) % step.max(1) == 0
+    };
+
+    let mut resolutions = vec![Resolution {
+        width: width.0,
+        height: height.0,
+    }];
+
+    resolutions.extend(
+        COMMON_RESOLUTIONS
+            .iter()
+            .filter(|(w, h)| fits(*w, width) && fits(*h, height))
+            .map(|(w, h)| Resolution { width: *w, height: *h }),
+    );
+
+    resolutions.push(Resolution {
+        width: width.1,
+        height: height.1,
+    });
+
+    resolutions.sort();
+    resolutions.dedup();
+    resolutions
+}
+
+/// The bounds and the common frame rates in between, highest frame rate
+/// first. The minimum interval is the maximum frame rate.
+fn stepwise_intervals(min: Interval, max: Interval) -> Vec<Interval> {
+    let (max_fps, min_fps) = (min.fps(), max.fps());
+
+    let mut intervals = vec![min];
+    intervals.extend(
+        COMMON_FRAME_RATES
+            .iter()
+            .filter(|fps| **fps as f64 > min_fps && (**fps as f64) < max_fps)
+            .map(|fps| Interval::from_fps(*fps)),
+    );
+    intervals.push(max);
+
+    intervals.sort_by(|a, b| b.fps().total_cmp(&a.fps()));
+    intervals
+}
+
+/// Reads the mode, the device is currently set to.
+pub fn get_capture_mode(device: &Device) -> io::Result<CaptureMode> {
+    let format = device.format()?;
+
+    Ok(CaptureMode {
+        fourcc: format.fourcc,
+        resolution: Resolution {
+            width: format.width,
+            height: format.height,
+        },
+        interval: device.params().ok().map(|p| Interval::from(p.interval)),
+    })
+}
+
+/// Sets the mode with `S_FMT` and `S_PARM`. Returns the mode accepted by the
+/// driver, which may differ from the requested one.
+pub fn set_capture_mode(device: &Device, mode: &CaptureMode) -> io::Result<CaptureMode> {
+    let mut format = device.format()?;
+    format.fourcc = mode.fourcc;
+    format.width = mode.resolution.width;
+    format.height = mode.resolution.height;
+    device.set_format(&format)?;
+
+    if let Some(interval) = mode.interval {
+        let fraction = Fraction::new(interval.numerator, interval.denominator);
+        if let Err(e) = device.set_params(&Parameters::new(fraction)) {
+            eprintln!("Error setting frame interval {}: {}", interval, e);
+        }
+    }
+
+    get_capture_mode(device)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stepwise_resolutions() {
+        let resolutions = stepwise_resolutions((320, 1920, 16), (240, 1080, 8));
+        let labels: Vec<String> = resolutions.iter().map(|r| r.to_string()).collect();
+
+        assert_eq!(
+            labels,
+            ["320×240", "640×360", "640×480", "800×600", "1024×768", "1280×720", "1280×960", "1920×1080"]
+        );
+    }
+
+    #[test]
+    fn test_stepwise_intervals() {
+        let intervals = stepwise_intervals(Interval::from_fps(30), Interval { numerator: 2, denominator: 15 });
+        let labels: Vec<String> = intervals.iter().map(|i| i.to_string()).collect();
+
+        assert_eq!(labels, ["30 fps", "25 fps", "24 fps", "20 fps", "15 fps", "10 fps", "7.50 fps"]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use toml::{Table, Value};
+
+use crate::files::{load_config, parse_config, save_config};
+
+const SETTINGS_FILE_NAME: &str = "capture.toml";
+const DESCRIPTION: &str = "capture settings";
+const DEFAULT_FOLDER_NAME: &str = "Camera Settings";
+
+/// File format of captured stills.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StillFormat {
+    Png,
+    Jpeg,
+}
+
+impl StillFormat {
+    pub const ALL: [StillFormat; 2] = [StillFormat::Png, StillFormat::Jpeg];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StillFormat::Png => "png",
+            StillFormat::Jpeg => "jpg",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StillFormat::Png => "PNG",
+            StillFormat::Jpeg => "JPEG",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        StillFormat::ALL.into_iter().find(|f| f.extension() == extension)
+    }
+}
+
+/// Where and how captures are saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureSettings {
+    pub folder: PathBuf,
+    pub still_format: StillFormat,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        let pictures = glib::user_special_dir(glib::UserDirectory::Pictures).unwrap_or_else(glib::home_dir);
+
+        CaptureSettings {
+            folder: pictures.join(DEFAULT_FOLDER_NAME),
+            still_format: StillFormat::Png,
+        }
+    }
+}
+
+impl CaptureSettings {
+    /// Loads the stored settings. Missing or invalid values are replaced by
+    /// their defaults.
+    pub fn load() -> Self {
+        load_config(SETTINGS_FILE_NAME, DESCRIPTION)
+            .map_or_else(CaptureSettings::default, |t| CaptureSettings::from_table(&t))
+    }
+
+    pub fn save(&self) {
+        save_config(SETTINGS_FILE_NAME, DESCRIPTION, &self.to_table());
+    }
+
+    pub fn parse(content: &str) -> Self {
+        parse_config(content, DESCRIPTION)
+            .map_or_else(CaptureSettings::default, |t| CaptureSettings::from_table(&t))
+    }
+
+    pub fn to_toml(&self) -> String {
+        self.to_table().to_string()
+    }
+
+    fn from_table(table: &Table) -> Self {
+        let defaults = CaptureSettings::default();
+
+        CaptureSettings {
+            folder: table
+                .get("folder")
+                .and_then(|v| v.as_str())
+                .filter(|f| !f.is_empty())
+                .map_or(defaults.folder, PathBuf::from),
+            still_format: table
+                .get("still_format")
+                .and_then(|v| v.as_str())
+                .and_then(StillFormat::from_extension)
+                .unwrap_or(defaults.still_format),
+        }
+    }
+
+    fn to_table(&self) -> Table {
+        let mut table = Table::new();
+
+        table.insert("folder".to_string(), Value::String(self.folder.display().to_string()));
+        table.insert("still_format".to_string(), Value::String(self.still_format.extension().to_string()));
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_settings_roundtrip() {
+        let settings = CaptureSettings {
+            folder: PathBuf::from("/home/user/Captures"),
+            still_format: StillFormat::Jpeg,
+        };
+
+        assert_eq!(CaptureSettings::parse(&settings.to_toml()), settings);
+
+        let invalid = CaptureSettings::parse("folder = \"\"\nstill_format = \"bmp\"");
+        assert_eq!(invalid, CaptureSettings::default());
+    }
+}
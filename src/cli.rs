@@ -0,0 +1,600 @@
+use std::collections::HashSet;
+use std::io;
+
+use v4l::{control::{Description, Flags, Type}, Device};
+
+use crate::{
+    daemon,
+    device_info::query_device_info,
+    files::{get_device_id, get_video_devices, resolve_device_id},
+    json::JsonValue,
+    profiles::read_control_value,
+    quirks::DeviceQuirks,
+};
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NO_DEVICE: i32 = 3;
+const EXIT_BUSY: i32 = 4;
+const EXIT_INVALID_VALUE: i32 = 5;
+const EXIT_NO_CONTROL: i32 = 6;
+
+const SUBCOMMANDS: [&str; 8] = [
+    "daemon",
+    "get",
+    "help",
+    "info",
+    "list-controls",
+    "list-devices",
+    "reset",
+    "set",
+];
+
+const USAGE: &str = "Usage: v4l2-gui [COMMAND] [--json]
+
+Without a command, the graphical interface is started.
+
+Commands:
+  list-devices                      List all video devices
+  info DEVICE                       Show capabilities, parameters and formats
+  list-controls DEVICE              List all controls with their values
+  get DEVICE CONTROL...             Print the values of the controls
+  set DEVICE CONTROL=VALUE...       Set the controls, in the given order after the quirks
+  reset DEVICE [CONTROL...]         Reset the controls, or all, to their defaults
+  daemon [--enforce]                Apply bound profiles, when cameras appear
+
+DEVICE is a path like /dev/video0 or a stable id from /dev/v4l/by-id.
+CONTROL is the id or the name of a control, like brightness or 0x00980900.
+
+Exit codes:
+  1 general error, 2 wrong usage, 3 device not found, 4 device busy,
+  5 invalid or out-of-range value, 6 control not found";
+
+struct CliError {
+    message: String,
+    code: i32,
+}
+
+impl CliError {
+    fn new(code: i32, message: String) -> Self {
+        CliError { message, code }
+    }
+
+    fn from_io(context: &str, error: io::Error) -> Self {
+        let code = match error.raw_os_error() {
+            Some(libc::EBUSY) => EXIT_BUSY,
+            Some(libc::ENOENT) | Some(libc::ENODEV) | Some(libc::ENXIO) => EXIT_NO_DEVICE,
+            Some(libc::EINVAL) | Some(libc::ERANGE) => EXIT_INVALID_VALUE,
+            _ => EXIT_FAILURE,
+        };
+
+        CliError::new(code, format!("{}: {}", context, error))
+    }
+}
+
+type CliResult = Result<(), CliError>;
+
+pub fn is_subcommand(arg: &str) -> bool {
+    SUBCOMMANDS.contains(&arg)
+}
+
+/// Runs a command without graphical interface. `args` start with the name of
+/// the command.
+pub fn run(args: &[String]) -> glib::ExitCode {
+    let json = args.iter().any(|a| a == "--json");
+    let params: Vec<&str> = args[1..]
+        .iter()
+        .filter(|a| a.as_str() != "--json")
+        .map(|a| a.as_str())
+        .collect();
+
+    let result = match args[0].as_str() {
+        "daemon" => return daemon::run(&args[1..]),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        "list-devices" => list_devices(json),
+        "info" => with_device(&params, |path, _| info(path, json)),
+        "list-controls" => with_device(&params, |path, _| list_controls(path, json)),
+        "get" => with_device(&params, |path, controls| get(path, controls, json)),
+        "set" => with_device(&params, set),
+        "reset" => with_device(&params, reset),
+        _ => Err(CliError::new(EXIT_USAGE, USAGE.to_string())),
+    };
+
+    exit_code(result)
+}
+
+/// Reports the error and turns it into the exit status of the process.
+fn exit_code(result: CliResult) -> glib::ExitCode {
+    match result {
+        Ok(_) => glib::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e.message);
+            glib::ExitCode::from(e.code)
+        }
+    }
+}
+
+/// Resolves the device of the first parameter and calls `command` with its
+/// path and the remaining parameters.
+fn with_device(params: &[&str], command: impl Fn(&str, &[&str]) -> CliResult) -> CliResult {
+    let device = match params.first() {
+        Some(d) => d,
+        None => return Err(CliError::new(EXIT_USAGE, USAGE.to_string())),
+    };
+
+    match resolve_device_id(device) {
+        Some(path) => command(&path, &params[1..]),
+        None => Err(CliError::new(EXIT_NO_DEVICE, format!("No device found for {}", device))),
+    }
+}
+
+fn open_device(device_path: &str) -> Result<Device, CliError> {
+    Device::with_path(device_path)
+        .map_err(|e| CliError::from_io(&format!("Error opening {}", device_path), e))
+}
+
+fn list_devices(json: bool) -> CliResult {
+    let mut paths = get_video_devices("/dev");
+    paths.sort();
+
+    let mut devices = vec![];
+    for path in paths {
+        let (card, driver) = match Device::with_path(&path).and_then(|d| d.query_caps()) {
+            Ok(caps) => (caps.card, caps.driver),
+            Err(e) => (format!("Unreadable: {}", e), String::new()),
+        };
+
+        if json {
+            devices.push(JsonValue::object(vec![
+                ("path", JsonValue::string(&path)),
+                ("id", JsonValue::string(&get_device_id(&path))),
+                ("card", JsonValue::String(card)),
+                ("driver", JsonValue::String(driver)),
+            ]));
+        } else {
+            println!("{}\t{}\t{}", path, card, get_device_id(&path));
+        }
+    }
+
+    if json {
+        println!("{}", JsonValue::Array(devices));
+    }
+
+    Ok(())
+}
+
+fn info(device_path: &str, json: bool) -> CliResult {
+    // Fail with the proper exit code, device-info only reports errors as text
+    open_device(device_path)?;
+
+    let sections = query_device_info(device_path);
+
+    if json {
+        let value = sections
+            .into_iter()
+            .map(|s| {
+                let flags = s.flags.into_iter().map(|(label, flags)| {
+                    let names = flags.into_iter().map(|f| JsonValue::String(f.name)).collect();
+                    (label, JsonValue::Array(names))
+                });
+                let entries = s
+                    .entries
+                    .into_iter()
+                    .map(|(label, value)| (label, JsonValue::String(value)))
+                    .chain(flags)
+                    .collect();
+                (s.title, JsonValue::Object(entries))
+            })
+            .collect();
+        println!("{}", JsonValue::Object(value));
+        return Ok(());
+    }
+
+    for section in sections {
+        println!("{}", section.title);
+        for (label, value) in section.entries {
+            println!("  {}: {}", label, value.replace('\n', ", "));
+        }
+        for (label, flags) in section.flags {
+            let names: Vec<String> = flags.into_iter().map(|f| f.name).collect();
+            println!("  {}: {}", label, names.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn list_controls(device_path: &str, json: bool) -> CliResult {
+    let device = open_device(device_path)?;
+    let descriptions = query_controls(&device)?;
+
+    let mut controls = vec![];
+    let mut class = String::new();
+    for desc in descriptions.iter() {
+        if desc.typ == Type::CtrlClass {
+            class = desc.name.clone();
+            if !json {
+                println!("{}", desc.name);
+            }
+            continue;
+        }
+
+        let value = read_control_value(&device, desc).ok();
+
+        if json {
+            controls.push(control_to_json(desc, &class, value));
+        } else {
+            println!("  {}", format_control(desc, value));
+        }
+    }
+
+    if json {
+        println!("{}", JsonValue::Array(controls));
+    }
+
+    Ok(())
+}
+
+fn get(device_path: &str, controls: &[&str], json: bool) -> CliResult {
+    if controls.is_empty() {
+        return Err(CliError::new(EXIT_USAGE, USAGE.to_string()));
+    }
+
+    let device = open_device(device_path)?;
+    let descriptions = query_controls(&device)?;
+
+    let mut values = vec![];
+    for control in controls {
+        let desc = find_control(&descriptions, control)?;
+        let value = read_control_value(&device, desc)
+            .map_err(|e| CliError::new(EXIT_FAILURE, e.message))?;
+
+        if json {
+            values.push((control_key(&desc.name), JsonValue::Integer(value)));
+        } else {
+            println!("{}={}", control_key(&desc.name), value);
+        }
+    }
+
+    if json {
+        println!("{}", JsonValue::Object(values));
+    }
+
+    Ok(())
+}
+
+fn set(device_path: &str, assignments: &[&str]) -> CliResult {
+    if assignments.is_empty() {
+        return Err(CliError::new(EXIT_USAGE, USAGE.to_string()));
+    }
+
+    let device = open_device(device_path)?;
+    let quirks = DeviceQuirks::for_device(device_path, &device);
+    let (descriptions, hidden) = query_controls_with_quirks(&device, &quirks)?;
+
+    // All values are checked, before any is written
+    let mut values = vec![];
+    for assignment in assignments {
+        let (control, value) = match assignment.split_once('=') {
+            Some(a) => a,
+            None => return Err(CliError::new(
+                EXIT_USAGE,
+                format!("Expected CONTROL=VALUE, got {}", assignment)
+            )),
+        };
+
+        let desc = find_visible_control(&descriptions, &hidden, control)?;
+        values.push((desc, parse_value(desc, value)?));
+    }
+
+    write_values(&device, &quirks, values)
+}
+
+fn reset(device_path: &str, controls: &[&str]) -> CliResult {
+    let device = open_device(device_path)?;
+    let quirks = DeviceQuirks::for_device(device_path, &device);
+    let (descriptions, hidden) = query_controls_with_quirks(&device, &quirks)?;
+
+    let selected: Vec<&Description> = if controls.is_empty() {
+        descriptions
+            .iter()
+            .filter(|d| is_writable(d) && !hidden.contains(&d.id))
+            .collect()
+    } else {
+        controls
+            .iter()
+            .map(|c| find_visible_control(&descriptions, &hidden, c))
+            .collect::<Result<_, _>>()?
+    };
+
+    let values = selected.into_iter().map(|d| (d, d.default)).collect();
+    write_values(&device, &quirks, values)
+}
+
+/// The descriptions with the overrides of the quirks, and the ids of the
+/// controls hidden by them.
+fn query_controls_with_quirks(
+    device: &Device,
+    quirks: &DeviceQuirks,
+) -> Result<(Vec<Description>, HashSet<u32>), CliError> {
+    let mut descriptions = query_controls(device)?;
+
+    let hidden = descriptions
+        .iter_mut()
+        .filter_map(|d| (!quirks.apply(d)).then_some(d.id))
+        .collect();
+
+    Ok((descriptions, hidden))
+}
+
+/// Writes the values in the write order of the quirks. All values are tried,
+/// the errors are reported and the last is returned for the exit code.
+fn write_values(device: &Device, quirks: &DeviceQuirks, mut values: Vec<(&Description, i64)>) -> CliResult {
+    values.sort_by_key(|(d, _)| quirks.write_position(d.id));
+
+    let mut errors: Vec<CliError> = values
+        .into_iter()
+        .filter_map(|(d, v)| write_value(device, d, v).err())
+        .collect();
+
+    match errors.pop() {
+        Some(last) => {
+            for e in errors {
+                eprintln!("{}", e.message);
+            }
+            Err(last)
+        }
+        None => Ok(()),
+    }
+}
+
+fn query_controls(device: &Device) -> Result<Vec<Description>, CliError> {
+    match device.query_controls() {
+        Ok(d) => Ok(d
+            .into_iter()
+            .filter(|d| !d.flags.contains(Flags::DISABLED))
+            .collect()),
+        Err(e) => Err(CliError::from_io("Error querying controls", e)),
+    }
+}
+
+fn find_control<'a>(descriptions: &'a [Description], control: &str) -> Result<&'a Description, CliError> {
+    let id = parse_control_id(control);
+    let key = control_key(control);
+
+    descriptions
+        .iter()
+        .filter(|d| d.typ != Type::CtrlClass)
+        .find(|d| Some(d.id) == id || control_key(&d.name) == key)
+        .ok_or_else(|| CliError::new(EXIT_NO_CONTROL, format!("No control {}", control)))
+}
+
+/// Like `find_control`, but controls hidden by the quirks, as in the panel,
+/// are not written.
+fn find_visible_control<'a>(
+    descriptions: &'a [Description],
+    hidden: &HashSet<u32>,
+    control: &str,
+) -> Result<&'a Description, CliError> {
+    let desc = find_control(descriptions, control)?;
+
+    match hidden.contains(&desc.id) {
+        true => Err(CliError::new(EXIT_NO_CONTROL, format!("{} is hidden by the quirks of the device", desc.name))),
+        false => Ok(desc),
+    }
+}
+
+fn is_writable(desc: &Description) -> bool {
+    let not_writable = Flags::READ_ONLY | Flags::INACTIVE | Flags::GRABBED;
+
+    !desc.flags.intersects(not_writable)
+        && !matches!(desc.typ, Type::Button | Type::CtrlClass | Type::String | Type::Area)
+}
+
+fn write_value(device: &Device, desc: &Description, value: i64) -> CliResult {
+    if desc.flags.contains(Flags::READ_ONLY) {
+        return Err(CliError::new(EXIT_INVALID_VALUE, format!("{} is read-only", desc.name)));
+    }
+
+    // Not using the profiles-module here, to keep the OS-error for the exit code
+    let new_value = match desc.typ {
+        Type::Boolean => v4l::control::Value::Boolean(value != 0),
+        _ => v4l::control::Value::Integer(value),
+    };
+
+    device
+        .set_control(v4l::control::Control { id: desc.id, value: new_value })
+        .map_err(|e| CliError::from_io(&format!("Error setting {}", desc.name), e))
+}
+
+/// Parses integers, booleans and labels of menu-items and checks the range
+/// and the step.
+fn parse_value(desc: &Description, value: &str) -> Result<i64, CliError> {
+    let menu_value = desc.items.as_ref().and_then(|items| {
+        items
+            .iter()
+            .find(|(_, item)| control_key(&item.to_string()) == control_key(value))
+            .map(|(k, _)| *k as i64)
+    });
+
+    let parsed = match (value, menu_value) {
+        (_, Some(v)) => v,
+        ("true", _) | ("on", _) => 1,
+        ("false", _) | ("off", _) => 0,
+        (v, _) => v.parse::<i64>().map_err(|_| {
+            CliError::new(EXIT_INVALID_VALUE, format!("Invalid value {} for {}", v, desc.name))
+        })?,
+    };
+
+    let in_menu = match &desc.items {
+        Some(items) => items.iter().any(|(k, _)| *k as i64 == parsed),
+        None => true,
+    };
+
+    if parsed < desc.minimum || parsed > desc.maximum || !in_menu {
+        return Err(CliError::new(EXIT_INVALID_VALUE, format!(
+            "Value {} is out of range for {} ({} to {})",
+            parsed, desc.name, desc.minimum, desc.maximum
+        )));
+    }
+
+    if desc.items.is_none() && desc.step > 1 && (parsed - desc.minimum) as u64 % desc.step != 0 {
+        return Err(CliError::new(EXIT_INVALID_VALUE, format!(
+            "Value {} of {} is not a step of {} from {}",
+            parsed, desc.name, desc.step, desc.minimum
+        )));
+    }
+
+    Ok(parsed)
+}
+
+fn parse_control_id(control: &str) -> Option<u32> {
+    match control.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => control.parse::<u32>().ok(),
+    }
+}
+
+/// Turns a name like "White Balance, Automatic" into "white_balance_automatic".
+fn control_key(name: &str) -> String {
+    let mut key = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            key.extend(c.to_lowercase());
+        } else if !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+
+    key.trim_end_matches('_').to_string()
+}
+
+fn flag_names(flags: Flags) -> Vec<&'static str> {
+    let names = [
+        (Flags::READ_ONLY, "read-only"),
+        (Flags::WRITE_ONLY, "write-only"),
+        (Flags::INACTIVE, "inactive"),
+        (Flags::GRABBED, "grabbed"),
+        (Flags::VOLATILE, "volatile"),
+        (Flags::UPDATE, "update"),
+        (Flags::SLIDER, "slider"),
+        (Flags::EXECUTE_ON_WRITE, "execute-on-write"),
+    ];
+
+    names
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn format_control(desc: &Description, value: Option<i64>) -> String {
+    let mut line = format!(
+        "{} (0x{:08x}): {} min={} max={} step={} default={}",
+        control_key(&desc.name),
+        desc.id,
+        desc.typ,
+        desc.minimum,
+        desc.maximum,
+        desc.step,
+        desc.default
+    );
+
+    if let Some(v) = value {
+        line.push_str(&format!(" value={}", v));
+    }
+
+    let flags = flag_names(desc.flags);
+    if !flags.is_empty() {
+        line.push_str(&format!(" flags={}", flags.join(",")));
+    }
+
+    if let Some(items) = &desc.items {
+        for (k, item) in items {
+            line.push_str(&format!("\n      {}: {}", k, item));
+        }
+    }
+
+    line
+}
+
+fn control_to_json(desc: &Description, class: &str, value: Option<i64>) -> JsonValue {
+    let menu = desc.items.as_ref().map(|items| {
+        JsonValue::Array(
+            items
+                .iter()
+                .map(|(k, item)| JsonValue::object(vec![
+                    ("value", JsonValue::Integer(*k as i64)),
+                    ("label", JsonValue::String(item.to_string())),
+                ]))
+                .collect(),
+        )
+    });
+
+    JsonValue::object(vec![
+        ("id", JsonValue::Integer(desc.id as i64)),
+        ("key", JsonValue::String(control_key(&desc.name))),
+        ("name", JsonValue::string(&desc.name)),
+        ("class", JsonValue::string(class)),
+        ("type", JsonValue::String(desc.typ.to_string())),
+        ("minimum", JsonValue::Integer(desc.minimum)),
+        ("maximum", JsonValue::Integer(desc.maximum)),
+        ("step", JsonValue::Integer(desc.step as i64)),
+        ("default", JsonValue::Integer(desc.default)),
+        ("value", value.map(JsonValue::Integer).unwrap_or(JsonValue::Null)),
+        ("flags", JsonValue::Array(
+            flag_names(desc.flags).into_iter().map(JsonValue::string).collect()
+        )),
+        ("menu", menu.unwrap_or(JsonValue::Null)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_key() {
+        assert_eq!(control_key("Brightness"), "brightness");
+        assert_eq!(control_key("White Balance, Automatic"), "white_balance_automatic");
+        assert_eq!(control_key(" Exposure Time (Absolute) "), "exposure_time_absolute");
+    }
+
+    #[test]
+    fn test_parse_control_id() {
+        assert_eq!(parse_control_id("0x00980900"), Some(0x00980900));
+        assert_eq!(parse_control_id("9963776"), Some(9963776));
+        assert_eq!(parse_control_id("brightness"), None);
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let desc = Description {
+            id: 0x00980900,
+            typ: Type::Integer,
+            name: "Brightness".to_string(),
+            minimum: -10,
+            maximum: 100,
+            step: 5,
+            default: 0,
+            flags: Flags::empty(),
+            items: None,
+        };
+
+        assert_eq!(parse_value(&desc, "15").ok(), Some(15));
+        assert_eq!(parse_value(&desc, "12").err().map(|e| e.code), Some(EXIT_INVALID_VALUE));
+        assert_eq!(parse_value(&desc, "105").err().map(|e| e.code), Some(EXIT_INVALID_VALUE));
+        assert_eq!(parse_value(&desc, "bright").err().map(|e| e.code), Some(EXIT_INVALID_VALUE));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(i32::from(exit_code(Ok(()))), 0);
+
+        let busy = CliError::from_io("Error opening /dev/video0", io::Error::from_raw_os_error(libc::EBUSY));
+        assert_eq!(i32::from(exit_code(Err(busy))), EXIT_BUSY);
+    }
+}
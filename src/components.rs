@@ -47,3 +47,11 @@ pub fn create_info_row(label: String, info: String) -> ActionRow {
         .build()
 }
 
+/// Create a small heading above a section, e.g. of a panel.
+pub fn create_heading(label: &str) -> Label {
+    Label::builder()
+        .css_classes(["caption-heading"])
+        .halign(gtk::Align::Start)
+        .label(label)
+        .build()
+}
@@ -0,0 +1,74 @@
+use std::{io, mem, os::raw::c_int};
+
+use v4l::v4l2::{self, vidioc::_IOC_TYPE};
+use v4l::v4l_sys::{
+    v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER64, v4l2_event, v4l2_event_subscription, V4L2_EVENT_CTRL,
+};
+
+// Not provided by the v4l-crate, see linux/videodev2.h
+const VIDIOC_DQEVENT: _IOC_TYPE = ioc(IOC_READ, 89, mem::size_of::<v4l2_event>());
+const VIDIOC_SUBSCRIBE_EVENT: _IOC_TYPE =
+    ioc(IOC_WRITE, 90, mem::size_of::<v4l2_event_subscription>());
+
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+const fn ioc(dir: u32, nr: u32, size: usize) -> _IOC_TYPE {
+    ((dir << 30) | ((size as u32) << 16) | ((b'V' as u32) << 8) | nr) as _IOC_TYPE
+}
+
+/// A value-change of a control, as reported by the driver.
+#[derive(Debug, Clone, Copy)]
+pub struct ControlEvent {
+    pub id: u32,
+    pub value: i64,
+}
+
+/// Subscribes to changes of the given control on the file-descriptor.
+///
+/// Changes made through the same file-descriptor are not reported, so only
+/// changes by other processes or file-handles lead to an event. Pending events
+/// are signaled as priority-data (`POLLPRI`) on the file-descriptor.
+pub fn subscribe_control(fd: c_int, control_id: u32) -> io::Result<()> {
+    let mut sub: v4l2_event_subscription = unsafe { mem::zeroed() };
+    sub.type_ = V4L2_EVENT_CTRL;
+    sub.id = control_id;
+
+    unsafe {
+        v4l2::ioctl(
+            fd,
+            VIDIOC_SUBSCRIBE_EVENT,
+            &mut sub as *mut _ as *mut std::os::raw::c_void,
+        )
+    }
+}
+
+/// Dequeues the next pending event. Returns `None`, if the event is not a
+/// control-event.
+pub fn dequeue_event(fd: c_int) -> io::Result<Option<ControlEvent>> {
+    let mut event: v4l2_event = unsafe { mem::zeroed() };
+
+    unsafe {
+        v4l2::ioctl(
+            fd,
+            VIDIOC_DQEVENT,
+            &mut event as *mut _ as *mut std::os::raw::c_void,
+        )?;
+    }
+
+    if event.type_ != V4L2_EVENT_CTRL {
+        return Ok(None);
+    }
+
+    let value = unsafe {
+        if event.u.ctrl.type_ == v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER64 {
+            event.u.ctrl.__bindgen_anon_1.value64
+        } else {
+            event.u.ctrl.__bindgen_anon_1.value as i64
+        }
+    };
+    Ok(Some(ControlEvent {
+        id: event.id,
+        value,
+    }))
+}
@@ -8,5 +8,8 @@ pub trait ControlUi {
     fn update_value(&self, description: &Description);
     fn update_state(&self, description: &Description);
     fn reset_default(&self);
+    /// Reads the control from the device, if it has no V4L2 description to
+    /// be updated from.
+    fn update_from_device(&self) {}
 }
 
@@ -0,0 +1,223 @@
+use std::rc::Rc;
+
+use adw::{prelude::*, ComboRow, PreferencesRow, SwitchRow};
+use gtk::{Adjustment, Orientation, PositionType, Scale, StringList};
+use v4l::{control::Description, Device};
+
+use crate::{
+    components::create_pref_row_with_box_and_label,
+    uvc_xu::{XuControl, XuControlType, UVC_GET_DEF, UVC_GET_MAX, UVC_GET_MIN, UVC_GET_RES},
+};
+
+use super::ControlUi;
+
+enum ValueWidget {
+    Scale(Scale),
+    Switch(SwitchRow),
+    // Values of the menu entries by position
+    Menu(ComboRow, Vec<i64>),
+}
+
+/// Control of a UVC extension unit, described by a mapping of the user.
+pub struct ExtensionUnitControl {
+    control: Rc<XuControl>,
+    default: Option<i64>,
+    device: Rc<Device>,
+    pref_row: Rc<PreferencesRow>,
+    widget: ValueWidget,
+}
+
+impl ExtensionUnitControl {
+    pub fn new(device: Rc<Device>, control: XuControl) -> Self {
+        let fd = device.handle().fd();
+        let control = Rc::new(control);
+
+        let value = Self::query_state(&device, &control);
+        let default = control.query(fd, UVC_GET_DEF).ok();
+
+        let (pref_row, widget) = match &control.mapping.control_type {
+            XuControlType::Integer => {
+                let minimum = control.query(fd, UVC_GET_MIN).unwrap_or(0);
+                let maximum = control.query(fd, UVC_GET_MAX).unwrap_or(255);
+                let step = control.query(fd, UVC_GET_RES).unwrap_or(1).max(1);
+
+                let adjustment = Adjustment::builder()
+                    .lower(minimum as f64)
+                    .upper(maximum as f64)
+                    .step_increment(step as f64)
+                    .value(value as f64)
+                    .build();
+
+                let scale = Scale::builder()
+                    .adjustment(&adjustment)
+                    .digits(0)
+                    .draw_value(true)
+                    .halign(gtk::Align::End)
+                    .hexpand(true)
+                    .orientation(Orientation::Horizontal)
+                    .sensitive(control.writable)
+                    .value_pos(PositionType::Right)
+                    .width_request(360)
+                    .build();
+
+                let control_for_change = control.clone();
+                let device_for_change = device.clone();
+                scale.connect_value_changed(move |scale| {
+                    Self::set_value(&device_for_change, &control_for_change, scale.value() as i64);
+                });
+
+                let (row, rowbox) = create_pref_row_with_box_and_label(control.mapping.name.clone());
+                rowbox.append(&scale);
+
+                (row, ValueWidget::Scale(scale))
+            }
+
+            XuControlType::Boolean => {
+                let row = SwitchRow::builder()
+                    .active(value != 0)
+                    .hexpand(true)
+                    .sensitive(control.writable)
+                    .title(control.mapping.name.clone())
+                    .build();
+
+                let control_for_change = control.clone();
+                let device_for_change = device.clone();
+                row.connect_active_notify(move |row| {
+                    Self::set_value(&device_for_change, &control_for_change, row.is_active() as i64);
+                });
+
+                (row.clone().upcast(), ValueWidget::Switch(row))
+            }
+
+            XuControlType::Menu(options) => {
+                let names: Vec<&str> = options.iter().map(|(_, name)| name.as_str()).collect();
+                let values: Vec<i64> = options.iter().map(|(value, _)| *value).collect();
+
+                let row = ComboRow::builder()
+                    .model(&StringList::new(&names))
+                    .selected(Self::menu_position(&values, value))
+                    .sensitive(control.writable)
+                    .title(control.mapping.name.clone())
+                    .build();
+
+                let control_for_change = control.clone();
+                let device_for_change = device.clone();
+                let values_for_change = values.clone();
+                row.connect_selected_notify(move |row| {
+                    if let Some(value) = values_for_change.get(row.selected() as usize) {
+                        Self::set_value(&device_for_change, &control_for_change, *value);
+                    }
+                });
+
+                (row.clone().upcast(), ValueWidget::Menu(row, values))
+            }
+        };
+
+        ExtensionUnitControl {
+            control,
+            default,
+            device,
+            pref_row: Rc::new(pref_row),
+            widget,
+        }
+    }
+
+    fn query_state(device: &Device, control: &XuControl) -> i64 {
+        match control.get(device.handle().fd()) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error while checking state of {}: {}", control.mapping.name, e);
+                0
+            }
+        }
+    }
+
+    fn set_value(device: &Device, control: &XuControl, value: i64) {
+        if let Err(e) = control.set(device.handle().fd(), value) {
+            eprintln!("Error setting {}: {}", control.mapping.name, e);
+        }
+    }
+
+    fn menu_position(values: &[i64], value: i64) -> u32 {
+        match values.iter().position(|v| *v == value) {
+            Some(p) => p as u32,
+            None => gtk::INVALID_LIST_POSITION,
+        }
+    }
+
+    fn show_current_value(&self) {
+        self.show_value(Self::query_state(&self.device, &self.control));
+    }
+
+    /// Disables the widget, while the camera does not accept values.
+    fn show_enabled(&self) {
+        let enabled = match self.control.is_enabled(self.device.handle().fd()) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!("Error while checking access of {}: {}", self.control.mapping.name, e);
+                self.control.writable
+            }
+        };
+
+        let widget = self.value_widget();
+        if widget.is_sensitive() != enabled {
+            widget.set_sensitive(enabled);
+        }
+    }
+
+    fn value_widget(&self) -> gtk::Widget {
+        match &self.widget {
+            ValueWidget::Scale(scale) => scale.clone().upcast(),
+            ValueWidget::Switch(row) => row.clone().upcast(),
+            ValueWidget::Menu(row, _) => row.clone().upcast(),
+        }
+    }
+
+    fn show_value(&self, value: i64) {
+        match &self.widget {
+            ValueWidget::Scale(scale) => {
+                if scale.value() as i64 != value {
+                    scale.set_value(value as f64);
+                }
+            }
+            ValueWidget::Switch(row) => {
+                if row.is_active() != (value != 0) {
+                    row.set_active(value != 0);
+                }
+            }
+            ValueWidget::Menu(row, values) => {
+                let position = Self::menu_position(values, value);
+                if row.selected() != position {
+                    row.set_selected(position);
+                }
+            }
+        };
+    }
+}
+
+impl ControlUi for ExtensionUnitControl {
+    fn preference_row(&self) -> Rc<PreferencesRow> {
+        self.pref_row.clone()
+    }
+
+    // Extension unit controls have no V4L2 description, so they are read
+    // directly from the camera
+    fn update_value(&self, _description: &Description) {
+        self.show_current_value();
+    }
+
+    fn update_state(&self, _description: &Description) {
+        self.show_enabled();
+    }
+
+    fn reset_default(&self) {
+        if let Some(default) = self.default {
+            self.show_value(default);
+        }
+    }
+
+    fn update_from_device(&self) {
+        self.show_enabled();
+        self.show_current_value();
+    }
+}
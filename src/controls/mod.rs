@@ -15,3 +15,6 @@ pub use self::integer_control::IntegerControl;
 
 mod menu_control;
 pub use self::menu_control::MenuControl;
+
+mod extension_unit_control;
+pub use self::extension_unit_control::ExtensionUnitControl;
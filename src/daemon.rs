@@ -0,0 +1,208 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use gtk::gio::{self, prelude::*};
+use glib::{ControlFlow, IOCondition, SourceId};
+use v4l::{control::Description, Device};
+
+use crate::{
+    control_events::{dequeue_event, subscribe_control},
+    device_discovery::{watch_devices, DeviceEvent, SETTLE_DELAY},
+    files::{get_device_id, get_video_devices},
+    profiles::{profiles_for_device, write_control_value},
+    quirks::DeviceQuirks,
+};
+
+const DEVICE_DIR: &str = "/dev";
+
+/// Runs without a window, applies bound profiles to cameras when they appear
+/// or the system resumes from suspend.
+///
+/// With `--enforce`, locked controls of the profiles are set again, whenever
+/// another process changes them.
+pub fn run(args: &[String]) -> glib::ExitCode {
+    let enforce = args.iter().any(|a| a == "--enforce");
+
+    let main_loop = glib::MainLoop::new(None, false);
+    let daemon = Rc::new(Daemon {
+        enforce,
+        enforced_devices: RefCell::new(HashMap::new()),
+    });
+
+    daemon.apply_all();
+
+    // Both have to live as long as the main loop runs
+    let _device_monitor = watch_cameras(daemon.clone());
+    let _sleep_subscription = watch_sleep(daemon.clone());
+
+    println!("Watching cameras for bound profiles");
+    main_loop.run();
+
+    glib::ExitCode::SUCCESS
+}
+
+struct Daemon {
+    enforce: bool,
+    // Event-sources watching locked controls by device-path
+    enforced_devices: RefCell<HashMap<String, SourceId>>,
+}
+
+impl Daemon {
+    fn apply_all(self: &Rc<Self>) {
+        let mut paths = get_video_devices(DEVICE_DIR);
+        paths.sort();
+
+        for path in paths {
+            self.apply_to(&path);
+        }
+    }
+
+    fn apply_to(self: &Rc<Self>, device_path: &str) {
+        let device_id = get_device_id(device_path);
+        let profiles = profiles_for_device(&device_id);
+        if profiles.is_empty() {
+            return;
+        }
+
+        let device = match Device::with_path(device_path) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Error opening {}: {}", device_path, e);
+                return;
+            }
+        };
+
+        let quirks = DeviceQuirks::for_device(device_path, &device);
+
+        for profile in &profiles {
+            for error in profile.apply(&device, &quirks) {
+                eprintln!("Profile {} on {}: {}", profile.name, device_path, error);
+            }
+            println!("Applied profile {} to {}", profile.name, device_path);
+        }
+
+        if !self.enforce {
+            return;
+        }
+
+        let locked: HashMap<u32, i64> = profiles
+            .iter()
+            .flat_map(|p| p.locked_controls())
+            .map(|c| (c.id, c.value))
+            .collect();
+
+        if !locked.is_empty() {
+            self.enforce_locked(device_path, device, locked);
+        }
+    }
+
+    /// Watches the locked controls on the device and sets their value again,
+    /// when changed from outside.
+    fn enforce_locked(self: &Rc<Self>, device_path: &str, device: Device, locked: HashMap<u32, i64>) {
+        self.stop_enforcing(device_path);
+
+        let descriptions: Vec<(Description, i64)> = match device.query_controls() {
+            Ok(d) => d
+                .into_iter()
+                .filter_map(|d| locked.get(&d.id).map(|v| (d, *v)))
+                .collect(),
+            Err(e) => {
+                eprintln!("Error querying controls of {}: {}", device_path, e);
+                return;
+            }
+        };
+
+        let fd = device.handle().fd();
+        for (desc, _) in &descriptions {
+            if let Err(e) = subscribe_control(fd, desc.id) {
+                eprintln!("Cannot watch control {} on {}: {}", desc.name, device_path, e);
+            }
+        }
+
+        let daemon = self.clone();
+        let path = device_path.to_string();
+        let source = glib::unix_fd_add_local(
+            fd,
+            IOCondition::PRI | IOCondition::ERR | IOCondition::HUP,
+            move |_, condition| {
+                if condition.intersects(IOCondition::ERR | IOCondition::HUP) {
+                    println!("Stopped enforcing locked controls on {}", path);
+                    daemon.enforced_devices.borrow_mut().remove(&path);
+                    return ControlFlow::Break;
+                }
+
+                while let Ok(event) = dequeue_event(device.handle().fd()) {
+                    let event = match event {
+                        Some(e) => e,
+                        None => continue,
+                    };
+
+                    let (desc, value) = match descriptions.iter().find(|(d, _)| d.id == event.id) {
+                        Some(d) => d,
+                        None => continue,
+                    };
+
+                    if event.value != *value {
+                        println!("Restoring locked control {} on {}", desc.name, path);
+                        if let Err(e) = write_control_value(&device, desc, *value) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                }
+
+                ControlFlow::Continue
+            },
+        );
+
+        self.enforced_devices
+            .borrow_mut()
+            .insert(device_path.to_string(), source);
+    }
+
+    fn stop_enforcing(&self, device_path: &str) {
+        let source = self.enforced_devices.borrow_mut().remove(device_path);
+        if let Some(s) = source {
+            s.remove();
+        }
+    }
+}
+
+/// Applies profiles to video-devices, which appear in `/dev`.
+fn watch_cameras(daemon: Rc<Daemon>) -> Option<gio::FileMonitor> {
+    watch_devices(move |event| match event {
+        DeviceEvent::Added(path) => daemon.apply_to(&path),
+        DeviceEvent::Removed(path) => daemon.stop_enforcing(&path),
+    })
+}
+
+/// Applies all profiles again, after the system resumed from suspend.
+fn watch_sleep(daemon: Rc<Daemon>) -> Option<(gio::DBusConnection, gio::SignalSubscriptionId)> {
+    let connection = match gio::bus_get_sync(gio::BusType::System, None::<&gio::Cancellable>) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Cannot connect to system-bus, profiles are not applied after resume: {}", e);
+            return None;
+        }
+    };
+
+    let subscription = connection.signal_subscribe(
+        Some("org.freedesktop.login1"),
+        Some("org.freedesktop.login1.Manager"),
+        Some("PrepareForSleep"),
+        Some("/org/freedesktop/login1"),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            let going_to_sleep = match params.get::<(bool,)>() {
+                Some((s,)) => s,
+                None => return,
+            };
+
+            if !going_to_sleep {
+                let daemon = daemon.clone();
+                glib::timeout_add_local_once(SETTLE_DELAY, move || daemon.apply_all());
+            }
+        },
+    );
+
+    Some((connection, subscription))
+}
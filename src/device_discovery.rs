@@ -0,0 +1,417 @@
+use std::{fs, path::{Path, PathBuf}, rc::Rc, time::Duration};
+
+use gtk::{gio, gio::prelude::*, glib};
+use v4l::{capability::Flags, Device};
+
+use crate::{camera::CameraDevice, files::get_video_devices};
+
+const DEVICE_DIR: &str = "/dev";
+const SYSFS_DIR: &str = "/sys/class/video4linux";
+
+/// Time to wait after a device-node appeared or the system resumed, so udev
+/// and the driver are done with the device.
+pub const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+/// A video device-node, which appeared or disappeared.
+pub enum DeviceEvent {
+    Added(String),
+    Removed(String),
+}
+
+/// What a device-node is used for. A camera often has a capture and a
+/// metadata node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeRole {
+    Capture,
+    Metadata,
+    Output,
+    MemoryToMemory,
+    Other,
+}
+
+impl NodeRole {
+    pub fn from_capabilities(capabilities: Flags) -> Self {
+        if capabilities.intersects(Flags::VIDEO_M2M | Flags::VIDEO_M2M_MPLANE) {
+            NodeRole::MemoryToMemory
+        } else if capabilities.intersects(Flags::VIDEO_CAPTURE | Flags::VIDEO_CAPTURE_MPLANE) {
+            NodeRole::Capture
+        } else if capabilities.intersects(Flags::META_CAPTURE | Flags::META_OUTPUT) {
+            NodeRole::Metadata
+        } else if capabilities.intersects(Flags::VIDEO_OUTPUT | Flags::VIDEO_OUTPUT_MPLANE) {
+            NodeRole::Output
+        } else {
+            NodeRole::Other
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NodeRole::Capture => "Capture",
+            NodeRole::Metadata => "Metadata",
+            NodeRole::Output => "Output",
+            NodeRole::MemoryToMemory => "M2M",
+            NodeRole::Other => "Other",
+        }
+    }
+}
+
+/// Where a physical device is attached, to tell identical cameras apart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceLocation {
+    pub serial: Option<String>,
+    /// Port on the bus, e.g. "1-2" for USB
+    pub bus: Option<String>,
+    /// Name of the device-node, e.g. "video2"
+    pub node: String,
+}
+
+/// Lists capture devices directly from `/dev` and sysfs, for systems where
+/// cameras cannot be listed through PipeWire.
+pub fn discover_devices() -> Vec<CameraDevice> {
+    let mut paths = get_video_devices(DEVICE_DIR);
+    paths.sort_by_key(|p| node_number(p));
+
+    paths
+        .into_iter()
+        .filter(|p| is_capture_device(p))
+        .map(|path| {
+            let name = read_device_name(Path::new(SYSFS_DIR), &path);
+            CameraDevice { path, name }
+        })
+        .collect()
+}
+
+/// Calls `on_event`, when video device-nodes appear or disappear. Appearing
+/// nodes are reported after the settle delay.
+pub fn watch_devices(on_event: impl Fn(DeviceEvent) + 'static) -> Option<gio::FileMonitor> {
+    let dir = gio::File::for_path(DEVICE_DIR);
+    let monitor = match dir.monitor_directory(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Cannot watch {} for new cameras: {}", DEVICE_DIR, e);
+            return None;
+        }
+    };
+
+    let on_event = Rc::new(on_event);
+    monitor.connect_changed(move |_, file, _, event| {
+        let path = match file.path().and_then(|p| p.to_str().map(|s| s.to_string())) {
+            Some(p) => p,
+            None => return,
+        };
+
+        if !path.starts_with(&format!("{}/video", DEVICE_DIR)) {
+            return;
+        }
+
+        match event {
+            gio::FileMonitorEvent::Created => {
+                let on_event = on_event.clone();
+                glib::timeout_add_local_once(SETTLE_DELAY, move || on_event(DeviceEvent::Added(path)));
+            }
+            gio::FileMonitorEvent::Deleted => on_event(DeviceEvent::Removed(path)),
+            _ => {}
+        }
+    });
+
+    Some(monitor)
+}
+
+fn is_capture_device(device_path: &str) -> bool {
+    match get_node_role(device_path) {
+        Some(role) => role == NodeRole::Capture,
+        // Keep devices, that cannot be queried, e.g. without permissions
+        None => true,
+    }
+}
+
+pub fn get_node_role(device_path: &str) -> Option<NodeRole> {
+    let caps = Device::with_path(device_path).and_then(|d| d.query_caps()).ok()?;
+    Some(NodeRole::from_capabilities(caps.capabilities))
+}
+
+/// Lists all device-nodes of the physical device, the node belongs to,
+/// including itself, with their roles.
+pub fn list_sibling_nodes(device_path: &str) -> Vec<(String, Option<NodeRole>)> {
+    let sysfs_dir = Path::new(SYSFS_DIR);
+    let parent = match get_parent_device(sysfs_dir, device_path) {
+        Some(p) => p,
+        None => return vec![(device_path.to_string(), get_node_role(device_path))],
+    };
+
+    let mut paths = get_video_devices(DEVICE_DIR);
+    paths.sort_by_key(|p| node_number(p));
+
+    paths
+        .into_iter()
+        .filter(|p| get_parent_device(sysfs_dir, p).as_ref() == Some(&parent))
+        .map(|p| {
+            let role = get_node_role(&p);
+            (p, role)
+        })
+        .collect()
+}
+
+/// Identifies the physical device of the node by the bus info of its driver,
+/// which all nodes of a camera share. Nodes, that cannot be queried, stand
+/// for themselves.
+pub fn get_physical_device(device_path: &str) -> String {
+    match Device::with_path(device_path).and_then(|d| d.query_caps()) {
+        Ok(caps) if !caps.bus.is_empty() => caps.bus,
+        _ => device_path.to_string(),
+    }
+}
+
+/// Labels the node with its role, e.g. "video2 (Capture)", to choose between
+/// the nodes of a camera.
+pub fn get_node_label(device_path: &str) -> String {
+    let node = Path::new(device_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(device_path);
+
+    match get_node_role(device_path) {
+        Some(role) => format!("{} ({})", node, role.label()),
+        None => node.to_string(),
+    }
+}
+
+pub fn get_location(device_path: &str) -> DeviceLocation {
+    read_location(Path::new(SYSFS_DIR), device_path)
+}
+
+/// Resolves the physical device in sysfs, e.g. the USB interface of a camera.
+fn get_parent_device(sysfs_dir: &Path, device_path: &str) -> Option<PathBuf> {
+    let node = Path::new(device_path).file_name()?;
+    fs::canonicalize(sysfs_dir.join(node).join("device")).ok()
+}
+
+fn read_location(sysfs_dir: &Path, device_path: &str) -> DeviceLocation {
+    let node = Path::new(device_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(device_path)
+        .to_string();
+
+    let parent = match get_parent_device(sysfs_dir, device_path) {
+        Some(p) => p,
+        None => return DeviceLocation { node, ..Default::default() },
+    };
+
+    let (bus, device_dir) = split_usb_interface(&parent);
+
+    DeviceLocation {
+        serial: read_sysfs_value(&device_dir, "serial"),
+        bus: Some(bus).filter(|b| !b.is_empty()),
+        node,
+    }
+}
+
+/// Directory of the USB device in sysfs, which holds its raw descriptors.
+pub fn get_usb_device_dir(device_path: &str) -> Option<PathBuf> {
+    let parent = get_parent_device(Path::new(SYSFS_DIR), device_path)?;
+    let (_, device_dir) = split_usb_interface(&parent);
+    Some(device_dir)
+}
+
+/// USB vendor and product id like "046d:0825", if it is a USB device.
+pub fn get_usb_id(device_path: &str) -> Option<String> {
+    let device_dir = get_usb_device_dir(device_path)?;
+    let vendor = read_sysfs_value(&device_dir, "idVendor")?;
+    let product = read_sysfs_value(&device_dir, "idProduct")?;
+    Some(format!("{}:{}", vendor, product))
+}
+
+/// Hardware details of the physical device from sysfs, as labeled values.
+pub fn read_hardware_details(device_path: &str) -> Vec<(String, String)> {
+    read_hardware_details_in(Path::new(SYSFS_DIR), device_path)
+}
+
+fn read_hardware_details_in(sysfs_dir: &Path, device_path: &str) -> Vec<(String, String)> {
+    let mut details = vec![];
+
+    let parent = match get_parent_device(sysfs_dir, device_path) {
+        Some(p) => p,
+        None => return details,
+    };
+
+    let (bus, device_dir) = split_usb_interface(&parent);
+    let mut add = |label: &str, value: Option<String>| {
+        if let Some(value) = value {
+            details.push((label.to_string(), value));
+        }
+    };
+
+    let vendor_id = read_sysfs_value(&device_dir, "idVendor");
+    let product_id = read_sysfs_value(&device_dir, "idProduct");
+    if let (Some(vendor), Some(product)) = (vendor_id, product_id) {
+        add("USB ID", Some(format!("{}:{}", vendor, product)));
+    }
+
+    add("Manufacturer", read_sysfs_value(&device_dir, "manufacturer"));
+    add("Product", read_sysfs_value(&device_dir, "product"));
+    add("Serial", read_sysfs_value(&device_dir, "serial"));
+    add("Bus position", Some(bus).filter(|b| !b.is_empty()));
+    add("USB speed", read_sysfs_value(&device_dir, "speed").map(|s| format!("{} Mbit/s", s)));
+    add("Firmware version", read_sysfs_value(&device_dir, "bcdDevice").map(|v| format_bcd_version(&v)));
+    add("Driver", read_link_name(&parent.join("driver")));
+    add("Driver module", read_link_name(&parent.join("driver").join("module")));
+
+    details
+}
+
+/// USB cameras are bound by their interface, e.g. "1-2:1.0", while the
+/// descriptors belong to the USB device "1-2" above. Returns the bus position
+/// and the directory of the device.
+fn split_usb_interface(parent: &Path) -> (String, PathBuf) {
+    let interface = parent.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    match interface.split_once(':') {
+        Some((port, _)) => (port.to_string(), parent.parent().unwrap_or(parent).to_path_buf()),
+        None => (interface.to_string(), parent.to_path_buf()),
+    }
+}
+
+fn read_sysfs_value(dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn read_link_name(link: &Path) -> Option<String> {
+    let target = fs::read_link(link).ok()?;
+    target.file_name()?.to_str().map(|s| s.to_string())
+}
+
+/// Formats a binary-coded decimal version like "0016" as "0.16".
+fn format_bcd_version(bcd: &str) -> String {
+    if bcd.len() != 4 {
+        return bcd.to_string();
+    }
+
+    let (major, minor) = bcd.split_at(2);
+    let major = major.trim_start_matches('0');
+    format!("{}.{}", if major.is_empty() { "0" } else { major }, minor)
+}
+
+/// Reads the name of the device from sysfs, falls back to the device path.
+fn read_device_name(sysfs_dir: &Path, device_path: &str) -> String {
+    let node = match Path::new(device_path).file_name() {
+        Some(n) => n,
+        None => return device_path.to_string(),
+    };
+
+    match fs::read_to_string(sysfs_dir.join(node).join("name")) {
+        Ok(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => device_path.to_string(),
+    }
+}
+
+/// Number of the device-node, so /dev/video10 is sorted after /dev/video2.
+fn node_number(device_path: &str) -> u32 {
+    device_path
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_device_name() {
+        let sysfs_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(sysfs_dir.path().join("video2")).unwrap();
+        fs::write(sysfs_dir.path().join("video2").join("name"), "HD Webcam: HD Webcam\n").unwrap();
+
+        assert_eq!(read_device_name(sysfs_dir.path(), "/dev/video2"), "HD Webcam: HD Webcam");
+        assert_eq!(read_device_name(sysfs_dir.path(), "/dev/video3"), "/dev/video3");
+    }
+
+    #[test]
+    fn test_read_location() {
+        let root = tempfile::tempdir().unwrap();
+        let usb_device = root.path().join("devices/usb1/1-2");
+        fs::create_dir_all(usb_device.join("1-2:1.0")).unwrap();
+        fs::write(usb_device.join("serial"), "ABC123\n").unwrap();
+
+        let sysfs_dir = root.path().join("video4linux");
+        for node in ["video0", "video1"] {
+            fs::create_dir_all(sysfs_dir.join(node)).unwrap();
+            std::os::unix::fs::symlink(usb_device.join("1-2:1.0"), sysfs_dir.join(node).join("device")).unwrap();
+        }
+
+        let location = read_location(&sysfs_dir, "/dev/video1");
+        assert_eq!(location.serial.as_deref(), Some("ABC123"));
+        assert_eq!(location.bus.as_deref(), Some("1-2"));
+        assert_eq!(location.node, "video1");
+
+        assert_eq!(
+            get_parent_device(&sysfs_dir, "/dev/video0"),
+            get_parent_device(&sysfs_dir, "/dev/video1")
+        );
+        assert_eq!(read_location(&sysfs_dir, "/dev/video5").bus, None);
+    }
+
+    #[test]
+    fn test_read_hardware_details() {
+        let root = tempfile::tempdir().unwrap();
+        let usb_device = root.path().join("devices/usb1/1-2");
+        let interface = usb_device.join("1-2:1.0");
+        let driver = root.path().join("drivers/uvcvideo");
+        fs::create_dir_all(&interface).unwrap();
+        fs::create_dir_all(&driver).unwrap();
+        fs::create_dir_all(root.path().join("module/uvcvideo")).unwrap();
+        std::os::unix::fs::symlink(&driver, interface.join("driver")).unwrap();
+        std::os::unix::fs::symlink(root.path().join("module/uvcvideo"), driver.join("module")).unwrap();
+
+        for (name, value) in [
+            ("idVendor", "046d"),
+            ("idProduct", "085e"),
+            ("manufacturer", ""),
+            ("product", "Logitech BRIO"),
+            ("speed", "5000"),
+            ("bcdDevice", "0317"),
+        ] {
+            fs::write(usb_device.join(name), format!("{}\n", value)).unwrap();
+        }
+
+        let sysfs_dir = root.path().join("video4linux");
+        fs::create_dir_all(sysfs_dir.join("video0")).unwrap();
+        std::os::unix::fs::symlink(&interface, sysfs_dir.join("video0").join("device")).unwrap();
+
+        let details = read_hardware_details_in(&sysfs_dir, "/dev/video0");
+        let labels: Vec<(&str, &str)> = details.iter().map(|(l, v)| (l.as_str(), v.as_str())).collect();
+
+        assert_eq!(
+            labels,
+            vec![
+                ("USB ID", "046d:085e"),
+                ("Product", "Logitech BRIO"),
+                ("Bus position", "1-2"),
+                ("USB speed", "5000 Mbit/s"),
+                ("Firmware version", "3.17"),
+                ("Driver", "uvcvideo"),
+                ("Driver module", "uvcvideo"),
+            ]
+        );
+        assert_eq!(format_bcd_version("0016"), "0.16");
+    }
+
+    #[test]
+    fn test_node_role() {
+        assert_eq!(NodeRole::from_capabilities(Flags::VIDEO_CAPTURE | Flags::STREAMING), NodeRole::Capture);
+        assert_eq!(NodeRole::from_capabilities(Flags::META_CAPTURE | Flags::STREAMING), NodeRole::Metadata);
+        assert_eq!(NodeRole::from_capabilities(Flags::VIDEO_M2M_MPLANE), NodeRole::MemoryToMemory);
+        assert_eq!(NodeRole::from_capabilities(Flags::VIDEO_OUTPUT), NodeRole::Output);
+    }
+
+    #[test]
+    fn test_node_number() {
+        let mut paths = vec!["/dev/video10", "/dev/video2", "/dev/video0"];
+        paths.sort_by_key(|p| node_number(p));
+
+        assert_eq!(paths, vec!["/dev/video0", "/dev/video2", "/dev/video10"]);
+    }
+}
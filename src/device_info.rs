@@ -0,0 +1,224 @@
+use v4l::{format::Description, video::{capture::Parameters, Capture}, Device};
+
+use crate::{
+    capability_flags::{
+        decode_capabilities, decode_format_flags, decode_stream_capabilities, decode_stream_modes,
+        query_capability_flags, Flag,
+    },
+    device_discovery::{get_usb_device_dir, list_sibling_nodes, read_hardware_details},
+    files::get_device_links,
+    mode_matrix::{read_current_format, read_mode_matrix, FormatMatrix},
+    uvc_xu::read_extension_units,
+};
+
+/// A titled list of labeled values, describing one aspect of a device.
+pub struct InfoSection {
+    pub title: String,
+    pub entries: Vec<(String, String)>,
+    /// Decoded flags, shown after the entries
+    pub flags: Vec<(String, Vec<Flag>)>,
+}
+
+impl InfoSection {
+    fn new(title: &str) -> Self {
+        InfoSection {
+            title: title.to_string(),
+            entries: vec![],
+            flags: vec![],
+        }
+    }
+
+    fn add(&mut self, label: &str, value: String) {
+        self.entries.push((label.to_string(), value));
+    }
+
+    fn add_flags(&mut self, label: &str, flags: Vec<Flag>) {
+        self.flags.push((label.to_string(), flags));
+    }
+}
+
+/// Queries capabilities, parameters and formats of the device.
+pub fn query_device_info(device_path: &str) -> Vec<InfoSection> {
+    let mut about = InfoSection::new("About");
+
+    let device = match Device::with_path(device_path) {
+        Ok(d) => d,
+        Err(e) => {
+            about.add("Error getting device for capabilities", e.to_string());
+            return vec![about];
+        }
+    };
+
+    let caps = match device.query_caps() {
+        Ok(caps) => caps,
+        Err(e) => {
+            about.add("Error querying capabilities", e.to_string());
+            return vec![about];
+        }
+    };
+
+    let (major, minor, patch) = caps.version;
+    about.add("Bus", caps.bus.clone());
+    about.add("Card", caps.card.clone());
+    about.add("Driver", caps.driver.clone());
+    about.add("Version", format!("{}.{}.{}", major, minor, patch));
+
+    match query_capability_flags(device.handle().fd()) {
+        Ok(flags) => {
+            about.add_flags("Device capabilities", decode_capabilities(flags.device));
+            about.add_flags("Driver capabilities", decode_capabilities(flags.driver));
+        }
+        Err(e) => about.add("Error querying capability flags", e.to_string()),
+    };
+
+    let mut sections = vec![about, create_hardware_section(device_path), create_nodes_section(device_path)];
+
+    if let Some(section) = create_extension_units_section(device_path) {
+        sections.push(section);
+    }
+
+    match device.params() {
+        Ok(params) => sections.push(create_params_section(params)),
+        Err(e) => {
+            let mut section = InfoSection::new("Parameters");
+            section.add("Error querying params", e.to_string());
+            sections.push(section);
+        }
+    };
+
+    match device.enum_formats() {
+        Ok(formats) => sections.push(create_formats_section(formats)),
+        Err(e) => {
+            let mut section = InfoSection::new("Formats");
+            section.add("Error querying formats", e.to_string());
+            sections.push(section);
+        }
+    };
+
+    sections.push(create_current_format_section(&device));
+
+    match read_mode_matrix(&device) {
+        Ok(matrix) => sections.extend(matrix.iter().map(create_modes_section)),
+        Err(e) => {
+            let mut section = InfoSection::new("Modes");
+            section.add("Error enumerating modes", e.to_string());
+            sections.push(section);
+        }
+    };
+
+    sections
+}
+
+/// Identifies the exact hardware, e.g. for support requests.
+fn create_hardware_section(device_path: &str) -> InfoSection {
+    let mut section = InfoSection::new("Hardware");
+
+    for (label, value) in read_hardware_details(device_path) {
+        section.add(&label, value);
+    }
+
+    for link in get_device_links(device_path) {
+        section.add("Link", link);
+    }
+
+    if section.entries.is_empty() {
+        section.add("Details", "Not available from sysfs".to_string());
+    }
+
+    section
+}
+
+/// Lists all device-nodes of the physical device with their roles.
+fn create_nodes_section(device_path: &str) -> InfoSection {
+    let mut section = InfoSection::new("Device Nodes");
+
+    for (path, role) in list_sibling_nodes(device_path) {
+        let role = match role {
+            Some(r) => r.label(),
+            None => "Unknown",
+        };
+
+        let label = if path == device_path { format!("{} (selected)", path) } else { path };
+        section.add(&label, role.to_string());
+    }
+
+    section
+}
+
+/// Lists the UVC extension units, e.g. to write mappings for their controls.
+fn create_extension_units_section(device_path: &str) -> Option<InfoSection> {
+    let units = read_extension_units(&get_usb_device_dir(device_path)?);
+    if units.is_empty() {
+        return None;
+    }
+
+    let mut section = InfoSection::new("Extension Units");
+
+    for unit in units {
+        let selectors: Vec<String> = unit.selectors().iter().map(|s| s.to_string()).collect();
+        section.add(
+            &format!("Unit {}", unit.unit_id),
+            format!("{}\n{} controls, selectors: {}", unit.guid, unit.num_controls, selectors.join(", ")),
+        );
+    }
+
+    Some(section)
+}
+
+fn create_params_section(params: Parameters) -> InfoSection {
+    let mut section = InfoSection::new("Parameters");
+
+    section.add("Interval", params.interval.to_string());
+    section.add_flags("Capabilities", decode_stream_capabilities(params.capabilities.bits()));
+    section.add_flags("Modes", decode_stream_modes(params.modes.bits()));
+
+    section
+}
+
+fn create_formats_section(descriptions: Vec<Description>) -> InfoSection {
+    let mut section = InfoSection::new("Formats");
+
+    for desc in descriptions {
+        let label = format!("{} ({})", desc.description, desc.fourcc);
+        section.add_flags(&label, decode_format_flags(desc.flags.bits()));
+    }
+
+    section
+}
+
+fn create_current_format_section(device: &Device) -> InfoSection {
+    let mut section = InfoSection::new("Current Format");
+
+    match read_current_format(device) {
+        Ok(current) => {
+            for (label, value) in current.entries() {
+                section.add(label, value);
+            }
+        }
+        Err(e) => section.add("Error querying format", e.to_string()),
+    };
+
+    section
+}
+
+/// Frame sizes of a pixel format with their frame rates. Rates of size
+/// ranges are those of the maximum size.
+fn create_modes_section(format: &FormatMatrix) -> InfoSection {
+    let mut section = InfoSection::new(&format!("{} Modes", format.fourcc));
+
+    for size in &format.sizes {
+        let rates: Vec<String> = size.intervals.iter().map(|i| i.label()).collect();
+        let rates = match rates.is_empty() {
+            true => "No frame rates reported".to_string(),
+            false => rates.join(", "),
+        };
+
+        section.add(&size.label(), rates);
+    }
+
+    if section.entries.is_empty() {
+        section.add("Frame sizes", "None reported".to_string());
+    }
+
+    section
+}
@@ -0,0 +1,144 @@
+use std::{fs, io, mem, path::Path};
+
+use v4l::{buffer, v4l2, v4l_sys::v4l2_requestbuffers, Device, Memory};
+
+/// A process, that has the device opened.
+#[derive(Debug, PartialEq)]
+pub struct DeviceHolder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Checks, if another application is streaming from the device. Requesting
+/// no buffers changes nothing, but is refused with EBUSY, while another
+/// file-handle owns the buffers.
+fn is_device_busy(device: &Device) -> bool {
+    let mut request = v4l2_requestbuffers {
+        count: 0,
+        type_: buffer::Type::VideoCapture as u32,
+        memory: Memory::Mmap as u32,
+        ..unsafe { mem::zeroed() }
+    };
+
+    let result = unsafe {
+        v4l2::ioctl(
+            device.handle().fd(),
+            v4l2::vidioc::VIDIOC_REQBUFS,
+            &mut request as *mut _ as *mut std::os::raw::c_void,
+        )
+    };
+
+    match result {
+        Ok(_) => false,
+        Err(e) => is_busy_error(&e),
+    }
+}
+
+/// Lists the other applications streaming from the device, without stopping
+/// the own preview. It streams in this process or through PipeWire, which are
+/// both not counted, so applications streaming through PipeWire are not
+/// found either.
+pub fn find_streaming_holders(device: &Device, device_path: &str) -> Vec<DeviceHolder> {
+    if !is_device_busy(device) {
+        return vec![];
+    }
+
+    find_device_holders(device_path)
+        .into_iter()
+        .filter(|h| h.name != PIPEWIRE_PROCESS)
+        .collect()
+}
+
+const PIPEWIRE_PROCESS: &str = "pipewire";
+
+pub fn is_busy_error(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(libc::EBUSY)
+}
+
+/// Lists the other processes holding the device open. Processes of other
+/// users are only found with sufficient permissions.
+pub fn find_device_holders(device_path: &str) -> Vec<DeviceHolder> {
+    let own_pid = std::process::id();
+
+    find_holders_in(Path::new("/proc"), Path::new(device_path))
+        .into_iter()
+        .filter(|h| h.pid != own_pid)
+        .collect()
+}
+
+/// Describes the holders for messages, e.g. "obs (1234), firefox (5678)".
+pub fn format_holders(holders: &[DeviceHolder]) -> String {
+    holders
+        .iter()
+        .map(|h| format!("{} ({})", h.name, h.pid))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn find_holders_in(proc_dir: &Path, device_path: &Path) -> Vec<DeviceHolder> {
+    let mut holders = vec![];
+
+    let entries = match fs::read_dir(proc_dir) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", proc_dir.display(), e);
+            return holders;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        // Mostly permission denied for processes of other users
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+
+        let holds_device = fds
+            .flatten()
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == device_path));
+
+        if holds_device {
+            let name = fs::read_to_string(entry.path().join("comm"))
+                .map(|n| n.trim().to_string())
+                .unwrap_or_else(|_| "Unknown".to_string());
+
+            holders.push(DeviceHolder { pid, name });
+        }
+    }
+
+    holders.sort_by_key(|h| h.pid);
+    holders
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn test_find_holders_in() {
+        let proc_dir = tempfile::tempdir().unwrap();
+        let proc_path = proc_dir.path();
+
+        for (pid, name, target) in [
+            ("42", "obs", "/dev/video0"),
+            ("7", "firefox", "/dev/video2"),
+            ("self", "self", "/dev/video0"),
+        ] {
+            fs::create_dir_all(proc_path.join(pid).join("fd")).unwrap();
+            fs::write(proc_path.join(pid).join("comm"), format!("{}\n", name)).unwrap();
+            symlink(target, proc_path.join(pid).join("fd").join("3")).unwrap();
+        }
+
+        let holders = find_holders_in(proc_path, Path::new("/dev/video0"));
+
+        assert_eq!(holders, vec![DeviceHolder { pid: 42, name: "obs".to_string() }]);
+        assert_eq!(format_holders(&holders), "obs (42)");
+    }
+}
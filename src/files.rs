@@ -1,6 +1,10 @@
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
-pub fn _get_video_devices(dir: &str) -> Vec<String> {
+use toml::Table;
+
+pub fn get_video_devices(dir: &str) -> Vec<String> {
     let mut video_files = Vec::new();
 
     // Read the entries in the specified directory
@@ -22,6 +26,132 @@ pub fn _get_video_devices(dir: &str) -> Vec<String> {
     video_files
 }
 
+/// Returns the directory for user-specific configuration of this app.
+pub fn get_config_dir() -> PathBuf {
+    glib::user_config_dir().join(CONFIG_DIR_NAME)
+}
+
+const CONFIG_DIR_NAME: &str = "v4l2-gui";
+
+/// Reads a TOML file of the config directory. Returns `None`, if it is
+/// missing or invalid, so the defaults are used.
+pub fn load_config(file_name: &str, description: &str) -> Option<Table> {
+    match fs::read_to_string(get_config_dir().join(file_name)) {
+        Ok(content) => parse_config(&content, description),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", description, e);
+            None
+        }
+    }
+}
+
+pub fn parse_config(content: &str, description: &str) -> Option<Table> {
+    match content.parse::<Table>() {
+        Ok(t) => Some(t),
+        Err(e) => {
+            eprintln!("Invalid {}, using defaults: {}", description, e);
+            None
+        }
+    }
+}
+
+/// Writes a TOML file to the config directory, which is created if needed.
+pub fn save_config(file_name: &str, description: &str, table: &Table) {
+    let path = get_config_dir().join(file_name);
+
+    let result = fs::create_dir_all(get_config_dir())
+        .and_then(|_| fs::write(&path, table.to_string()));
+
+    if let Err(e) = result {
+        eprintln!("Error writing {} to {}: {}", description, path.display(), e);
+    }
+}
+
+/// Returns a stable identifier for the given device-node, which survives
+/// re-plugging and reboots.
+///
+/// The name of the matching symlink in `/dev/v4l/by-id` is used. If there is
+/// none, e.g. for non-USB devices, the device path itself is returned.
+pub fn get_device_id(device_path: &str) -> String {
+    match find_symlink_to(BY_ID_DIR, device_path) {
+        Some(id) => id,
+        None => device_path.to_string(),
+    }
+}
+
+const BY_ID_DIR: &str = "/dev/v4l/by-id";
+
+/// Resolves a stable identifier, as returned by `get_device_id`, back to the
+/// current device-node. Device paths are returned as they are.
+pub fn resolve_device_id(device_id: &str) -> Option<String> {
+    if device_id.starts_with("/dev/") {
+        return Some(device_id.to_string());
+    }
+
+    let link = Path::new(BY_ID_DIR).join(device_id);
+    match fs::canonicalize(link) {
+        Ok(p) => p.to_str().map(|s| s.to_string()),
+        Err(_) => None,
+    }
+}
+
+/// Lists the persistent symlinks of the device-node in `/dev/v4l/by-id` and
+/// `/dev/v4l/by-path`.
+pub fn get_device_links(device_path: &str) -> Vec<String> {
+    let mut links = vec![];
+
+    for dir in [BY_ID_DIR, BY_PATH_DIR] {
+        for name in find_symlinks_to(dir, device_path) {
+            links.push(format!("{}/{}", dir, name));
+        }
+    }
+
+    links
+}
+
+const BY_PATH_DIR: &str = "/dev/v4l/by-path";
+
+/// Searches `dir` for a symlink pointing to `target` and returns its name.
+fn find_symlink_to(dir: &str, target: &str) -> Option<String> {
+    find_symlinks_to(dir, target).into_iter().next()
+}
+
+/// Searches `dir` for all symlinks pointing to `target` and returns their
+/// names, sorted.
+fn find_symlinks_to(dir: &str, target: &str) -> Vec<String> {
+    let mut names = vec![];
+
+    let target = match fs::canonicalize(target) {
+        Ok(t) => t,
+        Err(_) => return names,
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return names,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_symlink() {
+            continue;
+        }
+
+        match fs::canonicalize(&path) {
+            Ok(p) if p == target => {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+            _ => continue,
+        }
+    }
+
+    names.sort();
+    names
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,7 +172,7 @@ mod tests {
         File::create(&video_file_1).unwrap();
         File::create(&non_video_file).unwrap();
 
-        let mut result = _get_video_devices(dir_path.to_str().unwrap());
+        let mut result = get_video_devices(dir_path.to_str().unwrap());
 
         // Convert paths to strings for easier comparison
         result.sort();
@@ -53,4 +183,32 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_find_symlink_to() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path();
+
+        let video_file = dir_path.join("video0");
+        File::create(&video_file).unwrap();
+
+        let by_id = dir_path.join("by-id");
+        fs::create_dir(&by_id).unwrap();
+        std::os::unix::fs::symlink(&video_file, by_id.join("usb-Cam_1234-video-index0")).unwrap();
+
+        let result = find_symlink_to(
+            by_id.to_str().unwrap(),
+            video_file.to_str().unwrap()
+        );
+        assert_eq!(result, Some("usb-Cam_1234-video-index0".to_string()));
+
+        let other_file = dir_path.join("video1");
+        File::create(&other_file).unwrap();
+
+        let missing = find_symlink_to(
+            by_id.to_str().unwrap(),
+            other_file.to_str().unwrap()
+        );
+        assert_eq!(missing, None);
+    }
 }
@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+
+use crate::profiles::LiveControl;
+
+/// Intervals longer than this many nominal intervals are counted as dropped
+/// frames
+const DROP_THRESHOLD: f64 = 1.5;
+/// Longer gaps are a paused stream, e.g. while the format changes, rather
+/// than dropped frames
+const MAX_DROP_GAP: i64 = 1_000_000;
+/// Seconds of history, which are kept
+pub const HISTORY_SECONDS: f64 = 120.0;
+/// Seconds before and after a change, which are compared
+const IMPACT_SECONDS: f64 = 3.0;
+
+/// Times of the frames since the last sample, in microseconds.
+#[derive(Default)]
+pub struct FrameTimes {
+    last: Option<i64>,
+    intervals: Vec<i64>,
+    frames: u32,
+}
+
+impl FrameTimes {
+    pub fn add_frame(&mut self, time: i64) {
+        if let Some(last) = self.last {
+            self.intervals.push(time - last);
+        }
+
+        self.last = Some(time);
+        self.frames += 1;
+    }
+
+    /// Summarizes the frames within `duration` and starts the next sample.
+    /// Without a nominal interval, the median interval is expected.
+    pub fn take_sample(&mut self, duration: i64, nominal_interval: Option<f64>) -> Sample {
+        let intervals = std::mem::take(&mut self.intervals);
+        let frames = std::mem::take(&mut self.frames);
+
+        let fps = match duration {
+            d if d > 0 => frames as f64 * 1_000_000.0 / d as f64,
+            _ => 0.0,
+        };
+
+        Sample {
+            fps,
+            jitter: standard_deviation(&intervals) / 1000.0,
+            dropped: count_dropped(&intervals, nominal_interval.or_else(|| median(&intervals))),
+        }
+    }
+}
+
+/// Statistics of one period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub fps: f64,
+    /// Standard deviation of the frame intervals in milliseconds
+    pub jitter: f64,
+    pub dropped: u32,
+}
+
+/// A change of a control or the format, at seconds since the start.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub time: f64,
+    pub label: String,
+}
+
+/// Samples and changes by seconds since the start.
+#[derive(Default)]
+pub struct Timeline {
+    pub samples: VecDeque<(f64, Sample)>,
+    pub markers: VecDeque<Marker>,
+    pub dropped_total: u64,
+}
+
+impl Timeline {
+    pub fn add_sample(&mut self, time: f64, sample: Sample) {
+        self.dropped_total += sample.dropped as u64;
+        self.samples.push_back((time, sample));
+
+        while self.samples.front().is_some_and(|(t, _)| *t < time - HISTORY_SECONDS) {
+            self.samples.pop_front();
+        }
+        while self.markers.front().is_some_and(|m| m.time < time - HISTORY_SECONDS) {
+            self.markers.pop_front();
+        }
+    }
+
+    pub fn add_marker(&mut self, time: f64, label: String) {
+        self.markers.push_back(Marker { time, label });
+    }
+
+    /// Mean frame rate before and after the change. The second right after
+    /// is skipped, as the camera settles.
+    pub fn impact(&self, marker: &Marker) -> Option<(f64, f64)> {
+        let before = self.mean_fps(marker.time - IMPACT_SECONDS, marker.time)?;
+        let after = self.mean_fps(marker.time + 1.0, marker.time + 1.0 + IMPACT_SECONDS)?;
+        Some((before, after))
+    }
+
+    fn mean_fps(&self, from: f64, to: f64) -> Option<f64> {
+        let fps: Vec<f64> = self
+            .samples
+            .iter()
+            .filter(|(t, _)| *t > from && *t <= to)
+            .map(|(_, s)| s.fps)
+            .collect();
+
+        if fps.is_empty() {
+            return None;
+        }
+        Some(fps.iter().sum::<f64>() / fps.len() as f64)
+    }
+}
+
+/// Describes the controls, which differ between both readings, e.g.
+/// `Brightness: 128 → 140`.
+pub fn describe_changes(old: &[LiveControl], new: &[LiveControl]) -> Vec<String> {
+    new.iter()
+        .filter_map(|control| {
+            let old_value = old.iter().find(|c| c.id == control.id)?.value?;
+            let new_value = control.value?;
+
+            (old_value != new_value).then(|| {
+                format!(
+                    "{}: {} → {}",
+                    control.name,
+                    control.format_value(old_value),
+                    control.format_value(new_value)
+                )
+            })
+        })
+        .collect()
+}
+
+fn count_dropped(intervals: &[i64], expected: Option<f64>) -> u32 {
+    let expected = match expected {
+        Some(e) if e > 0.0 => e,
+        _ => return 0,
+    };
+
+    intervals
+        .iter()
+        .filter(|i| **i as f64 > expected * DROP_THRESHOLD && **i <= MAX_DROP_GAP)
+        .map(|i| ((*i as f64 / expected).round() as u32).max(2) - 1)
+        .sum()
+}
+
+fn median(intervals: &[i64]) -> Option<f64> {
+    let mut sorted = intervals.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).map(|i| *i as f64)
+}
+
+fn standard_deviation(intervals: &[i64]) -> f64 {
+    if intervals.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = intervals.iter().sum::<i64>() as f64 / intervals.len() as f64;
+    let variance = intervals.iter().map(|i| (*i as f64 - mean).powi(2)).sum::<f64>() / intervals.len() as f64;
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_sample() {
+        let mut times = FrameTimes::default();
+
+        // 10 fps with the 4th and 5th frame missing and a pause of the stream
+        for time in [0, 100_000, 200_000, 500_000, 600_000, 700_000, 2_700_000] {
+            times.add_frame(time);
+        }
+
+        let sample = times.take_sample(2_000_000, Some(100_000.0));
+        assert_eq!(sample.fps, 3.5);
+        assert_eq!(sample.dropped, 2);
+        assert!(sample.jitter > 0.0);
+
+        // The median of regular intervals is expected without nominal interval
+        times.add_frame(2_800_000);
+        times.add_frame(2_900_000);
+        times.add_frame(3_100_000);
+        let sample = times.take_sample(1_000_000, None);
+        assert_eq!((sample.fps, sample.dropped), (3.0, 1));
+
+        assert_eq!(times.take_sample(1_000_000, None), Sample { fps: 0.0, jitter: 0.0, dropped: 0 });
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut timeline = Timeline::default();
+        let sample = |fps| Sample { fps, jitter: 0.0, dropped: 1 };
+
+        for time in 1..=10 {
+            timeline.add_sample(time as f64, sample(if time <= 5 { 30.0 } else { 15.0 }));
+        }
+        timeline.add_marker(5.5, "Exposure: 100 → 300".to_string());
+
+        assert_eq!(timeline.impact(&timeline.markers[0]), Some((30.0, 15.0)));
+        assert_eq!(timeline.dropped_total, 10);
+
+        timeline.add_sample(200.0, sample(30.0));
+        assert_eq!(timeline.samples.len(), 1);
+        assert!(timeline.markers.is_empty());
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let control = |id, name: &str, value| LiveControl {
+            id,
+            name: name.to_string(),
+            minimum: 0,
+            maximum: 3,
+            step: 1,
+            menu_items: (id == 2).then(|| vec![(1, "Manual".to_string()), (3, "Aperture Priority".to_string())]),
+            read_only: false,
+            value,
+        };
+
+        let old = [control(1, "Gain", Some(0)), control(2, "Auto Exposure", Some(3)), control(3, "Zoom", None)];
+        let new = [control(1, "Gain", Some(0)), control(2, "Auto Exposure", Some(1)), control(3, "Zoom", Some(1))];
+
+        assert_eq!(describe_changes(&old, &new), ["Auto Exposure: Aperture Priority (3) → Manual (1)"]);
+    }
+}
@@ -0,0 +1,134 @@
+/// Share of the frame for actions, as in EBU R 95
+pub const ACTION_SAFE: f64 = 0.93;
+/// Share of the frame for titles, as in EBU R 95
+pub const TITLE_SAFE: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    /// The largest rectangle of the ratio, centered within this one.
+    pub fn fit(&self, ratio: f64) -> Rect {
+        let (width, height) = if self.width / self.height > ratio {
+            (self.height * ratio, self.height)
+        } else {
+            (self.width, self.width / ratio)
+        };
+
+        Rect {
+            x: self.x + (self.width - width) / 2.0,
+            y: self.y + (self.height - height) / 2.0,
+            width,
+            height,
+        }
+    }
+
+    /// A centered rectangle with the share of the width and height.
+    pub fn scale(&self, share: f64) -> Rect {
+        let (width, height) = (self.width * share, self.height * share);
+
+        Rect {
+            x: self.x + (self.width - width) / 2.0,
+            y: self.y + (self.height - height) / 2.0,
+            width,
+            height,
+        }
+    }
+
+    /// Positions of the lines, dividing the rectangle into thirds, as x and y.
+    pub fn thirds(&self) -> ([f64; 2], [f64; 2]) {
+        (
+            [self.x + self.width / 3.0, self.x + self.width * 2.0 / 3.0],
+            [self.y + self.height / 3.0, self.y + self.height * 2.0 / 3.0],
+        )
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectRatio {
+    Wide,
+    Standard,
+    Square,
+    Portrait,
+}
+
+impl AspectRatio {
+    pub const ALL: [AspectRatio; 4] = [
+        AspectRatio::Wide,
+        AspectRatio::Standard,
+        AspectRatio::Square,
+        AspectRatio::Portrait,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AspectRatio::Wide => "16:9",
+            AspectRatio::Standard => "4:3",
+            AspectRatio::Square => "1:1",
+            AspectRatio::Portrait => "9:16",
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        match self {
+            AspectRatio::Wide => 16.0 / 9.0,
+            AspectRatio::Standard => 4.0 / 3.0,
+            AspectRatio::Square => 1.0,
+            AspectRatio::Portrait => 9.0 / 16.0,
+        }
+    }
+}
+
+/// Where the frame is shown within the preview, which fits the frame with its
+/// aspect ratio.
+pub fn frame_rect(area_width: f64, area_height: f64, frame_width: f64, frame_height: f64) -> Option<Rect> {
+    if area_width <= 0.0 || area_height <= 0.0 || frame_width <= 0.0 || frame_height <= 0.0 {
+        return None;
+    }
+
+    let area = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: area_width,
+        height: area_height,
+    };
+    Some(area.fit(frame_width / frame_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_rect() {
+        // Pillarboxed and letterboxed
+        let frame = frame_rect(1000.0, 600.0, 1280.0, 960.0).unwrap();
+        assert_eq!(frame, Rect { x: 100.0, y: 0.0, width: 800.0, height: 600.0 });
+        let frame = frame_rect(800.0, 800.0, 1920.0, 1080.0).unwrap();
+        assert_eq!(frame, Rect { x: 0.0, y: 175.0, width: 800.0, height: 450.0 });
+
+        assert_eq!(frame_rect(800.0, 600.0, 0.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_guides() {
+        let frame = Rect { x: 100.0, y: 0.0, width: 800.0, height: 600.0 };
+
+        assert_eq!(frame.fit(AspectRatio::Wide.ratio()), Rect { x: 100.0, y: 75.0, width: 800.0, height: 450.0 });
+        assert_eq!(frame.fit(AspectRatio::Portrait.ratio()).width, 337.5);
+        assert_eq!(frame.fit(AspectRatio::Square.ratio()).x, 200.0);
+
+        assert_eq!(frame.scale(0.5), Rect { x: 300.0, y: 150.0, width: 400.0, height: 300.0 });
+        assert_eq!(frame.thirds(), ([100.0 + 800.0 / 3.0, 100.0 + 1600.0 / 3.0], [200.0, 400.0]));
+        assert_eq!(frame.center(), (500.0, 300.0));
+    }
+}
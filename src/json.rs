@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+/// Minimal JSON-value for machine-readable output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Creates an object from key-value pairs, keeping their order.
+    pub fn object(entries: Vec<(&str, JsonValue)>) -> Self {
+        JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    pub fn string(value: &str) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl Display for JsonValue {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Integer(i) => write!(f, "{}", i),
+            // JSON has no representation of NaN and infinity
+            JsonValue::Float(v) if !v.is_finite() => write!(f, "null"),
+            JsonValue::Float(v) => write!(f, "{}", v),
+            JsonValue::String(s) => write_escaped(f, s),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_escaped(f: &mut Formatter, s: &str) -> Result<(), std::fmt::Error> {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_output() {
+        let value = JsonValue::object(vec![
+            ("name", JsonValue::string("Cam \"HD\"\n")),
+            ("id", JsonValue::Integer(-3)),
+            ("flags", JsonValue::Array(vec![JsonValue::string("a"), JsonValue::Null])),
+            ("fps", JsonValue::Array(vec![JsonValue::Float(7.5), JsonValue::Float(f64::NAN)])),
+        ]);
+
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"Cam \"HD\"\n","id":-3,"flags":["a",null],"fps":[7.5,null]}"#
+        );
+    }
+
+    #[test]
+    fn test_control_characters() {
+        assert_eq!(JsonValue::string("a\u{1}b").to_string(), r#""a\u0001b""#);
+    }
+}
@@ -5,25 +5,54 @@ use application::Application;
 
 mod application;
 mod camera;
+mod capability_flags;
+mod capture_mode;
+mod capture_settings;
+mod cli;
 mod components;
+mod control_events;
 mod controls;
+mod daemon;
+mod device_discovery;
+mod device_info;
+mod device_usage;
 mod files;
+mod frame_statistics;
+mod framing_guides;
+mod json;
 mod key_value_item;
+mod media_controller;
+mod mode_matrix;
+mod native_stream;
+mod pixel_conversion;
+mod preview_frame;
+mod preview_overlays;
+mod profiles;
+mod quirks;
+mod scopes;
+mod sidecar;
+mod startup_options;
+mod still_capture;
+mod uvc_xu;
+mod video_recording;
 mod widgets;
+mod window_state;
 
 // Next Steps
 // TODO About Dialog
 // TODO Flatpack packaging
-// TODO What if device is in use by other app?
-// TODO What if aperture assertion?
 // TODO All controls
-// TODO Hot (de-)plug?
 // TODO Error / Notice, when controls cannot be read
-// TODO CLI-Param to overide /dev/video*
 fn main() -> glib::ExitCode {
+    // Registered first, as subcommands use the bundled quirks as well
     gio::resources_register_include!("camera_settings.gresource")
         .expect("Failed to register resources.");
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|a| cli::is_subcommand(a)) {
+        return cli::run(&args[1..]);
+    }
+
     let app = crate::Application::new();
     app.run()
 }
@@ -0,0 +1,514 @@
+use std::{collections::HashMap, fs, io, mem, os::unix::fs::MetadataExt, os::unix::io::AsRawFd};
+
+use v4l::v4l2::{self, vidioc::_IOC_TYPE};
+
+// Not provided by the v4l-crate, see linux/media.h
+const MEDIA_IOC_DEVICE_INFO: _IOC_TYPE = media_ioc(0x00, mem::size_of::<media_device_info>());
+const MEDIA_IOC_SETUP_LINK: _IOC_TYPE = media_ioc(0x03, mem::size_of::<media_link_desc>());
+const MEDIA_IOC_G_TOPOLOGY: _IOC_TYPE = media_ioc(0x04, mem::size_of::<media_v2_topology>());
+
+const fn media_ioc(nr: u32, size: usize) -> _IOC_TYPE {
+    // Read and write
+    ((3 << 30) | ((size as u32) << 16) | ((b'|' as u32) << 8) | nr) as _IOC_TYPE
+}
+
+const MEDIA_LNK_FL_ENABLED: u32 = 1 << 0;
+const MEDIA_LNK_FL_IMMUTABLE: u32 = 1 << 1;
+const MEDIA_LNK_FL_LINK_TYPE: u32 = 0xf << 28;
+const MEDIA_LNK_FL_DATA_LINK: u32 = 0;
+const MEDIA_LNK_FL_INTERFACE_LINK: u32 = 1 << 28;
+
+const DEVICE_DIR: &str = "/dev";
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_device_info {
+    driver: [u8; 16],
+    model: [u8; 32],
+    serial: [u8; 40],
+    bus_info: [u8; 32],
+    media_version: u32,
+    hw_revision: u32,
+    driver_version: u32,
+    reserved: [u32; 31],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_v2_topology {
+    topology_version: u64,
+    num_entities: u32,
+    reserved1: u32,
+    ptr_entities: u64,
+    num_interfaces: u32,
+    reserved2: u32,
+    ptr_interfaces: u64,
+    num_pads: u32,
+    reserved3: u32,
+    ptr_pads: u64,
+    num_links: u32,
+    reserved4: u32,
+    ptr_links: u64,
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_v2_entity {
+    id: u32,
+    name: [u8; 64],
+    function: u32,
+    flags: u32,
+    reserved: [u32; 5],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_v2_interface {
+    id: u32,
+    intf_type: u32,
+    flags: u32,
+    reserved: [u32; 9],
+    // Union with the devnode as first member
+    devnode_major: u32,
+    devnode_minor: u32,
+    raw: [u32; 14],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_v2_pad {
+    id: u32,
+    entity_id: u32,
+    flags: u32,
+    index: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_v2_link {
+    id: u32,
+    source_id: u32,
+    sink_id: u32,
+    flags: u32,
+    reserved: [u32; 6],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_pad_desc {
+    entity: u32,
+    index: u16,
+    flags: u32,
+    reserved: [u32; 2],
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct media_link_desc {
+    source: media_pad_desc,
+    sink: media_pad_desc,
+    flags: u32,
+    reserved: [u32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaEntity {
+    pub id: u32,
+    pub name: String,
+    pub function: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPad {
+    pub id: u32,
+    pub entity_id: u32,
+    pub index: u32,
+    pub flags: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaLink {
+    pub id: u32,
+    /// Source pad, or the interface for interface-links
+    pub source_id: u32,
+    /// Sink pad, or the entity for interface-links
+    pub sink_id: u32,
+    pub flags: u32,
+}
+
+impl MediaLink {
+    pub fn is_data_link(&self) -> bool {
+        self.flags & MEDIA_LNK_FL_LINK_TYPE == MEDIA_LNK_FL_DATA_LINK
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.flags & MEDIA_LNK_FL_ENABLED != 0
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.flags & MEDIA_LNK_FL_IMMUTABLE == 0
+    }
+}
+
+/// A device-node, through which an entity is controlled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInterface {
+    pub id: u32,
+    pub device_number: (u32, u32),
+    /// Path of the device-node, e.g. /dev/video0 or /dev/v4l-subdev1
+    pub devnode: Option<String>,
+}
+
+/// The graph of a media controller device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaTopology {
+    pub media_path: String,
+    pub driver: String,
+    pub model: String,
+    pub bus_info: String,
+    pub entities: Vec<MediaEntity>,
+    pub pads: Vec<MediaPad>,
+    pub links: Vec<MediaLink>,
+    pub interfaces: Vec<MediaInterface>,
+}
+
+impl MediaTopology {
+    pub fn data_links(&self) -> Vec<&MediaLink> {
+        self.links.iter().filter(|l| l.is_data_link()).collect()
+    }
+
+    pub fn pad(&self, pad_id: u32) -> Option<&MediaPad> {
+        self.pads.iter().find(|p| p.id == pad_id)
+    }
+
+    pub fn entity(&self, entity_id: u32) -> Option<&MediaEntity> {
+        self.entities.iter().find(|e| e.id == entity_id)
+    }
+
+    /// Source and sink entity of a data-link.
+    pub fn link_entities(&self, link: &MediaLink) -> Option<(&MediaEntity, &MediaEntity)> {
+        let source = self.entity(self.pad(link.source_id)?.entity_id)?;
+        let sink = self.entity(self.pad(link.sink_id)?.entity_id)?;
+        Some((source, sink))
+    }
+
+    /// Device-nodes of the entity, e.g. the video node of a DMA engine.
+    pub fn devnodes_of_entity(&self, entity_id: u32) -> Vec<String> {
+        self.links
+            .iter()
+            .filter(|l| l.flags & MEDIA_LNK_FL_LINK_TYPE == MEDIA_LNK_FL_INTERFACE_LINK)
+            .filter(|l| l.sink_id == entity_id)
+            .filter_map(|l| self.interfaces.iter().find(|i| i.id == l.source_id))
+            .filter_map(|i| i.devnode.clone())
+            .collect()
+    }
+
+    /// Describes the link as "source:pad → sink:pad".
+    pub fn describe_link(&self, link: &MediaLink) -> String {
+        let describe_pad = |pad_id: u32| match self.pad(pad_id) {
+            Some(pad) => {
+                let name = self
+                    .entity(pad.entity_id)
+                    .map_or("Unknown".to_string(), |e| e.name.clone());
+                format!("{}:{}", name, pad.index)
+            }
+            None => format!("Pad {}", pad_id),
+        };
+
+        format!("{} → {}", describe_pad(link.source_id), describe_pad(link.sink_id))
+    }
+}
+
+/// Finds the media controller, the video device-node belongs to, by looking
+/// for the node in the topology of each media device.
+pub fn find_media_device(video_path: &str) -> Option<MediaTopology> {
+    let rdev = fs::metadata(video_path).ok()?.rdev();
+    let device_number = (libc::major(rdev), libc::minor(rdev));
+
+    let mut media_paths: Vec<String> = fs::read_dir(DEVICE_DIR)
+        .ok()?
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+        .filter(|n| n.starts_with("media"))
+        .map(|n| format!("{}/{}", DEVICE_DIR, n))
+        .collect();
+    media_paths.sort();
+
+    media_paths
+        .iter()
+        .filter_map(|p| match read_topology(p) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("Error reading topology of {}: {}", p, e);
+                None
+            }
+        })
+        .find(|t| t.interfaces.iter().any(|i| i.device_number == device_number))
+}
+
+pub fn read_topology(media_path: &str) -> io::Result<MediaTopology> {
+    let file = fs::File::open(media_path)?;
+    let fd = file.as_raw_fd();
+
+    let mut info: media_device_info = unsafe { mem::zeroed() };
+    unsafe {
+        v4l2::ioctl(fd, MEDIA_IOC_DEVICE_INFO, &mut info as *mut _ as *mut std::os::raw::c_void)?;
+    }
+
+    // The first call returns the number of elements, the second fills them
+    let mut topology: media_v2_topology = unsafe { mem::zeroed() };
+    unsafe {
+        v4l2::ioctl(fd, MEDIA_IOC_G_TOPOLOGY, &mut topology as *mut _ as *mut std::os::raw::c_void)?;
+    }
+
+    let mut entities: Vec<media_v2_entity> = (0..topology.num_entities).map(|_| unsafe { mem::zeroed() }).collect();
+    let mut interfaces: Vec<media_v2_interface> = (0..topology.num_interfaces).map(|_| unsafe { mem::zeroed() }).collect();
+    let mut pads: Vec<media_v2_pad> = (0..topology.num_pads).map(|_| unsafe { mem::zeroed() }).collect();
+    let mut links: Vec<media_v2_link> = (0..topology.num_links).map(|_| unsafe { mem::zeroed() }).collect();
+
+    topology.ptr_entities = entities.as_mut_ptr() as u64;
+    topology.ptr_interfaces = interfaces.as_mut_ptr() as u64;
+    topology.ptr_pads = pads.as_mut_ptr() as u64;
+    topology.ptr_links = links.as_mut_ptr() as u64;
+
+    unsafe {
+        v4l2::ioctl(fd, MEDIA_IOC_G_TOPOLOGY, &mut topology as *mut _ as *mut std::os::raw::c_void)?;
+    }
+
+    // The graph may have shrunk in between
+    entities.truncate(topology.num_entities as usize);
+    interfaces.truncate(topology.num_interfaces as usize);
+    pads.truncate(topology.num_pads as usize);
+    links.truncate(topology.num_links as usize);
+
+    Ok(MediaTopology {
+        media_path: media_path.to_string(),
+        driver: c_string(&info.driver),
+        model: c_string(&info.model),
+        bus_info: c_string(&info.bus_info),
+        entities: entities
+            .iter()
+            .map(|e| MediaEntity {
+                id: e.id,
+                name: c_string(&e.name),
+                function: e.function,
+            })
+            .collect(),
+        pads: pads
+            .iter()
+            .map(|p| MediaPad {
+                id: p.id,
+                entity_id: p.entity_id,
+                index: p.index,
+                flags: p.flags,
+            })
+            .collect(),
+        links: links
+            .iter()
+            .map(|l| MediaLink {
+                id: l.id,
+                source_id: l.source_id,
+                sink_id: l.sink_id,
+                flags: l.flags,
+            })
+            .collect(),
+        interfaces: interfaces
+            .iter()
+            .map(|i| MediaInterface {
+                id: i.id,
+                device_number: (i.devnode_major, i.devnode_minor),
+                devnode: find_devnode(i.devnode_major, i.devnode_minor),
+            })
+            .collect(),
+    })
+}
+
+/// Enables or disables a mutable data-link.
+pub fn setup_link(topology: &MediaTopology, link: &MediaLink, enabled: bool) -> io::Result<()> {
+    let (source, sink) = match (topology.pad(link.source_id), topology.pad(link.sink_id)) {
+        (Some(source), Some(sink)) => (source, sink),
+        _ => return Err(io::Error::new(io::ErrorKind::NotFound, "Pads of the link not found")),
+    };
+
+    let pad_desc = |pad: &MediaPad| media_pad_desc {
+        entity: pad.entity_id,
+        index: pad.index as u16,
+        flags: pad.flags,
+        reserved: [0; 2],
+    };
+
+    let mut desc = media_link_desc {
+        source: pad_desc(source),
+        sink: pad_desc(sink),
+        flags: match enabled {
+            true => link.flags | MEDIA_LNK_FL_ENABLED,
+            false => link.flags & !MEDIA_LNK_FL_ENABLED,
+        },
+        reserved: [0; 2],
+    };
+
+    let file = fs::OpenOptions::new().read(true).write(true).open(&topology.media_path)?;
+    unsafe {
+        v4l2::ioctl(
+            file.as_raw_fd(),
+            MEDIA_IOC_SETUP_LINK,
+            &mut desc as *mut _ as *mut std::os::raw::c_void,
+        )
+    }
+}
+
+/// Names the function of an entity, see MEDIA_ENT_F_* in linux/media.h.
+pub fn entity_function_label(function: u32) -> String {
+    let label = match function {
+        0x00010001 => "I/O",
+        0x00010002 => "VBI I/O",
+        0x00010003 => "SDR I/O",
+        0x00020001 => "Camera sensor",
+        0x00020002 => "Flash",
+        0x00020003 => "Lens",
+        0x00020004 => "Video decoder",
+        0x00020005 => "Tuner",
+        0x00004001 => "Composer",
+        0x00004002 => "Pixel formatter",
+        0x00004003 => "Format converter",
+        0x00004004 => "Look-up table",
+        0x00004005 => "Scaler",
+        0x00004006 => "Statistics",
+        0x00004007 => "Encoder",
+        0x00004008 => "Decoder",
+        0x00004009 => "ISP",
+        0x00005001 => "Multiplexer",
+        0x00005002 => "Interface bridge",
+        0x00030001 => "RF connector",
+        0x00030002 => "S-Video connector",
+        0x00030003 => "Composite connector",
+        _ => return format!("Function {:#x}", function),
+    };
+
+    label.to_string()
+}
+
+/// Arranges the entities in columns, following the data-links from sources
+/// to sinks. Returns the column and row of each entity.
+pub fn layout_entities(topology: &MediaTopology) -> HashMap<u32, (usize, usize)> {
+    let mut columns: HashMap<u32, usize> = topology.entities.iter().map(|e| (e.id, 0)).collect();
+
+    let edges: Vec<(u32, u32)> = topology
+        .data_links()
+        .iter()
+        .filter_map(|l| topology.link_entities(l))
+        .map(|(source, sink)| (source.id, sink.id))
+        .filter(|(source, sink)| source != sink)
+        .collect();
+
+    // Longest path from a source, limited in case of cycles
+    for _ in 0..topology.entities.len() {
+        let mut changed = false;
+        for (source, sink) in &edges {
+            let column = columns[source] + 1;
+            if column > columns[sink] && column < topology.entities.len() {
+                columns.insert(*sink, column);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut entity_ids: Vec<u32> = topology.entities.iter().map(|e| e.id).collect();
+    entity_ids.sort();
+
+    let mut rows: HashMap<usize, usize> = HashMap::new();
+    let mut positions = HashMap::new();
+    for id in entity_ids {
+        let column = columns[&id];
+        let row = rows.entry(column).or_insert(0);
+        positions.insert(id, (column, *row));
+        *row += 1;
+    }
+
+    positions
+}
+
+fn find_devnode(major: u32, minor: u32) -> Option<String> {
+    if major == 0 && minor == 0 {
+        return None;
+    }
+
+    let target = fs::read_link(format!("/sys/dev/char/{}:{}", major, minor)).ok()?;
+    let name = target.file_name()?.to_str()?;
+    Some(format!("{}/{}", DEVICE_DIR, name))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_topology() -> MediaTopology {
+        let entity = |id: u32, name: &str| MediaEntity { id, name: name.to_string(), function: 0 };
+        let pad = |id: u32, entity_id: u32, index: u32, flags: u32| MediaPad { id, entity_id, index, flags };
+        let (sink, source) = (1, 2);
+
+        MediaTopology {
+            entities: vec![entity(1, "sensor"), entity(2, "csi"), entity(3, "dma"), entity(4, "stats")],
+            pads: vec![
+                pad(10, 1, 0, source),
+                pad(20, 2, 0, sink),
+                pad(21, 2, 1, source),
+                pad(30, 3, 0, sink),
+                pad(40, 4, 0, sink),
+            ],
+            links: vec![
+                MediaLink { id: 100, source_id: 10, sink_id: 20, flags: MEDIA_LNK_FL_ENABLED | MEDIA_LNK_FL_IMMUTABLE },
+                MediaLink { id: 101, source_id: 21, sink_id: 30, flags: MEDIA_LNK_FL_ENABLED },
+                MediaLink { id: 102, source_id: 21, sink_id: 40, flags: 0 },
+                MediaLink { id: 103, source_id: 200, sink_id: 3, flags: MEDIA_LNK_FL_INTERFACE_LINK },
+            ],
+            interfaces: vec![MediaInterface {
+                id: 200,
+                device_number: (81, 0),
+                devnode: Some("/dev/video0".to_string()),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layout_entities() {
+        let positions = layout_entities(&sample_topology());
+
+        assert_eq!(positions[&1], (0, 0));
+        assert_eq!(positions[&2], (1, 0));
+        assert_eq!(positions[&3], (2, 0));
+        assert_eq!(positions[&4], (2, 1));
+    }
+
+    #[test]
+    fn test_links() {
+        let topology = sample_topology();
+        let links = topology.data_links();
+
+        assert_eq!(links.len(), 3);
+        assert!(!links[0].is_mutable());
+        assert!(links[2].is_mutable() && !links[2].is_enabled());
+        assert_eq!(topology.describe_link(links[1]), "csi:1 → dma:0");
+        assert_eq!(topology.devnodes_of_entity(3), vec!["/dev/video0".to_string()]);
+    }
+
+    #[test]
+    fn test_struct_sizes() {
+        assert_eq!(mem::size_of::<media_device_info>(), 256);
+        assert_eq!(mem::size_of::<media_v2_topology>(), 72);
+        assert_eq!(mem::size_of::<media_v2_entity>(), 96);
+        assert_eq!(mem::size_of::<media_v2_interface>(), 112);
+        assert_eq!(mem::size_of::<media_link_desc>(), 52);
+    }
+}
@@ -0,0 +1,382 @@
+use std::io;
+
+use v4l::{
+    format::FourCC,
+    frameinterval::FrameIntervalEnum,
+    framesize::FrameSizeEnum,
+    video::Capture,
+    Device,
+};
+
+use crate::{
+    capture_mode::{Interval, Resolution},
+    json::JsonValue,
+};
+
+/// How a range of frame sizes or intervals is enumerated by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeKind {
+    Discrete,
+    Stepwise,
+    Continuous,
+}
+
+impl RangeKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RangeKind::Discrete => "discrete",
+            RangeKind::Stepwise => "stepwise",
+            RangeKind::Continuous => "continuous",
+        }
+    }
+}
+
+/// Frame sizes with their frame intervals. Discrete sizes have the same
+/// minimum and maximum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeRange {
+    pub kind: RangeKind,
+    pub min: Resolution,
+    pub max: Resolution,
+    pub step: Resolution,
+    /// Frame intervals of the size, or of the maximum size for ranges
+    pub intervals: Vec<IntervalRange>,
+}
+
+impl SizeRange {
+    pub fn label(&self) -> String {
+        match self.kind {
+            RangeKind::Discrete => self.min.to_string(),
+            RangeKind::Stepwise => format!("{} – {} (step {})", self.min, self.max, self.step),
+            RangeKind::Continuous => format!("{} – {}", self.min, self.max),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntervalRange {
+    pub kind: RangeKind,
+    pub min: Interval,
+    pub max: Interval,
+    pub step: Interval,
+}
+
+impl IntervalRange {
+    /// Frame rates, the highest first.
+    pub fn label(&self) -> String {
+        match self.kind {
+            RangeKind::Discrete => self.min.to_string(),
+            _ => format!("{} – {}", self.min, self.max),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatMatrix {
+    pub fourcc: FourCC,
+    pub description: String,
+    pub sizes: Vec<SizeRange>,
+}
+
+/// The format currently set on the device, as reported by `G_FMT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentFormat {
+    pub fourcc: FourCC,
+    pub resolution: Resolution,
+    pub bytes_per_line: u32,
+    pub size_image: u32,
+    pub field: String,
+    pub colorspace: String,
+    pub quantization: String,
+    pub transfer: String,
+}
+
+impl CurrentFormat {
+    /// Labeled values for display.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Pixel format", self.fourcc.to_string()),
+            ("Resolution", self.resolution.to_string()),
+            ("Bytes per line", self.bytes_per_line.to_string()),
+            ("Size image", format!("{} bytes", self.size_image)),
+            ("Field", self.field.clone()),
+            ("Colorspace", self.colorspace.clone()),
+            ("Quantization", self.quantization.clone()),
+            ("Transfer function", self.transfer.clone()),
+        ]
+    }
+}
+
+pub fn read_current_format(device: &Device) -> io::Result<CurrentFormat> {
+    let format = device.format()?;
+
+    Ok(CurrentFormat {
+        fourcc: format.fourcc,
+        resolution: Resolution {
+            width: format.width,
+            height: format.height,
+        },
+        bytes_per_line: format.stride,
+        size_image: format.size,
+        field: format.field_order.to_string(),
+        colorspace: format.colorspace.to_string(),
+        quantization: format.quantization.to_string(),
+        transfer: format.transfer.to_string(),
+    })
+}
+
+/// Enumerates all pixel formats with their frame sizes and frame intervals,
+/// as reported by the driver.
+pub fn read_mode_matrix(device: &Device) -> io::Result<Vec<FormatMatrix>> {
+    let mut formats = vec![];
+
+    for description in device.enum_formats()? {
+        let frame_sizes = match device.enum_framesizes(description.fourcc) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error listing frame sizes of {}: {}", description.fourcc, e);
+                vec![]
+            }
+        };
+
+        let sizes = frame_sizes
+            .into_iter()
+            .map(|frame_size| {
+                let mut size = match frame_size.size {
+                    FrameSizeEnum::Discrete(d) => {
+                        let resolution = Resolution {
+                            width: d.width,
+                            height: d.height,
+                        };
+                        SizeRange {
+                            kind: RangeKind::Discrete,
+                            min: resolution,
+                            max: resolution,
+                            step: Resolution { width: 0, height: 0 },
+                            intervals: vec![],
+                        }
+                    }
+                    FrameSizeEnum::Stepwise(s) => SizeRange {
+                        kind: match s.step_width <= 1 && s.step_height <= 1 {
+                            true => RangeKind::Continuous,
+                            false => RangeKind::Stepwise,
+                        },
+                        min: Resolution {
+                            width: s.min_width,
+                            height: s.min_height,
+                        },
+                        max: Resolution {
+                            width: s.max_width,
+                            height: s.max_height,
+                        },
+                        step: Resolution {
+                            width: s.step_width,
+                            height: s.step_height,
+                        },
+                        intervals: vec![],
+                    },
+                };
+
+                size.intervals = read_intervals(device, description.fourcc, size.max);
+                size
+            })
+            .collect();
+
+        formats.push(FormatMatrix {
+            fourcc: description.fourcc,
+            description: description.description,
+            sizes,
+        });
+    }
+
+    Ok(formats)
+}
+
+fn read_intervals(device: &Device, fourcc: FourCC, resolution: Resolution) -> Vec<IntervalRange> {
+    let frame_intervals = match device.enum_frameintervals(fourcc, resolution.width, resolution.height) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("Error listing frame intervals of {} {}: {}", fourcc, resolution, e);
+            return vec![];
+        }
+    };
+
+    frame_intervals
+        .into_iter()
+        .map(|frame_interval| match frame_interval.interval {
+            FrameIntervalEnum::Discrete(f) => IntervalRange {
+                kind: RangeKind::Discrete,
+                min: Interval::from(f),
+                max: Interval::from(f),
+                step: Interval {
+                    numerator: 0,
+                    denominator: 1,
+                },
+            },
+            FrameIntervalEnum::Stepwise(s) => IntervalRange {
+                kind: match s.step.numerator <= 1 && s.step.denominator <= 1 {
+                    true => RangeKind::Continuous,
+                    false => RangeKind::Stepwise,
+                },
+                min: Interval::from(s.min),
+                max: Interval::from(s.max),
+                step: Interval::from(s.step),
+            },
+        })
+        .collect()
+}
+
+/// Plain text for pasting into issues and documents.
+pub fn matrix_to_text(formats: &[FormatMatrix], current: Option<&CurrentFormat>) -> String {
+    let mut text = String::new();
+
+    if let Some(current) = current {
+        text.push_str("Current format\n");
+        for (label, value) in current.entries() {
+            text.push_str(&format!("  {}: {}\n", label, value));
+        }
+        text.push('\n');
+    }
+
+    for format in formats {
+        text.push_str(&format!("{} ({})\n", format.fourcc, format.description));
+        for size in &format.sizes {
+            let rates: Vec<String> = size.intervals.iter().map(|i| i.label()).collect();
+            text.push_str(&format!("  {}: {}\n", size.label(), rates.join(", ")));
+        }
+    }
+
+    text
+}
+
+pub fn matrix_to_json(formats: &[FormatMatrix], current: Option<&CurrentFormat>) -> JsonValue {
+    let resolution = |r: &Resolution| {
+        JsonValue::object(vec![
+            ("width", JsonValue::Integer(r.width as i64)),
+            ("height", JsonValue::Integer(r.height as i64)),
+        ])
+    };
+
+    let interval = |i: &Interval| {
+        JsonValue::object(vec![
+            ("numerator", JsonValue::Integer(i.numerator as i64)),
+            ("denominator", JsonValue::Integer(i.denominator as i64)),
+            ("fps", JsonValue::Float(i.fps())),
+        ])
+    };
+
+    let current = match current {
+        Some(c) => JsonValue::object(vec![
+            ("fourcc", JsonValue::string(&c.fourcc.to_string())),
+            ("width", JsonValue::Integer(c.resolution.width as i64)),
+            ("height", JsonValue::Integer(c.resolution.height as i64)),
+            ("bytes_per_line", JsonValue::Integer(c.bytes_per_line as i64)),
+            ("size_image", JsonValue::Integer(c.size_image as i64)),
+            ("field", JsonValue::string(&c.field)),
+            ("colorspace", JsonValue::string(&c.colorspace)),
+            ("quantization", JsonValue::string(&c.quantization)),
+            ("transfer", JsonValue::string(&c.transfer)),
+        ]),
+        None => JsonValue::Null,
+    };
+
+    let formats = formats
+        .iter()
+        .map(|f| {
+            let sizes = f
+                .sizes
+                .iter()
+                .map(|s| {
+                    let intervals = s
+                        .intervals
+                        .iter()
+                        .map(|i| {
+                            JsonValue::object(vec![
+                                ("type", JsonValue::string(i.kind.label())),
+                                ("min", interval(&i.min)),
+                                ("max", interval(&i.max)),
+                                ("step", interval(&i.step)),
+                            ])
+                        })
+                        .collect();
+
+                    JsonValue::object(vec![
+                        ("type", JsonValue::string(s.kind.label())),
+                        ("min", resolution(&s.min)),
+                        ("max", resolution(&s.max)),
+                        ("step", resolution(&s.step)),
+                        ("intervals", JsonValue::Array(intervals)),
+                    ])
+                })
+                .collect();
+
+            JsonValue::object(vec![
+                ("fourcc", JsonValue::string(&f.fourcc.to_string())),
+                ("description", JsonValue::string(&f.description)),
+                ("sizes", JsonValue::Array(sizes)),
+            ])
+        })
+        .collect();
+
+    JsonValue::object(vec![("current", current), ("formats", JsonValue::Array(formats))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> Vec<FormatMatrix> {
+        let discrete = |fps| IntervalRange {
+            kind: RangeKind::Discrete,
+            min: Interval::from_fps(fps),
+            max: Interval::from_fps(fps),
+            step: Interval {
+                numerator: 0,
+                denominator: 1,
+            },
+        };
+
+        vec![FormatMatrix {
+            fourcc: FourCC::new(b"MJPG"),
+            description: "Motion-JPEG".to_string(),
+            sizes: vec![
+                SizeRange {
+                    kind: RangeKind::Discrete,
+                    min: Resolution { width: 1280, height: 720 },
+                    max: Resolution { width: 1280, height: 720 },
+                    step: Resolution { width: 0, height: 0 },
+                    intervals: vec![discrete(30), discrete(15)],
+                },
+                SizeRange {
+                    kind: RangeKind::Stepwise,
+                    min: Resolution { width: 320, height: 240 },
+                    max: Resolution { width: 1920, height: 1080 },
+                    step: Resolution { width: 16, height: 8 },
+                    intervals: vec![IntervalRange {
+                        kind: RangeKind::Continuous,
+                        min: Interval::from_fps(60),
+                        max: Interval::from_fps(1),
+                        step: Interval::from_fps(1),
+                    }],
+                },
+            ],
+        }]
+    }
+
+    #[test]
+    fn test_matrix_to_text() {
+        assert_eq!(
+            matrix_to_text(&matrix(), None),
+            "MJPG (Motion-JPEG)\n  1280×720: 30 fps, 15 fps\n  320×240 – 1920×1080 (step 16×8): 60 fps – 1 fps\n"
+        );
+    }
+
+    #[test]
+    fn test_matrix_to_json() {
+        let json = matrix_to_json(&matrix(), None).to_string();
+
+        assert!(json.starts_with(r#"{"current":null,"formats":[{"fourcc":"MJPG","description":"Motion-JPEG","sizes":[{"type":"discrete","min":{"width":1280"#));
+        assert!(json.contains(r#""min":{"numerator":1,"denominator":30,"fps":30}"#));
+        assert!(json.contains(r#""type":"stepwise","min":{"width":320,"height":240}"#));
+    }
+}
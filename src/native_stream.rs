@@ -0,0 +1,143 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::debug;
+use v4l::{buffer::Type, device::Handle, io::mmap::Stream, io::traits::CaptureStream, video::Capture, Device};
+
+use crate::{
+    device_usage::is_busy_error,
+    pixel_conversion::{convert_to_rgba, is_supported, RgbaImage},
+};
+
+const BUFFER_COUNT: u32 = 4;
+// A previous stream, e.g. of the viewfinder, may release the device with a
+// delay
+const BUSY_RETRIES: u32 = 10;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(100);
+// Waiting for a frame is interrupted regularly, to notice when to stop
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Also covers slow modes, e.g. 1 fps, and cameras adjusting the exposure
+// before the first frame
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The latest decoded frame, or why streaming ended. Frames, which are not
+/// taken in time, are replaced.
+pub type LatestFrame = Arc<Mutex<Option<Result<RgbaImage, String>>>>;
+
+/// Streams from the device with mmap buffers in a thread, independent of
+/// PipeWire. The device is streamed in its current format.
+///
+/// Dropping the stream only tells the thread to stop, which releases the
+/// device within the poll interval, see `is_released`.
+pub struct NativeStream {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl NativeStream {
+    pub fn start(device_path: &str, latest: LatestFrame) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stop_for_thread = stop.clone();
+        let device_path = device_path.to_string();
+        let thread = thread::spawn(move || {
+            if let Err(e) = stream_frames(&device_path, &latest, &stop_for_thread) {
+                eprintln!("Error streaming {}: {}", device_path, e);
+                if let Ok(mut frame) = latest.lock() {
+                    *frame = Some(Err(e));
+                }
+            }
+        });
+
+        NativeStream {
+            stop,
+            thread,
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// True, once the thread ended and closed the device.
+    pub fn is_released(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+impl Drop for NativeStream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Frames are only taken with `next()`, once the device has one ready.
+/// `next()` queues the previous buffer again before waiting, so it must not
+/// time out, as it would queue the same buffer twice on the next call.
+fn stream_frames(device_path: &str, latest: &LatestFrame, stop: &AtomicBool) -> Result<(), String> {
+    let device = Device::with_path(device_path).map_err(|e| e.to_string())?;
+    let format = device.format().map_err(|e| format!("Error reading format: {}", e))?;
+    if !is_supported(&format.fourcc) {
+        return Err(format!("Pixel format {} is not supported by the direct preview", format.fourcc));
+    }
+
+    let mut stream = create_stream(&device, stop)?;
+    let handle = stream.handle();
+    // Only the first call of next() waits within, as it starts the stream
+    stream.set_timeout(FRAME_TIMEOUT);
+
+    let mut started = false;
+    while !stop.load(Ordering::Relaxed) {
+        if started && !is_frame_ready(&handle)? {
+            continue;
+        }
+        started = true;
+
+        let (buffer, meta) = match stream.next() {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                return Err(format!("No frame within {} seconds", FRAME_TIMEOUT.as_secs()));
+            }
+            Err(e) => return Err(format!("Error reading frame: {}", e)),
+        };
+
+        let used = (meta.bytesused as usize).min(buffer.len());
+        match convert_to_rgba(&buffer[..used], &format) {
+            Ok(image) => {
+                if let Ok(mut frame) = latest.lock() {
+                    *frame = Some(Ok(image));
+                }
+            }
+            // E.g. a truncated JPEG, the next frame is usually fine
+            Err(e) => debug!("Skipping frame {}: {}", meta.sequence, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Requests the buffers, retrying while the device is busy.
+fn create_stream<'a>(device: &Device, stop: &AtomicBool) -> Result<Stream<'a>, String> {
+    let mut retries = 0;
+    loop {
+        match Stream::with_buffers(device, Type::VideoCapture, BUFFER_COUNT) {
+            Err(e) if is_busy_error(&e) && retries < BUSY_RETRIES && !stop.load(Ordering::Relaxed) => {
+                retries += 1;
+                thread::sleep(BUSY_RETRY_DELAY);
+            }
+            result => return result.map_err(|e| format!("Error starting stream: {}", e)),
+        }
+    }
+}
+
+/// Waits up to the poll interval for a frame, or an error to be read.
+fn is_frame_ready(handle: &Handle) -> Result<bool, String> {
+    match handle.poll(libc::POLLIN, POLL_INTERVAL.as_millis() as i32) {
+        Ok(ready) => Ok(ready > 0),
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+        Err(e) => Err(format!("Error waiting for frame: {}", e)),
+    }
+}
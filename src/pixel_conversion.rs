@@ -0,0 +1,590 @@
+use gtk::gdk_pixbuf;
+use v4l::format::{Colorspace, Format, FourCC, Quantization};
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+/// Pixels as 8-bit RGBA without padding between rows.
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    fn new(width: usize, height: usize) -> Self {
+        RgbaImage {
+            width,
+            height,
+            data: vec![255; width * height * 4],
+        }
+    }
+
+    pub fn stride(&self) -> usize {
+        self.width * 4
+    }
+
+    fn set_rgb(&mut self, x: usize, y: usize, rgb: [u8; 3]) {
+        let offset = (y * self.width + x) * 4;
+        self.data[offset..offset + 3].copy_from_slice(&rgb);
+    }
+}
+
+/// Layouts of the supported pixel formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PixelFormat {
+    /// 4:2:2 in groups of 4 bytes, with the offsets of Y0, U, Y1 and V
+    Packed422([usize; 4]),
+    /// 4:2:0 with the luma plane followed by interleaved chroma
+    SemiPlanar420 { u_first: bool },
+    /// 4:2:0 with the luma plane followed by both chroma planes
+    Planar420 { u_first: bool },
+    Grey,
+    /// Offsets of red, green and blue
+    Rgb([usize; 3]),
+    /// Colors of the filter in the first two rows
+    Bayer([[usize; 2]; 2]),
+    Jpeg,
+}
+
+impl PixelFormat {
+    fn from_fourcc(fourcc: &FourCC) -> Option<Self> {
+        let format = match &fourcc.repr {
+            b"YUYV" => PixelFormat::Packed422([0, 1, 2, 3]),
+            b"YVYU" => PixelFormat::Packed422([0, 3, 2, 1]),
+            b"UYVY" => PixelFormat::Packed422([1, 0, 3, 2]),
+            b"VYUY" => PixelFormat::Packed422([1, 2, 3, 0]),
+            b"NV12" => PixelFormat::SemiPlanar420 { u_first: true },
+            b"NV21" => PixelFormat::SemiPlanar420 { u_first: false },
+            b"YU12" => PixelFormat::Planar420 { u_first: true },
+            b"YV12" => PixelFormat::Planar420 { u_first: false },
+            b"GREY" => PixelFormat::Grey,
+            b"RGB3" => PixelFormat::Rgb([0, 1, 2]),
+            b"BGR3" => PixelFormat::Rgb([2, 1, 0]),
+            b"BA81" => PixelFormat::Bayer([[B, G], [G, R]]),
+            b"GBRG" => PixelFormat::Bayer([[G, B], [R, G]]),
+            b"GRBG" => PixelFormat::Bayer([[G, R], [B, G]]),
+            b"RGGB" => PixelFormat::Bayer([[R, G], [G, B]]),
+            b"MJPG" | b"JPEG" => PixelFormat::Jpeg,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Bytes of a row of the first plane without padding.
+    fn row_bytes(&self, width: usize) -> usize {
+        match self {
+            PixelFormat::Packed422(_) => width.div_ceil(2) * 4,
+            PixelFormat::Rgb(_) => width * 3,
+            _ => width,
+        }
+    }
+}
+
+/// RGB from luma and chroma in 16-bit fixed point, for the matrix and range
+/// of the format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct YuvToRgb {
+    y_offset: i32,
+    y_scale: i32,
+    r_v: i32,
+    g_u: i32,
+    g_v: i32,
+    b_u: i32,
+}
+
+impl YuvToRgb {
+    /// `kr` and `kb` are the weights of red and blue in luma.
+    fn new(kr: f64, kb: f64, full_range: bool) -> Self {
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = if full_range {
+            (0, 1.0, 1.0)
+        } else {
+            (16, 255.0 / 219.0, 255.0 / 224.0)
+        };
+        let fixed = |value: f64| (value * 65536.0).round() as i32;
+
+        YuvToRgb {
+            y_offset,
+            y_scale: fixed(y_scale),
+            r_v: fixed(2.0 * (1.0 - kr) * c_scale),
+            g_u: fixed(2.0 * kb * (1.0 - kb) / kg * c_scale),
+            g_v: fixed(2.0 * kr * (1.0 - kr) / kg * c_scale),
+            b_u: fixed(2.0 * (1.0 - kb) * c_scale),
+        }
+    }
+
+    /// Defaults follow `V4L2_MAP_YCBCR_ENC_DEFAULT` and
+    /// `V4L2_MAP_QUANTIZATION_DEFAULT` of the kernel.
+    fn for_format(colorspace: Colorspace, quantization: Quantization) -> Self {
+        let (kr, kb) = match colorspace {
+            Colorspace::Rec709 | Colorspace::DCIP3 => (0.2126, 0.0722),
+            Colorspace::Rec2020 => (0.2627, 0.0593),
+            Colorspace::SMPTE240M => (0.212, 0.087),
+            _ => (0.299, 0.114),
+        };
+
+        let full_range = match quantization {
+            Quantization::FullRange => true,
+            Quantization::LimitedRange => false,
+            Quantization::Default => matches!(colorspace, Colorspace::JPEG),
+        };
+
+        YuvToRgb::new(kr, kb, full_range)
+    }
+
+    fn rgb(&self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let y = (y as i32 - self.y_offset) * self.y_scale;
+        let (u, v) = (u as i32 - 128, v as i32 - 128);
+
+        let clamp = |value: i32| ((value + 32768) >> 16).clamp(0, 255) as u8;
+        [clamp(y + self.r_v * v), clamp(y - self.g_u * u - self.g_v * v), clamp(y + self.b_u * u)]
+    }
+}
+
+pub fn is_supported(fourcc: &FourCC) -> bool {
+    PixelFormat::from_fourcc(fourcc).is_some()
+}
+
+/// Converts a buffer of the device in the given format. Rows are read with
+/// the stride of the format and YUV with its colorspace and quantization.
+pub fn convert_to_rgba(buffer: &[u8], format: &Format) -> Result<RgbaImage, String> {
+    let pixel_format = PixelFormat::from_fourcc(&format.fourcc)
+        .ok_or_else(|| format!("Pixel format {} is not supported", format.fourcc))?;
+
+    if pixel_format == PixelFormat::Jpeg {
+        return decode_jpeg(buffer);
+    }
+
+    let (width, height) = (format.width as usize, format.height as usize);
+    // Without padding, some drivers report no stride
+    let stride = (format.stride as usize).max(pixel_format.row_bytes(width));
+    let yuv = YuvToRgb::for_format(format.colorspace, format.quantization);
+
+    let mut image = RgbaImage::new(width, height);
+    match pixel_format {
+        PixelFormat::Packed422(offsets) => convert_packed_422(&mut image, buffer, stride, offsets, &yuv)?,
+        PixelFormat::SemiPlanar420 { u_first } => convert_semi_planar_420(&mut image, buffer, stride, u_first, &yuv)?,
+        PixelFormat::Planar420 { u_first } => convert_planar_420(&mut image, buffer, stride, u_first, &yuv)?,
+        PixelFormat::Grey => convert_grey(&mut image, buffer, stride)?,
+        PixelFormat::Rgb(offsets) => convert_rgb(&mut image, buffer, stride, offsets)?,
+        PixelFormat::Bayer(pattern) => convert_bayer(&mut image, buffer, stride, pattern)?,
+        PixelFormat::Jpeg => unreachable!(),
+    }
+
+    Ok(image)
+}
+
+/// The buffer from the start of a plane, if it holds all rows. The last row
+/// may end without padding.
+fn plane(buffer: &[u8], offset: usize, stride: usize, rows: usize, row_bytes: usize) -> Result<&[u8], String> {
+    let size = offset + stride * rows.saturating_sub(1) + row_bytes;
+    if rows > 0 && buffer.len() < size {
+        return Err(format!("Buffer of {} bytes is too small for the format, {} expected", buffer.len(), size));
+    }
+
+    Ok(buffer.get(offset..).unwrap_or_default())
+}
+
+fn convert_packed_422(
+    image: &mut RgbaImage,
+    buffer: &[u8],
+    stride: usize,
+    offsets: [usize; 4],
+    yuv: &YuvToRgb,
+) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let data = plane(buffer, 0, stride, height, width.div_ceil(2) * 4)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let group = &data[y * stride + x / 2 * 4..];
+            let luma = group[offsets[if x % 2 == 0 { 0 } else { 2 }]];
+            image.set_rgb(x, y, yuv.rgb(luma, group[offsets[1]], group[offsets[3]]));
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_semi_planar_420(
+    image: &mut RgbaImage,
+    buffer: &[u8],
+    stride: usize,
+    u_first: bool,
+    yuv: &YuvToRgb,
+) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let luma = plane(buffer, 0, stride, height, width)?;
+    let chroma = plane(buffer, stride * height, stride, height.div_ceil(2), width.div_ceil(2) * 2)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let pair = &chroma[y / 2 * stride + x / 2 * 2..];
+            let (u, v) = if u_first { (pair[0], pair[1]) } else { (pair[1], pair[0]) };
+            image.set_rgb(x, y, yuv.rgb(luma[y * stride + x], u, v));
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_planar_420(
+    image: &mut RgbaImage,
+    buffer: &[u8],
+    stride: usize,
+    u_first: bool,
+    yuv: &YuvToRgb,
+) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    // Chroma rows are half as long, including their padding
+    let chroma_stride = stride.div_ceil(2);
+
+    let luma = plane(buffer, 0, stride, height, width)?;
+    let first_offset = stride * height;
+    let second_offset = first_offset + chroma_stride * chroma_height;
+    let first = plane(buffer, first_offset, chroma_stride, chroma_height, chroma_width)?;
+    let second = plane(buffer, second_offset, chroma_stride, chroma_height, chroma_width)?;
+    let (u_plane, v_plane) = if u_first { (first, second) } else { (second, first) };
+
+    for y in 0..height {
+        for x in 0..width {
+            let chroma = y / 2 * chroma_stride + x / 2;
+            image.set_rgb(x, y, yuv.rgb(luma[y * stride + x], u_plane[chroma], v_plane[chroma]));
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_grey(image: &mut RgbaImage, buffer: &[u8], stride: usize) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let data = plane(buffer, 0, stride, height, width)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let luma = data[y * stride + x];
+            image.set_rgb(x, y, [luma; 3]);
+        }
+    }
+
+    Ok(())
+}
+
+fn convert_rgb(image: &mut RgbaImage, buffer: &[u8], stride: usize, offsets: [usize; 3]) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let data = plane(buffer, 0, stride, height, width * 3)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = &data[y * stride + x * 3..];
+            image.set_rgb(x, y, offsets.map(|o| pixel[o]));
+        }
+    }
+
+    Ok(())
+}
+
+/// Bilinear demosaicing: Missing colors are the mean of the neighbours with
+/// that color filter.
+fn convert_bayer(image: &mut RgbaImage, buffer: &[u8], stride: usize, pattern: [[usize; 2]; 2]) -> Result<(), String> {
+    let (width, height) = (image.width, image.height);
+    let data = plane(buffer, 0, stride, height, width)?;
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sums = [0u32; 3];
+            let mut counts = [0u32; 3];
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let color = pattern[ny % 2][nx % 2];
+                    sums[color] += data[ny * stride + nx] as u32;
+                    counts[color] += 1;
+                }
+            }
+
+            let own = pattern[y % 2][x % 2];
+            let rgb = [R, G, B].map(|color| match color {
+                c if c == own => data[y * stride + x],
+                c if counts[c] > 0 => ((sums[c] + counts[c] / 2) / counts[c]) as u8,
+                _ => 0,
+            });
+            image.set_rgb(x, y, rgb);
+        }
+    }
+
+    Ok(())
+}
+
+fn decode_jpeg(buffer: &[u8]) -> Result<RgbaImage, String> {
+    let loader = gdk_pixbuf::PixbufLoader::with_type("jpeg").map_err(|e| e.to_string())?;
+    loader.write(buffer).map_err(|e| e.to_string())?;
+    loader.close().map_err(|e| e.to_string())?;
+    let pixbuf = loader.pixbuf().ok_or("JPEG frame could not be decoded")?;
+
+    let (width, height) = (pixbuf.width() as usize, pixbuf.height() as usize);
+    let channels = pixbuf.n_channels() as usize;
+    let stride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+
+    let mut image = RgbaImage::new(width, height);
+    for (y, row) in bytes.chunks(stride).take(height).enumerate() {
+        for (x, pixel) in row.chunks(channels).take(width).enumerate() {
+            let offset = (y * width + x) * 4;
+            image.data[offset..offset + channels.min(4)].copy_from_slice(&pixel[..channels.min(4)]);
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 75% color bars as YCbCr: white, yellow, cyan, green, magenta, red,
+    // blue and black
+    const BARS_BT601: [[u8; 3]; 8] = [
+        [180, 128, 128],
+        [162, 44, 142],
+        [131, 156, 44],
+        [112, 72, 58],
+        [84, 184, 198],
+        [65, 100, 212],
+        [35, 212, 114],
+        [16, 128, 128],
+    ];
+    const BARS_BT709: [[u8; 3]; 8] = [
+        [180, 128, 128],
+        [168, 44, 136],
+        [145, 147, 44],
+        [133, 63, 52],
+        [63, 193, 204],
+        [51, 109, 212],
+        [28, 212, 120],
+        [16, 128, 128],
+    ];
+    const BARS_RGB: [[u8; 3]; 8] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+        [0, 0, 0],
+    ];
+
+    // Each bar is 2 pixels wide, so chroma is shared within a bar only
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 2;
+    const PADDING: usize = 4;
+
+    fn format(fourcc: &[u8; 4], stride: usize) -> Format {
+        let mut format = Format::new(WIDTH as u32, HEIGHT as u32, FourCC::new(fourcc));
+        format.stride = stride as u32;
+        format
+    }
+
+    /// Luma of the pixel and chroma of its bar.
+    fn bar(bars: &[[u8; 3]; 8], x: usize) -> [u8; 3] {
+        bars[x / 2]
+    }
+
+    fn encode_packed(bars: &[[u8; 3]; 8], offsets: [usize; 4]) -> Vec<u8> {
+        let stride = WIDTH * 2 + PADDING;
+        let mut buffer = vec![0; stride * HEIGHT];
+        for y in 0..HEIGHT {
+            for x in (0..WIDTH).step_by(2) {
+                let [luma, u, v] = bar(bars, x);
+                let group = &mut buffer[y * stride + x * 2..];
+                (group[offsets[0]], group[offsets[1]], group[offsets[2]], group[offsets[3]]) = (luma, u, luma, v);
+            }
+        }
+        buffer
+    }
+
+    fn encode_semi_planar(bars: &[[u8; 3]; 8], u_first: bool) -> Vec<u8> {
+        let stride = WIDTH + PADDING;
+        let mut buffer = vec![0; stride * HEIGHT * 3 / 2];
+        for x in 0..WIDTH {
+            let [luma, u, v] = bar(bars, x);
+            (buffer[x], buffer[stride + x]) = (luma, luma);
+            buffer[stride * HEIGHT + x / 2 * 2 + usize::from(!u_first)] = u;
+            buffer[stride * HEIGHT + x / 2 * 2 + usize::from(u_first)] = v;
+        }
+        buffer
+    }
+
+    fn encode_planar(bars: &[[u8; 3]; 8]) -> Vec<u8> {
+        let stride = WIDTH + PADDING;
+        let chroma_offset = stride * HEIGHT;
+        let mut buffer = vec![0; chroma_offset + stride];
+        for x in 0..WIDTH {
+            let [luma, u, v] = bar(bars, x);
+            (buffer[x], buffer[stride + x]) = (luma, luma);
+            (buffer[chroma_offset + x / 2], buffer[chroma_offset + stride / 2 + x / 2]) = (u, v);
+        }
+        buffer
+    }
+
+    fn assert_bars(image: &RgbaImage, name: &str) {
+        assert_eq!((image.width, image.height), (WIDTH, HEIGHT));
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let offset = (y * WIDTH + x) * 4;
+                let pixel = &image.data[offset..offset + 4];
+                let expected = BARS_RGB[x / 2];
+                let matches = (0..3).all(|c| pixel[c].abs_diff(expected[c]) <= 2) && pixel[3] == 255;
+                assert!(matches, "{} at {},{}: {:?} instead of {:?}", name, x, y, pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_yuv_color_bars() {
+        let cases = [
+            (b"YUYV", encode_packed(&BARS_BT601, [0, 1, 2, 3]), WIDTH * 2 + PADDING),
+            (b"YVYU", encode_packed(&BARS_BT601, [0, 3, 2, 1]), WIDTH * 2 + PADDING),
+            (b"UYVY", encode_packed(&BARS_BT601, [1, 0, 3, 2]), WIDTH * 2 + PADDING),
+            (b"VYUY", encode_packed(&BARS_BT601, [1, 2, 3, 0]), WIDTH * 2 + PADDING),
+            (b"NV12", encode_semi_planar(&BARS_BT601, true), WIDTH + PADDING),
+            (b"NV21", encode_semi_planar(&BARS_BT601, false), WIDTH + PADDING),
+            (b"YU12", encode_planar(&BARS_BT601), WIDTH + PADDING),
+        ];
+
+        for (fourcc, buffer, stride) in cases {
+            let image = convert_to_rgba(&buffer, &format(fourcc, stride)).unwrap();
+            assert_bars(&image, std::str::from_utf8(fourcc).unwrap());
+        }
+
+        // YV12 has V before U
+        let swapped: [[u8; 3]; 8] = BARS_BT601.map(|[luma, u, v]| [luma, v, u]);
+        let image = convert_to_rgba(&encode_planar(&swapped), &format(b"YV12", WIDTH + PADDING)).unwrap();
+        assert_bars(&image, "YV12");
+
+        let mut rec709 = format(b"YUYV", WIDTH * 2 + PADDING);
+        rec709.colorspace = Colorspace::Rec709;
+        let image = convert_to_rgba(&encode_packed(&BARS_BT709, [0, 1, 2, 3]), &rec709).unwrap();
+        assert_bars(&image, "YUYV in Rec. 709");
+    }
+
+    #[test]
+    fn test_quantization() {
+        let white_and_black = [255, 128, 0, 128];
+        let mut format = Format::new(2, 1, FourCC::new(b"YUYV"));
+
+        // Limited range by default, clipping below 16 and above 235
+        let image = convert_to_rgba(&white_and_black, &format).unwrap();
+        assert_eq!(image.data, [255, 255, 255, 255, 0, 0, 0, 255]);
+        let image = convert_to_rgba(&[235, 128, 128, 128], &format).unwrap();
+        assert_eq!(&image.data[..3], &[255, 255, 255]);
+        assert_eq!(&image.data[4..7], &[130, 130, 130]);
+
+        // JPEG implies full range
+        format.colorspace = Colorspace::JPEG;
+        let image = convert_to_rgba(&[235, 128, 128, 128], &format).unwrap();
+        assert_eq!(&image.data[..3], &[235, 235, 235]);
+
+        format.colorspace = Colorspace::Rec709;
+        format.quantization = Quantization::FullRange;
+        let image = convert_to_rgba(&[128, 128, 128, 128], &format).unwrap();
+        assert_eq!(&image.data[..3], &[128, 128, 128]);
+    }
+
+    #[test]
+    fn test_rgb_and_grey() {
+        // 2×2 with rows padded to a stride of 8
+        let rgb = [10, 20, 30, 40, 50, 60, 0, 0, 70, 80, 90, 100, 110, 120];
+        let expected = [10, 20, 30, 255, 40, 50, 60, 255, 70, 80, 90, 255, 100, 110, 120, 255];
+
+        let mut format = Format::new(2, 2, FourCC::new(b"RGB3"));
+        format.stride = 8;
+        assert_eq!(convert_to_rgba(&rgb, &format).unwrap().data, expected);
+
+        format.fourcc = FourCC::new(b"BGR3");
+        let image = convert_to_rgba(&rgb, &format).unwrap();
+        assert_eq!(&image.data[..8], &[30, 20, 10, 255, 60, 50, 40, 255]);
+
+        format.fourcc = FourCC::new(b"GREY");
+        format.stride = 3;
+        let image = convert_to_rgba(&[1, 2, 0, 3, 4], &format).unwrap();
+        assert_eq!(image.data, [1, 1, 1, 255, 2, 2, 2, 255, 3, 3, 3, 255, 4, 4, 4, 255]);
+    }
+
+    #[test]
+    fn test_bayer() {
+        let color = [200, 100, 50];
+
+        for fourcc in [b"BA81", b"GBRG", b"GRBG", b"RGGB"] {
+            let pattern = match PixelFormat::from_fourcc(&FourCC::new(fourcc)) {
+                Some(PixelFormat::Bayer(p)) => p,
+                _ => panic!("No Bayer pattern"),
+            };
+
+            // A uniform color, as seen through the filter
+            let mut format = Format::new(4, 3, FourCC::new(fourcc));
+            format.stride = 5;
+            let buffer: Vec<u8> = (0..15).map(|i| color[pattern[i / 5 % 2][i % 5 % 2]]).collect();
+
+            let image = convert_to_rgba(&buffer, &format).unwrap();
+            for pixel in image.data.chunks(4) {
+                assert_eq!(pixel, [200, 100, 50, 255], "{:?}", fourcc);
+            }
+        }
+    }
+
+    /// An 8×8 grey JPEG of level 200, with minimal quantization and Huffman
+    /// tables.
+    fn grey_jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend([0xFF, 0xDB, 0x00, 0x43, 0x00]);
+        jpeg.extend([1; 64]);
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00]);
+        // DC with the only code for category 10, AC with the only code for the
+        // end of block
+        for (class, symbol) in [(0x00, 0x0A), (0x10, 0x00)] {
+            jpeg.extend([0xFF, 0xC4, 0x00, 0x14, class, 1]);
+            jpeg.extend([0; 15]);
+            jpeg.push(symbol);
+        }
+        jpeg.extend([0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+        // DC of 576, which is 8 × (200 - 128)
+        jpeg.extend([0x48, 0x0F]);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn test_decode_jpeg() {
+        let format = Format::new(8, 8, FourCC::new(b"MJPG"));
+        let image = convert_to_rgba(&grey_jpeg(), &format).unwrap();
+
+        assert_eq!((image.width, image.height), (8, 8));
+        assert!(image.data.chunks(4).all(|p| p == [200, 200, 200, 255]));
+
+        assert!(convert_to_rgba(&grey_jpeg()[..40], &format).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let format = Format::new(2, 2, FourCC::new(b"H264"));
+        assert!(!is_supported(&format.fourcc));
+        assert_eq!(convert_to_rgba(&[], &format).err().unwrap(), "Pixel format H264 is not supported");
+
+        // The last row may end without padding
+        let mut format = Format::new(2, 2, FourCC::new(b"GREY"));
+        format.stride = 4;
+        assert!(convert_to_rgba(&[0; 6], &format).is_ok());
+        assert_eq!(
+            convert_to_rgba(&[0; 5], &format).err().unwrap(),
+            "Buffer of 5 bytes is too small for the format, 6 expected"
+        );
+
+        let format = Format::new(4, 4, FourCC::new(b"NV12"));
+        assert!(convert_to_rgba(&[0; 16], &format).is_err());
+        assert!(convert_to_rgba(&[0; 24], &format).is_ok());
+    }
+}
@@ -0,0 +1,186 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use gtk::{gdk, glib, graphene, prelude::*};
+
+use crate::scopes::RgbFrame;
+
+// Frames are analyzed scaled down and at a reduced rate, to keep the CPU
+// load low
+const SAMPLE_WIDTH: i32 = 480;
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(150);
+
+#[derive(Clone)]
+enum Subscriber {
+    /// Analyzes the frame, or shows why there is none
+    Frame(Rc<dyn Fn(Result<&RgbFrame, &str>)>),
+    Tick(Rc<dyn Fn()>),
+}
+
+/// Samples frames of the preview for the panels analyzing them, with one
+/// timer for all. Runs only while a panel is subscribed, and renders frames
+/// only for panels analyzing them.
+pub struct FrameSampler {
+    preview: gtk::Widget,
+    subscribers: RefCell<Vec<(u32, Subscriber)>>,
+    next_id: Cell<u32>,
+    timer: RefCell<Option<glib::SourceId>>,
+}
+
+impl FrameSampler {
+    pub fn new(preview: gtk::Widget) -> Rc<Self> {
+        Rc::new(FrameSampler {
+            preview,
+            subscribers: RefCell::new(vec![]),
+            next_id: Cell::new(0),
+            timer: RefCell::new(None),
+        })
+    }
+
+    /// The paintable showing the frames, which is replaced, when the stream
+    /// restarts.
+    pub fn paintable(&self) -> Option<gdk::Paintable> {
+        find_picture(&self.preview).and_then(|p| p.paintable())
+    }
+
+    /// Calls `on_frame` with each sampled frame. Returns the id to
+    /// unsubscribe.
+    pub fn subscribe_frames(self: &Rc<Self>, on_frame: impl Fn(Result<&RgbFrame, &str>) + 'static) -> u32 {
+        self.subscribe(Subscriber::Frame(Rc::new(on_frame)))
+    }
+
+    /// Calls `on_tick` on each sample, without rendering a frame for it.
+    /// Returns the id to unsubscribe.
+    pub fn subscribe_ticks(self: &Rc<Self>, on_tick: impl Fn() + 'static) -> u32 {
+        self.subscribe(Subscriber::Tick(Rc::new(on_tick)))
+    }
+
+    pub fn unsubscribe(&self, id: u32) {
+        self.subscribers.borrow_mut().retain(|(i, _)| *i != id);
+
+        if self.subscribers.borrow().is_empty() {
+            if let Some(timer) = self.timer.take() {
+                timer.remove();
+            }
+        }
+    }
+
+    fn subscribe(self: &Rc<Self>, subscriber: Subscriber) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.subscribers.borrow_mut().push((id, subscriber));
+
+        if self.timer.borrow().is_none() {
+            let weak_sampler: Weak<FrameSampler> = Rc::downgrade(self);
+            let timer = glib::timeout_add_local(SAMPLE_INTERVAL, move || match weak_sampler.upgrade() {
+                Some(sampler) => {
+                    sampler.sample();
+                    glib::ControlFlow::Continue
+                }
+                None => glib::ControlFlow::Break,
+            });
+            self.timer.replace(Some(timer));
+        }
+
+        id
+    }
+
+    fn sample(&self) {
+        if !self.preview.is_mapped() {
+            return;
+        }
+
+        // Copied, as subscribers may unsubscribe while called
+        let subscribers: Vec<Subscriber> = self.subscribers.borrow().iter().map(|(_, s)| s.clone()).collect();
+
+        let rendered = if subscribers.iter().any(|s| matches!(s, Subscriber::Frame(_))) {
+            Some(render_preview_frame(&self.preview, Some(SAMPLE_WIDTH)).map(|texture| {
+                let (bytes, stride) = download_rgb(&texture);
+                (texture, bytes, stride)
+            }))
+        } else {
+            None
+        };
+
+        let frame = rendered.as_ref().map(|r| match r {
+            Ok((texture, bytes, stride)) => Ok(RgbFrame {
+                width: texture.width() as usize,
+                height: texture.height() as usize,
+                stride: *stride,
+                data: bytes,
+            }),
+            Err(e) => Err(e.as_str()),
+        });
+
+        for subscriber in subscribers {
+            match (subscriber, &frame) {
+                (Subscriber::Frame(on_frame), Some(frame)) => on_frame(frame.as_ref().map_err(|e| *e)),
+                (Subscriber::Tick(on_tick), _) => on_tick(),
+                (Subscriber::Frame(_), None) => {}
+            };
+        }
+    }
+}
+
+/// Renders the current frame of the preview, scaled down to `max_width` if
+/// given, e.g. for analysis.
+pub fn render_preview_frame(preview: &gtk::Widget, max_width: Option<i32>) -> Result<gdk::Texture, String> {
+    let picture = find_picture(preview).ok_or("The preview has no picture")?;
+    let image = picture
+        .paintable()
+        .ok_or("The preview shows no frame")?
+        .current_image();
+
+    let (width, height) = (image.intrinsic_width(), image.intrinsic_height());
+    if width <= 0 || height <= 0 {
+        return Err("The preview shows no frame".to_string());
+    }
+
+    let scale = match max_width {
+        Some(max) if max < width => max as f64 / width as f64,
+        _ => 1.0,
+    };
+    let (width, height) = ((width as f64 * scale).round(), (height as f64 * scale).round().max(1.0));
+
+    let snapshot = gtk::Snapshot::new();
+    image.snapshot(&snapshot, width, height);
+    let node = snapshot.to_node().ok_or("The frame is empty")?;
+
+    let renderer = preview
+        .native()
+        .and_then(|n| n.renderer())
+        .ok_or("The preview is not shown")?;
+
+    let viewport = graphene::Rect::new(0.0, 0.0, width as f32, height as f32);
+    Ok(renderer.render_texture(node, Some(&viewport)))
+}
+
+/// The pixels as 8-bit RGB, with the stride in bytes.
+pub fn download_rgb(texture: &gdk::Texture) -> (glib::Bytes, usize) {
+    let mut downloader = gdk::TextureDownloader::new(texture);
+    downloader.set_format(gdk::MemoryFormat::R8g8b8);
+    downloader.download_bytes()
+}
+
+/// Both the viewfinder and the direct preview show their frames in a picture.
+/// Of stacks, only the visible page is searched.
+fn find_picture(widget: &gtk::Widget) -> Option<gtk::Picture> {
+    if let Some(picture) = widget.downcast_ref::<gtk::Picture>() {
+        return Some(picture.clone());
+    }
+
+    if let Some(stack) = widget.downcast_ref::<gtk::Stack>() {
+        return stack.visible_child().and_then(|c| find_picture(&c));
+    }
+
+    let mut child = widget.first_child();
+    while let Some(c) = child {
+        if let Some(picture) = find_picture(&c) {
+            return Some(picture);
+        }
+        child = c.next_sibling();
+    }
+
+    None
+}
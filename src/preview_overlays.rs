@@ -0,0 +1,77 @@
+use crate::scopes::{luma, RgbFrame};
+
+/// Sobel magnitude, from which an edge is highlighted by focus peaking
+const PEAKING_THRESHOLD: u32 = 160;
+
+/// Luma of the pixels in a plane without padding.
+pub fn luma_plane(frame: &RgbFrame) -> Vec<u8> {
+    let mut plane = Vec::with_capacity(frame.width * frame.height);
+
+    for y in 0..frame.height {
+        let row = &frame.data[y * frame.stride..];
+        plane.extend((0..frame.width).map(|x| luma([row[x * 3], row[x * 3 + 1], row[x * 3 + 2]])));
+    }
+
+    plane
+}
+
+/// Opaque, where the luma is at or above the threshold.
+pub fn zebra_mask(plane: &[u8], threshold: u8) -> Vec<u8> {
+    plane.iter().map(|l| if *l >= threshold { 255 } else { 0 }).collect()
+}
+
+/// Opaque at sharp edges, detected with the Sobel operator. The border is
+/// left out.
+pub fn peaking_mask(plane: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut mask = vec![0; width * height];
+    let at = |x: usize, y: usize| plane[y * width + x] as i32;
+
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1) - 2 * at(x, y - 1) - at(x + 1, y - 1);
+
+            if gx.unsigned_abs() + gy.unsigned_abs() >= PEAKING_THRESHOLD {
+                mask[y * width + x] = 255;
+            }
+        }
+    }
+
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masks() {
+        // A sharp vertical edge between dark and bright columns
+        let plane: Vec<u8> = (0..4 * 4).map(|i| if i % 4 < 2 { 10 } else { 250 }).collect();
+
+        assert_eq!(&zebra_mask(&plane, 240)[..4], [0, 0, 255, 255]);
+
+        let mask = peaking_mask(&plane, 4, 4);
+        assert_eq!(&mask[4..8], [0, 255, 255, 0]);
+        assert!(mask[..4].iter().all(|m| *m == 0));
+
+        let flat = vec![128; 16];
+        assert!(peaking_mask(&flat, 4, 4).iter().all(|m| *m == 0));
+    }
+
+    #[test]
+    fn test_luma_plane() {
+        // Rows padded to 5 bytes
+        let data = [255, 255, 255, 7, 7, 0, 0, 0];
+        let frame = RgbFrame {
+            width: 1,
+            height: 2,
+            stride: 5,
+            data: &data,
+        };
+
+        assert_eq!(luma_plane(&frame), [255, 0]);
+    }
+}
@@ -0,0 +1,247 @@
+use v4l::{control::Flags, Device};
+
+use super::{profile::{has_value, read_control_value}, Profile, ProfileControl, ProfileError};
+
+/// The current state of a control on a device, to compare profiles with.
+#[derive(Debug, Clone)]
+pub struct LiveControl {
+    pub id: u32,
+    pub name: String,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: u64,
+    pub menu_items: Option<Vec<(u32, String)>>,
+    pub read_only: bool,
+    /// `None`, if the value could not be read
+    pub value: Option<i64>,
+}
+
+impl LiveControl {
+    /// Reads all controls with a value from the device.
+    pub fn from_device(device: &Device) -> Result<Vec<LiveControl>, ProfileError> {
+        let descriptions = match device.query_controls() {
+            Ok(d) => d,
+            Err(e) => return Err(ProfileError::new(format!("Error querying controls: {}", e))),
+        };
+
+        let live = descriptions
+            .iter()
+            .filter(|d| has_value(d) && !d.flags.contains(Flags::DISABLED))
+            .map(|d| LiveControl {
+                id: d.id,
+                name: d.name.clone(),
+                minimum: d.minimum,
+                maximum: d.maximum,
+                step: d.step,
+                menu_items: d.items.as_ref().map(|items| {
+                    items.iter().map(|(k, v)| (*k, v.to_string())).collect()
+                }),
+                read_only: d.flags.contains(Flags::READ_ONLY),
+                value: read_control_value(device, d).ok(),
+            })
+            .collect();
+
+        Ok(live)
+    }
+
+    pub fn accepts(&self, value: i64) -> bool {
+        if value < self.minimum || value > self.maximum {
+            return false;
+        }
+
+        match &self.menu_items {
+            Some(items) => items.iter().any(|(k, _)| *k as i64 == value),
+            // Other values are rejected or rounded by the driver
+            None => self.step <= 1 || (value - self.minimum) as u64 % self.step == 0,
+        }
+    }
+
+    /// Formats the value with the label of the menu-item, if there is one.
+    pub fn format_value(&self, value: i64) -> String {
+        let item = self
+            .menu_items
+            .as_ref()
+            .and_then(|items| items.iter().find(|(k, _)| *k as i64 == value));
+
+        match item {
+            Some((_, label)) => format!("{} ({})", label, value),
+            None => value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStatus {
+    Equal,
+    Changed,
+    /// Control of the profile does not exist on the device
+    MissingOnDevice,
+    /// Control of the device is not part of the profile
+    MissingInProfile,
+    /// Value of the profile is not accepted by the device
+    OutOfRange,
+    ReadOnly,
+}
+
+impl DiffStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiffStatus::Equal => "Unchanged",
+            DiffStatus::Changed => "Changed",
+            DiffStatus::MissingOnDevice => "Missing on camera",
+            DiffStatus::MissingInProfile => "Not in profile",
+            DiffStatus::OutOfRange => "Out of range",
+            DiffStatus::ReadOnly => "Read-only",
+        }
+    }
+
+    /// Only changed values can be applied to the device.
+    pub fn is_applicable(&self) -> bool {
+        *self == DiffStatus::Changed
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlDiff {
+    pub name: String,
+    pub status: DiffStatus,
+    pub profile_value: Option<String>,
+    pub device_value: Option<String>,
+    /// The control of the profile, if there is one
+    pub control: Option<ProfileControl>,
+}
+
+/// Compares the profile with the live controls of a device. Controls of the
+/// profile come first, in their stored order.
+pub fn diff_profile(profile: &Profile, live_controls: &[LiveControl]) -> Vec<ControlDiff> {
+    let mut diffs = vec![];
+
+    for control in &profile.controls {
+        let live = match live_controls.iter().find(|l| l.id == control.id) {
+            Some(l) => l,
+            None => {
+                diffs.push(ControlDiff {
+                    name: control.name.clone(),
+                    status: DiffStatus::MissingOnDevice,
+                    profile_value: Some(control.value.to_string()),
+                    device_value: None,
+                    control: Some(control.clone()),
+                });
+                continue;
+            }
+        };
+
+        let status = if live.read_only {
+            DiffStatus::ReadOnly
+        } else if !live.accepts(control.value) {
+            DiffStatus::OutOfRange
+        } else if live.value == Some(control.value) {
+            DiffStatus::Equal
+        } else {
+            DiffStatus::Changed
+        };
+
+        diffs.push(ControlDiff {
+            name: live.name.clone(),
+            status,
+            profile_value: Some(live.format_value(control.value)),
+            device_value: live.value.map(|v| live.format_value(v)),
+            control: Some(control.clone()),
+        });
+    }
+
+    for live in live_controls {
+        if profile.controls.iter().any(|c| c.id == live.id) {
+            continue;
+        }
+
+        diffs.push(ControlDiff {
+            name: live.name.clone(),
+            status: DiffStatus::MissingInProfile,
+            profile_value: None,
+            device_value: live.value.map(|v| live.format_value(v)),
+            control: None,
+        });
+    }
+
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(id: u32, value: i64) -> LiveControl {
+        LiveControl {
+            id,
+            name: format!("Control {}", id),
+            minimum: 0,
+            maximum: 255,
+            step: 1,
+            menu_items: None,
+            read_only: false,
+            value: Some(value),
+        }
+    }
+
+    fn control(id: u32, value: i64) -> ProfileControl {
+        ProfileControl {
+            id,
+            name: format!("Control {}", id),
+            value,
+            locked: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_profile() {
+        let mut profile = Profile::new("Test".to_string(), None);
+        profile.controls = vec![
+            control(1, 10),
+            control(2, 20),
+            control(3, 300),
+            control(4, 40),
+            control(5, 50),
+        ];
+
+        let mut read_only = live(5, 0);
+        read_only.read_only = true;
+        let live_controls = vec![live(1, 10), live(2, 21), live(3, 30), read_only, live(6, 60)];
+
+        let statuses: Vec<DiffStatus> = diff_profile(&profile, &live_controls)
+            .iter()
+            .map(|d| d.status)
+            .collect();
+
+        assert_eq!(statuses, vec![
+            DiffStatus::Equal,
+            DiffStatus::Changed,
+            DiffStatus::OutOfRange,
+            DiffStatus::MissingOnDevice,
+            DiffStatus::ReadOnly,
+            DiffStatus::MissingInProfile,
+        ]);
+    }
+
+    #[test]
+    fn test_menu_values() {
+        let mut menu = live(1, 1);
+        menu.maximum = 3;
+        menu.menu_items = Some(vec![(1, "Manual Mode".to_string()), (3, "Aperture Priority Mode".to_string())]);
+
+        assert!(menu.accepts(3));
+        assert!(!menu.accepts(2));
+        assert_eq!(menu.format_value(1), "Manual Mode (1)");
+        assert_eq!(menu.format_value(2), "2");
+    }
+
+    #[test]
+    fn test_stepped_values() {
+        let mut stepped = live(2, 100);
+        stepped.minimum = 2;
+        stepped.step = 10;
+
+        assert!(stepped.accepts(102));
+        assert!(!stepped.accepts(100));
+    }
+}
@@ -0,0 +1,11 @@
+mod diff;
+pub use self::diff::{diff_profile, ControlDiff, DiffStatus, LiveControl};
+
+mod profile;
+pub use self::profile::{is_storable, read_control_value, write_control_value, Profile, ProfileControl};
+
+mod profile_error;
+pub use self::profile_error::ProfileError;
+
+mod store;
+pub use self::store::{list_profiles, load_profile, profiles_for_device, save_profile};
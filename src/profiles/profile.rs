@@ -0,0 +1,351 @@
+use std::collections::{HashMap, HashSet};
+
+use toml::{Table, Value};
+use v4l::{control::{Description, Flags, Type}, Device};
+
+use crate::quirks::DeviceQuirks;
+
+use super::ProfileError;
+
+/// A named set of control-values, which can be bound to a camera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// Stable id of the bound camera, see `files::get_device_id`
+    pub device: Option<String>,
+    pub controls: Vec<ProfileControl>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileControl {
+    pub id: u32,
+    pub name: String,
+    pub value: i64,
+    /// Locked controls are enforced, when changed by another process
+    pub locked: bool,
+}
+
+impl Profile {
+    pub fn new(name: String, device: Option<String>) -> Self {
+        Profile {
+            name,
+            device,
+            controls: vec![],
+        }
+    }
+
+    /// Creates a profile with the current values of all storable controls of
+    /// the device.
+    pub fn from_device(name: String, device_id: Option<String>, device: &Device) -> Result<Self, ProfileError> {
+        let descriptions = match device.query_controls() {
+            Ok(d) => d,
+            Err(e) => return Err(ProfileError::new(format!("Error querying controls: {}", e))),
+        };
+
+        let mut profile = Profile::new(name, device_id);
+
+        for desc in descriptions.iter().filter(|d| is_storable(d)) {
+            let value = match read_control_value(device, desc) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("Skipping control {} for profile: {}", desc.name, e);
+                    continue;
+                }
+            };
+
+            profile.controls.push(ProfileControl {
+                id: desc.id,
+                name: desc.name.clone(),
+                value,
+                locked: false,
+            });
+        }
+
+        Ok(profile)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ProfileError> {
+        let table = match content.parse::<Table>() {
+            Ok(t) => t,
+            Err(e) => return Err(ProfileError::new(format!("Invalid profile: {}", e))),
+        };
+
+        let name = match table.get("name").and_then(|v| v.as_str()) {
+            Some(n) => n.to_string(),
+            None => return Err(ProfileError::new("Profile has no name".to_string())),
+        };
+
+        let device = table
+            .get("device")
+            .and_then(|v| v.as_str())
+            .map(|d| d.to_string());
+
+        let mut profile = Profile::new(name, device);
+
+        let controls = match table.get("controls") {
+            Some(Value::Array(c)) => c,
+            Some(_) => return Err(ProfileError::new("'controls' has to be an array".to_string())),
+            None => return Ok(profile),
+        };
+
+        for control in controls {
+            let id = control.get("id").and_then(|v| v.as_integer());
+            let value = control.get("value").and_then(|v| v.as_integer());
+
+            let (id, value) = match (id, value) {
+                (Some(i), Some(v)) => (i, v),
+                _ => return Err(ProfileError::new(format!(
+                    "Control in profile {} needs an id and a value",
+                    profile.name
+                ))),
+            };
+            let id = match u32::try_from(id) {
+                Ok(i) => i,
+                Err(_) => return Err(ProfileError::new(format!(
+                    "Control id {} in profile {} is out of range",
+                    id, profile.name
+                ))),
+            };
+
+            profile.controls.push(ProfileControl {
+                id,
+                name: control
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                value,
+                locked: control
+                    .get("locked")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            });
+        }
+
+        Ok(profile)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(&self.to_table()).expect("A table is always serializable")
+    }
+
+    /// The profile as TOML table, e.g. to be extended by other files.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.insert("name".to_string(), Value::String(self.name.clone()));
+
+        if let Some(device) = &self.device {
+            table.insert("device".to_string(), Value::String(device.clone()));
+        }
+
+        let controls = self
+            .controls
+            .iter()
+            .map(|c| {
+                let mut control = Table::new();
+                control.insert("id".to_string(), Value::Integer(c.id as i64));
+                control.insert("name".to_string(), Value::String(c.name.clone()));
+                control.insert("value".to_string(), Value::Integer(c.value));
+                control.insert("locked".to_string(), Value::Boolean(c.locked));
+                Value::Table(control)
+            })
+            .collect();
+        table.insert("controls".to_string(), Value::Array(controls));
+
+        table
+    }
+
+    pub fn locked_controls(&self) -> Vec<&ProfileControl> {
+        self.controls.iter().filter(|c| c.locked).collect()
+    }
+
+    /// Sets all controls of this profile on the device, in the stored order
+    /// after the write order of the quirks. Controls hidden by the quirks are
+    /// skipped, values are clamped to the ranges of the quirks.
+    ///
+    /// Returns an error for each control, that could not be set.
+    pub fn apply(&self, device: &Device, quirks: &DeviceQuirks) -> Vec<ProfileError> {
+        let all_descriptions = match device.query_controls() {
+            Ok(d) => d,
+            Err(e) => return vec![ProfileError::new(format!("Error querying controls: {}", e))],
+        };
+
+        let mut descriptions: HashMap<u32, Description> = HashMap::new();
+        let mut hidden: HashSet<u32> = HashSet::new();
+        for mut desc in all_descriptions {
+            if quirks.apply(&mut desc) {
+                descriptions.insert(desc.id, desc);
+            } else {
+                hidden.insert(desc.id);
+            }
+        }
+
+        let mut controls: Vec<&ProfileControl> = self.controls.iter().filter(|c| !hidden.contains(&c.id)).collect();
+        controls.sort_by_key(|c| quirks.write_position(c.id));
+
+        let mut errors = vec![];
+        for control in controls {
+            let desc = match descriptions.get(&control.id) {
+                Some(d) => d,
+                None => {
+                    errors.push(ProfileError::new(format!(
+                        "Control {} is not available on this device",
+                        control.name
+                    )));
+                    continue;
+                }
+            };
+
+            if let Err(e) = write_control_value(device, desc, clamp_value(desc, control.value)) {
+                errors.push(e);
+            }
+        }
+
+        errors
+    }
+}
+
+/// Only controls with a readable and writable value are part of a profile.
+pub fn is_storable(desc: &Description) -> bool {
+    if desc.flags.intersects(Flags::DISABLED | Flags::READ_ONLY | Flags::WRITE_ONLY) {
+        return false;
+    }
+
+    has_value(desc)
+}
+
+/// Returns true for control-types, whose value can be stored as integer.
+pub fn has_value(desc: &Description) -> bool {
+    matches!(
+        desc.typ,
+        Type::Boolean
+            | Type::Integer
+            | Type::Integer64
+            | Type::IntegerMenu
+            | Type::Menu
+            | Type::U8
+            | Type::U16
+            | Type::U32
+    )
+}
+
+/// Limits the value to the range of the control. Boolean values stay as
+/// they are.
+fn clamp_value(desc: &Description, value: i64) -> i64 {
+    match desc.typ {
+        Type::Boolean => value,
+        _ if desc.minimum <= desc.maximum => value.clamp(desc.minimum, desc.maximum),
+        _ => value,
+    }
+}
+
+pub fn read_control_value(device: &Device, desc: &Description) -> Result<i64, ProfileError> {
+    let control = match device.control(desc.id) {
+        Ok(c) => c,
+        Err(e) => return Err(ProfileError::new(e.to_string())),
+    };
+
+    match control.value {
+        v4l::control::Value::Integer(v) => Ok(v),
+        v4l::control::Value::Boolean(b) => Ok(b as i64),
+        _ => Err(ProfileError::new(format!(
+            "Value of {} is neither integer nor boolean",
+            desc.name
+        ))),
+    }
+}
+
+pub fn write_control_value(device: &Device, desc: &Description, value: i64) -> Result<(), ProfileError> {
+    let new_value = match desc.typ {
+        Type::Boolean => v4l::control::Value::Boolean(value != 0),
+        _ => v4l::control::Value::Integer(value),
+    };
+
+    let new_control = v4l::control::Control {
+        id: desc.id,
+        value: new_value,
+    };
+
+    match device.set_control(new_control) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ProfileError::new(format!("Error setting {}: {}", desc.name, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_roundtrip() {
+        let mut profile = Profile::new(
+            "Studio".to_string(),
+            Some("usb-Cam_1234-video-index0".to_string())
+        );
+        profile.controls.push(ProfileControl {
+            id: 9963776,
+            name: "Brightness".to_string(),
+            value: 128,
+            locked: true,
+        });
+        profile.controls.push(ProfileControl {
+            id: 9963788,
+            name: "White Balance, Automatic".to_string(),
+            value: 0,
+            locked: false,
+        });
+
+        let parsed = Profile::parse(&profile.to_toml()).unwrap();
+
+        assert_eq!(parsed, profile);
+        assert_eq!(parsed.locked_controls().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let content = r#"
+            name = "Minimal"
+
+            [[controls]]
+            id = 1
+            value = -3
+        "#;
+
+        let profile = Profile::parse(content).unwrap();
+
+        assert_eq!(profile.device, None);
+        assert_eq!(profile.controls[0].value, -3);
+        assert!(!profile.controls[0].locked);
+    }
+
+    #[test]
+    fn test_clamp_value() {
+        let mut desc = Description {
+            id: 1,
+            typ: Type::Integer,
+            name: "Exposure".to_string(),
+            minimum: 3,
+            maximum: 2047,
+            step: 1,
+            default: 250,
+            flags: Flags::empty(),
+            items: None,
+        };
+
+        assert_eq!(clamp_value(&desc, 5000), 2047);
+        assert_eq!(clamp_value(&desc, 1), 3);
+        assert_eq!(clamp_value(&desc, 250), 250);
+
+        desc.typ = Type::Boolean;
+        assert_eq!(clamp_value(&desc, 5000), 5000);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Profile::parse("device = \"x\"").is_err());
+        assert!(Profile::parse("name = \"x\"\n[[controls]]\nid = 1").is_err());
+        assert!(Profile::parse("name = \"x\"\ncontrols = 3").is_err());
+        assert!(Profile::parse("name = \"x\"\n[[controls]]\nid = -1\nvalue = 0").is_err());
+        assert!(Profile::parse("name = \"x\"\n[[controls]]\nid = 4294967296\nvalue = 0").is_err());
+    }
+}
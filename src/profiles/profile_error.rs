@@ -0,0 +1,18 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone)]
+pub struct ProfileError {
+    pub message: String,
+}
+
+impl ProfileError {
+    pub fn new(message: String) -> ProfileError {
+        return ProfileError { message };
+    }
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.message)
+    }
+}
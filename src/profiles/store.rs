@@ -0,0 +1,118 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::files::get_config_dir;
+
+use super::{Profile, ProfileError};
+
+/// Returns the directory, where profiles are stored as TOML-files.
+pub fn get_profiles_dir() -> PathBuf {
+    get_config_dir().join("profiles")
+}
+
+/// Returns all readable profiles, sorted by name.
+pub fn list_profiles() -> Vec<Profile> {
+    let mut profiles = vec![];
+
+    let entries = match fs::read_dir(get_profiles_dir()) {
+        Ok(e) => e,
+        Err(_) => return profiles,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == "toml") {
+            match read_profile(&path) {
+                Ok(p) => profiles.push(p),
+                Err(e) => eprintln!("Ignoring profile {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles
+}
+
+/// Loads a profile by its name or by the path of its file.
+pub fn load_profile(name_or_path: &str) -> Result<Profile, ProfileError> {
+    let path = Path::new(name_or_path);
+    if path.is_file() {
+        return read_profile(path);
+    }
+
+    match list_profiles().into_iter().find(|p| p.name == name_or_path) {
+        Some(p) => Ok(p),
+        None => Err(ProfileError::new(format!("No profile named {}", name_or_path))),
+    }
+}
+
+/// Returns all profiles bound to the device with the given stable id.
+pub fn profiles_for_device(device_id: &str) -> Vec<Profile> {
+    list_profiles()
+        .into_iter()
+        .filter(|p| p.device.as_deref() == Some(device_id))
+        .collect()
+}
+
+/// Saves the profile to the profiles-directory, replacing a profile with the
+/// same name. Other profiles, whose names map to the same file name, are kept.
+pub fn save_profile(profile: &Profile) -> Result<PathBuf, ProfileError> {
+    let dir = get_profiles_dir();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        return Err(ProfileError::new(format!("Error creating {}: {}", dir.display(), e)));
+    }
+
+    let path = profile_path(&dir, &profile.name);
+    match fs::write(&path, profile.to_toml()) {
+        Ok(_) => Ok(path),
+        Err(e) => Err(ProfileError::new(format!("Error writing {}: {}", path.display(), e))),
+    }
+}
+
+fn read_profile(path: &Path) -> Result<Profile, ProfileError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Profile::parse(&content),
+        Err(e) => Err(ProfileError::new(e.to_string())),
+    }
+}
+
+/// The file of the profile with the name, or a free one, numbered if needed.
+fn profile_path(dir: &Path, profile_name: &str) -> PathBuf {
+    let sanitized: String = profile_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+
+    for number in 1.. {
+        let path = match number {
+            1 => dir.join(format!("{}.toml", sanitized)),
+            n => dir.join(format!("{}-{}.toml", sanitized, n)),
+        };
+
+        // Unreadable files are not replaced either
+        if !path.exists() || read_profile(&path).is_ok_and(|p| p.name == profile_name) {
+            return path;
+        }
+    }
+
+    unreachable!("Some number is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_profile_path() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+
+        let path = profile_path(dir, "My Profile");
+        assert_eq!(path, dir.join("My_Profile.toml"));
+        fs::write(&path, Profile::new("My Profile".to_string(), None).to_toml()).unwrap();
+
+        // The same name replaces, a different one with the same file name not
+        assert_eq!(profile_path(dir, "My Profile"), path);
+        assert_eq!(profile_path(dir, "My_Profile"), dir.join("My_Profile-2.toml"));
+    }
+}
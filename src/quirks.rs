@@ -0,0 +1,366 @@
+use std::{fs, io, path::PathBuf};
+
+use gtk::gio;
+use toml::{Table, Value};
+use v4l::{control::Description, Device};
+
+use crate::{device_discovery::get_usb_id, files::get_config_dir};
+
+const BUNDLED_QUIRKS: &str = "/de/pixelgerecht/CameraSettings/quirks.toml";
+const QUIRKS_FILE_NAME: &str = "quirks.toml";
+
+/// Identifies a camera for matching quirks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceIdentity {
+    pub driver: String,
+    pub card: String,
+    /// Vendor and product id like "046d:0825"
+    pub usb_id: Option<String>,
+}
+
+/// Overrides for a control, unset values are kept as reported by the driver.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControlQuirk {
+    pub id: Option<u32>,
+    pub name: Option<String>,
+    pub hidden: Option<bool>,
+    pub minimum: Option<i64>,
+    pub maximum: Option<i64>,
+    pub step: Option<u64>,
+    pub default: Option<i64>,
+    pub label: Option<String>,
+}
+
+impl ControlQuirk {
+    fn matches(&self, description: &Description) -> bool {
+        self.id == Some(description.id) || self.name.as_deref() == Some(description.name.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct QuirkEntry {
+    driver: Option<String>,
+    card: Option<String>,
+    usb_id: Option<String>,
+    write_order: Vec<u32>,
+    controls: Vec<ControlQuirk>,
+}
+
+impl QuirkEntry {
+    fn matches(&self, identity: &DeviceIdentity) -> bool {
+        let usb_id_matches = match (&self.usb_id, &identity.usb_id) {
+            (Some(expected), Some(actual)) => expected.eq_ignore_ascii_case(actual),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        usb_id_matches
+            && self.driver.as_ref().map_or(true, |d| *d == identity.driver)
+            && self.card.as_ref().map_or(true, |c| *c == identity.card)
+    }
+}
+
+/// The quirks of all entries matching a camera.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceQuirks {
+    controls: Vec<ControlQuirk>,
+    /// Controls to be written first and in this order
+    pub write_order: Vec<u32>,
+}
+
+impl DeviceQuirks {
+    /// Loads the bundled and the user's quirks for the device.
+    pub fn for_device(device_path: &str, device: &Device) -> Self {
+        let identity = match device.query_caps() {
+            Ok(caps) => DeviceIdentity {
+                driver: caps.driver,
+                card: caps.card,
+                usb_id: get_usb_id(device_path),
+            },
+            Err(e) => {
+                eprintln!("Error querying capabilities for quirks: {}", e);
+                return DeviceQuirks::default();
+            }
+        };
+
+        let mut entries = load_bundled_quirks();
+        entries.append(&mut load_user_quirks());
+
+        DeviceQuirks::from_entries(&entries, &identity)
+    }
+
+    fn from_entries(entries: &[QuirkEntry], identity: &DeviceIdentity) -> Self {
+        let mut quirks = DeviceQuirks::default();
+
+        for entry in entries.iter().filter(|e| e.matches(identity)) {
+            quirks.controls.extend(entry.controls.iter().cloned());
+
+            if !entry.write_order.is_empty() {
+                quirks.write_order = entry.write_order.clone();
+            }
+        }
+
+        quirks
+    }
+
+    /// Applies the overrides to the description. Returns false, if the
+    /// control is hidden.
+    pub fn apply(&self, description: &mut Description) -> bool {
+        let mut hidden = false;
+
+        // Matched up front, as labels change the name. Later quirks take precedence.
+        let matching: Vec<&ControlQuirk> = self.controls.iter().filter(|q| q.matches(description)).collect();
+
+        for quirk in matching {
+            hidden = quirk.hidden.unwrap_or(hidden);
+
+            if let Some(minimum) = quirk.minimum {
+                description.minimum = minimum;
+            }
+            if let Some(maximum) = quirk.maximum {
+                description.maximum = maximum;
+            }
+            if let Some(step) = quirk.step {
+                description.step = step;
+            }
+            if let Some(default) = quirk.default {
+                description.default = default;
+            }
+            if let Some(label) = &quirk.label {
+                description.name = label.clone();
+            }
+        }
+
+        !hidden
+    }
+
+    /// Sort key for writing controls: Controls of the write order come first,
+    /// the others keep their order with a stable sort.
+    pub fn write_position(&self, id: u32) -> usize {
+        self.write_order
+            .iter()
+            .position(|o| *o == id)
+            .unwrap_or(self.write_order.len())
+    }
+}
+
+/// Path of the user's quirks, which take precedence over the bundled ones.
+fn get_quirks_file() -> PathBuf {
+    get_config_dir().join(QUIRKS_FILE_NAME)
+}
+
+fn load_bundled_quirks() -> Vec<QuirkEntry> {
+    let data = match gio::resources_lookup_data(BUNDLED_QUIRKS, gio::ResourceLookupFlags::NONE) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error loading bundled quirks: {}", e);
+            return vec![];
+        }
+    };
+
+    match std::str::from_utf8(&data) {
+        Ok(content) => parse_quirks(content),
+        Err(e) => {
+            eprintln!("Bundled quirks are not UTF-8: {}", e);
+            vec![]
+        }
+    }
+}
+
+fn load_user_quirks() -> Vec<QuirkEntry> {
+    let path = get_quirks_file();
+
+    match fs::read_to_string(&path) {
+        Ok(content) => parse_quirks(&content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            vec![]
+        }
+    }
+}
+
+/// Parses the `[[device]]` entries, invalid entries are skipped.
+fn parse_quirks(content: &str) -> Vec<QuirkEntry> {
+    let table = match content.parse::<Table>() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Invalid quirks: {}", e);
+            return vec![];
+        }
+    };
+
+    let devices = match table.get("device").and_then(|d| d.as_array()) {
+        Some(d) => d,
+        None => return vec![],
+    };
+
+    devices
+        .iter()
+        .filter_map(|d| match parse_entry(d) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                eprintln!("Ignoring quirk: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_entry(value: &Value) -> Result<QuirkEntry, String> {
+    let get_str = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    let entry = QuirkEntry {
+        driver: get_str("driver"),
+        card: get_str("card"),
+        usb_id: get_str("usb_id"),
+        write_order: value
+            .get("write_order")
+            .and_then(|o| o.as_array())
+            .map(|o| o.iter().filter_map(|id| id.as_integer()).map(|id| id as u32).collect())
+            .unwrap_or_default(),
+        controls: value
+            .get("control")
+            .and_then(|c| c.as_array())
+            .map(|c| c.iter().map(parse_control_quirk).collect::<Result<Vec<_>, _>>())
+            .transpose()?
+            .unwrap_or_default(),
+    };
+
+    if entry.driver.is_none() && entry.card.is_none() && entry.usb_id.is_none() {
+        return Err("Entry needs a driver, card or usb_id to match".to_string());
+    }
+
+    Ok(entry)
+}
+
+fn parse_control_quirk(value: &Value) -> Result<ControlQuirk, String> {
+    let get_int = |key: &str| value.get(key).and_then(|v| v.as_integer());
+    let get_str = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    let quirk = ControlQuirk {
+        id: get_int("id").map(|id| id as u32),
+        name: get_str("name"),
+        hidden: value.get("hidden").and_then(|v| v.as_bool()),
+        minimum: get_int("minimum"),
+        maximum: get_int("maximum"),
+        step: get_int("step").filter(|s| *s > 0).map(|s| s as u64),
+        default: get_int("default"),
+        label: get_str("label"),
+    };
+
+    if quirk.id.is_none() && quirk.name.is_none() {
+        return Err("Control quirk needs an id or a name".to_string());
+    }
+
+    Ok(quirk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v4l::control::{Flags, Type};
+
+    fn description(id: u32, name: &str) -> Description {
+        Description {
+            id,
+            typ: Type::Integer,
+            name: name.to_string(),
+            minimum: 0,
+            maximum: 255,
+            step: 1,
+            default: 128,
+            flags: Flags::empty(),
+            items: None,
+        }
+    }
+
+    #[test]
+    fn test_bundled_quirks() {
+        let entries = parse_quirks(include_str!("../data/resources/quirks.toml"));
+
+        let uvc = DeviceIdentity {
+            driver: "uvcvideo".to_string(),
+            card: "Webcam".to_string(),
+            usb_id: None,
+        };
+        let quirks = DeviceQuirks::from_entries(&entries, &uvc);
+
+        // Automatic modes are written before the values they control
+        for (mode, value) in [(0x009a0901, 0x009a0902), (0x0098090c, 0x0098091a), (0x009a090c, 0x009a090a)] {
+            assert!(quirks.write_position(mode) < quirks.write_position(value));
+        }
+        assert_eq!(quirks.write_position(0x00980900), quirks.write_order.len());
+        assert!(quirks.apply(&mut description(0x009a0902, "Exposure Time, Absolute")));
+
+        let other = DeviceIdentity { driver: "other".to_string(), ..uvc };
+        assert_eq!(DeviceQuirks::from_entries(&entries, &other), DeviceQuirks::default());
+    }
+
+    #[test]
+    fn test_quirks() {
+        let bundled = parse_quirks(
+            r#"
+            [[device]]
+            driver = "uvcvideo"
+            write_order = [3, 1]
+
+            [[device]]
+            usb_id = "1234:ABCD"
+
+            [[device.control]]
+            id = 1
+            hidden = true
+
+            [[device.control]]
+            name = "Gain"
+            maximum = 100
+            label = "Analog Gain"
+
+            [[device]]
+            name = "Matches everything"
+            "#,
+        );
+        let user = parse_quirks(
+            r#"
+            [[device]]
+            driver = "uvcvideo"
+            card = "Webcam"
+
+            [[device.control]]
+            id = 1
+            hidden = false
+            default = 10
+            "#,
+        );
+        assert_eq!(bundled.len(), 2);
+
+        let identity = DeviceIdentity {
+            driver: "uvcvideo".to_string(),
+            card: "Webcam".to_string(),
+            usb_id: Some("1234:abcd".to_string()),
+        };
+
+        let quirks = DeviceQuirks::from_entries(&bundled, &identity);
+        assert!(!quirks.apply(&mut description(1, "Brightness")));
+
+        let mut gain = description(2, "Gain");
+        assert!(quirks.apply(&mut gain));
+        assert_eq!((gain.name.as_str(), gain.maximum), ("Analog Gain", 100));
+
+        let entries: Vec<QuirkEntry> = bundled.into_iter().chain(user).collect();
+        let quirks = DeviceQuirks::from_entries(&entries, &identity);
+        let mut brightness = description(1, "Brightness");
+        assert!(quirks.apply(&mut brightness));
+        assert_eq!(brightness.default, 10);
+
+        let mut ids = [1, 2, 3, 4];
+        ids.sort_by_key(|id| quirks.write_position(*id));
+        assert_eq!(ids, [3, 1, 2, 4]);
+
+        let other = DeviceIdentity { usb_id: None, ..identity };
+        let mut gain = description(2, "Gain");
+        DeviceQuirks::from_entries(&entries, &other).apply(&mut gain);
+        assert_eq!(gain.name, "Gain");
+    }
+}
@@ -0,0 +1,182 @@
+/// Luma at or below is counted as crushed shadows
+const SHADOW_CLIP_LEVEL: u8 = 2;
+/// Luma at or above is counted as clipped highlights
+const HIGHLIGHT_CLIP_LEVEL: u8 = 253;
+/// Share of clipped pixels, from which a warning is shown
+pub const CLIPPING_WARNING_SHARE: f64 = 0.02;
+
+/// Pixels as 8-bit RGB in rows of `stride` bytes.
+pub struct RgbFrame<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+    pub data: &'a [u8],
+}
+
+impl RgbFrame<'_> {
+    fn pixels(&self) -> impl Iterator<Item = (usize, [u8; 3])> + '_ {
+        (0..self.height).flat_map(move |y| {
+            let row = &self.data[y * self.stride..];
+            (0..self.width).map(move |x| (x, [row[x * 3], row[x * 3 + 1], row[x * 3 + 2]]))
+        })
+    }
+}
+
+/// Counts of 256 levels.
+pub type Histogram = [u32; 256];
+
+/// Luma distribution per column of the frame, like on a waveform monitor.
+pub struct Waveform {
+    pub columns: usize,
+    /// Counts of 256 levels per column
+    pub counts: Vec<u32>,
+}
+
+impl Waveform {
+    pub fn count(&self, column: usize, level: usize) -> u32 {
+        self.counts[column * 256 + level]
+    }
+}
+
+/// Chroma distribution on a square grid, Cb to the right and Cr upwards.
+pub struct Vectorscope {
+    pub size: usize,
+    pub counts: Vec<u32>,
+}
+
+impl Vectorscope {
+    pub fn count(&self, x: usize, y: usize) -> u32 {
+        self.counts[y * self.size + x]
+    }
+}
+
+/// Shares of pixels with clipped shadows and highlights.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clipping {
+    pub shadows: f64,
+    pub highlights: f64,
+}
+
+impl Clipping {
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        if self.highlights >= CLIPPING_WARNING_SHARE {
+            warnings.push(format!("Highlights clipped: {:.1}%", self.highlights * 100.0));
+        }
+        if self.shadows >= CLIPPING_WARNING_SHARE {
+            warnings.push(format!("Shadows crushed: {:.1}%", self.shadows * 100.0));
+        }
+
+        warnings
+    }
+}
+
+pub struct ScopeData {
+    pub luma: Histogram,
+    pub red: Histogram,
+    pub green: Histogram,
+    pub blue: Histogram,
+    pub waveform: Waveform,
+    pub vectorscope: Vectorscope,
+    pub clipping: Clipping,
+}
+
+/// Luma with BT.709 weights, which sum up to 256.
+pub fn luma(rgb: [u8; 3]) -> u8 {
+    ((54 * rgb[0] as u32 + 183 * rgb[1] as u32 + 19 * rgb[2] as u32) >> 8) as u8
+}
+
+/// Chroma with BT.709 weights, both in -128..128.
+fn chroma(rgb: [u8; 3]) -> (f64, f64) {
+    let (r, g, b) = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+
+    let cb = -0.1146 * r - 0.3854 * g + 0.5 * b;
+    let cr = 0.5 * r - 0.4542 * g - 0.0458 * b;
+    (cb, cr)
+}
+
+/// Computes all scopes of the frame in one pass.
+pub fn analyze(frame: &RgbFrame, waveform_columns: usize, vectorscope_size: usize) -> ScopeData {
+    let mut data = ScopeData {
+        luma: [0; 256],
+        red: [0; 256],
+        green: [0; 256],
+        blue: [0; 256],
+        waveform: Waveform {
+            columns: waveform_columns,
+            counts: vec![0; waveform_columns * 256],
+        },
+        vectorscope: Vectorscope {
+            size: vectorscope_size,
+            counts: vec![0; vectorscope_size * vectorscope_size],
+        },
+        clipping: Clipping {
+            shadows: 0.0,
+            highlights: 0.0,
+        },
+    };
+
+    let scale = (vectorscope_size - 1) as f64 / 256.0;
+
+    for (x, rgb) in frame.pixels() {
+        let level = luma(rgb);
+
+        data.luma[level as usize] += 1;
+        data.red[rgb[0] as usize] += 1;
+        data.green[rgb[1] as usize] += 1;
+        data.blue[rgb[2] as usize] += 1;
+
+        let column = x * waveform_columns / frame.width;
+        data.waveform.counts[column * 256 + level as usize] += 1;
+
+        let (cb, cr) = chroma(rgb);
+        let scope_x = ((cb + 128.0) * scale).round() as usize;
+        let scope_y = ((128.0 - cr) * scale).round() as usize;
+        data.vectorscope.counts[scope_y * vectorscope_size + scope_x] += 1;
+    }
+
+    let total = (frame.width * frame.height).max(1) as f64;
+    let shadows: u32 = data.luma[..=SHADOW_CLIP_LEVEL as usize].iter().sum();
+    let highlights: u32 = data.luma[HIGHLIGHT_CLIP_LEVEL as usize..].iter().sum();
+    data.clipping = Clipping {
+        shadows: shadows as f64 / total,
+        highlights: highlights as f64 / total,
+    };
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        // White, black, red and gray with padding at the end of the rows
+        let data = [
+            255, 255, 255, 0, 0, 0, 0, //
+            255, 0, 0, 128, 128, 128, 0,
+        ];
+        let frame = RgbFrame {
+            width: 2,
+            height: 2,
+            stride: 7,
+            data: &data,
+        };
+
+        let scopes = analyze(&frame, 2, 65);
+
+        assert_eq!((scopes.luma[255], scopes.luma[0], scopes.luma[53], scopes.luma[128]), (1, 1, 1, 1));
+        assert_eq!((scopes.red[255], scopes.green[0], scopes.blue[128]), (2, 2, 1));
+        assert_eq!((scopes.waveform.count(0, 255), scopes.waveform.count(0, 53)), (1, 1));
+        assert_eq!((scopes.waveform.count(1, 0), scopes.waveform.count(1, 128)), (1, 1));
+
+        // Neutral colors are in the center, red is up and left
+        assert_eq!(scopes.vectorscope.count(32, 32), 3);
+        assert_eq!(scopes.vectorscope.count(25, 0), 1);
+
+        assert_eq!(scopes.clipping, Clipping { shadows: 0.25, highlights: 0.25 });
+        assert_eq!(scopes.clipping.warnings(), ["Highlights clipped: 25.0%", "Shadows crushed: 25.0%"]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use toml::{Table, Value};
+use v4l::Device;
+
+use crate::{
+    capture_mode::{get_capture_mode, CaptureMode},
+    files::get_device_id,
+    profiles::Profile,
+};
+
+/// A new path for a capture in the folder, named by prefix and the current
+/// time. Returns the path with the time in ISO 8601 for the sidecar.
+pub fn create_capture_path(folder: &Path, prefix: &str, extension: &str) -> Result<(PathBuf, String), String> {
+    let now = glib::DateTime::now_local().map_err(|e| e.to_string())?;
+    let stamp = now.format("%Y-%m-%d_%H-%M-%S").map_err(|e| e.to_string())?;
+    let captured = now.format_iso8601().map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(folder).map_err(|e| format!("Error creating {}: {}", folder.display(), e))?;
+
+    Ok((unique_path(folder, &format!("{}-{}", prefix, stamp), extension), captured.to_string()))
+}
+
+/// Writes the controls and the capture mode of the device next to the
+/// capture, with the same name and the extension `toml`.
+pub fn write_sidecar(capture_path: &Path, device_path: &str, captured: &str) -> Result<(), String> {
+    let device = Device::with_path(device_path).map_err(|e| format!("Error opening device: {}", e))?;
+    let mode = match get_capture_mode(&device) {
+        Ok(m) => Some(m),
+        Err(e) => {
+            eprintln!("Error reading capture mode for sidecar: {}", e);
+            None
+        }
+    };
+
+    let file_name = capture_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let snapshot = Profile::from_device(file_name, Some(get_device_id(device_path)), &device)
+        .map_err(|e| format!("Error reading controls: {}", e))?;
+
+    let sidecar_path = capture_path.with_extension("toml");
+    fs::write(&sidecar_path, create_sidecar(&snapshot, mode.as_ref(), captured))
+        .map_err(|e| format!("Error writing {}: {}", sidecar_path.display(), e))
+}
+
+/// A path for `name` with the extension, which does not exist yet.
+fn unique_path(folder: &Path, name: &str, extension: &str) -> PathBuf {
+    let mut path = folder.join(format!("{}.{}", name, extension));
+
+    let mut counter = 2;
+    while path.exists() || path.with_extension("toml").exists() {
+        path = folder.join(format!("{}-{}.{}", name, counter, extension));
+        counter += 1;
+    }
+
+    path
+}
+
+/// The sidecar is a profile, so the settings of a capture can be loaded
+/// again. The capture mode is added for reference.
+fn create_sidecar(snapshot: &Profile, mode: Option<&CaptureMode>, captured: &str) -> String {
+    let mut table = snapshot.to_table();
+    table.insert("captured".to_string(), Value::String(captured.to_string()));
+
+    if let Some(mode) = mode {
+        let mut capture_mode = Table::new();
+        capture_mode.insert("fourcc".to_string(), Value::String(mode.fourcc.to_string()));
+        capture_mode.insert("width".to_string(), Value::Integer(mode.resolution.width as i64));
+        capture_mode.insert("height".to_string(), Value::Integer(mode.resolution.height as i64));
+
+        if let Some(interval) = mode.interval {
+            capture_mode.insert("interval".to_string(), Value::String(format!("{}/{}", interval.numerator, interval.denominator)));
+            capture_mode.insert("fps".to_string(), Value::Float(interval.fps()));
+        }
+
+        table.insert("capture_mode".to_string(), Value::Table(capture_mode));
+    }
+
+    toml::to_string(&table).expect("A table is always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture_mode::{Interval, Resolution};
+    use crate::profiles::ProfileControl;
+    use v4l::format::FourCC;
+
+    #[test]
+    fn test_sidecar() {
+        let mut snapshot = Profile::new("still-2026-01-02_03-04-05.png".to_string(), Some("usb-Cam".to_string()));
+        snapshot.controls.push(ProfileControl {
+            id: 0x00980900,
+            name: "Brightness".to_string(),
+            value: 128,
+            locked: false,
+        });
+
+        let mode = CaptureMode {
+            fourcc: FourCC::new(b"MJPG"),
+            resolution: Resolution { width: 1920, height: 1080 },
+            interval: Some(Interval::from_fps(30)),
+        };
+
+        let sidecar = create_sidecar(&snapshot, Some(&mode), "2026-01-02T03:04:05+01");
+        assert!(sidecar.contains("captured = \"2026-01-02T03:04:05+01\""));
+        assert!(sidecar.contains("fourcc = \"MJPG\""));
+        assert!(sidecar.contains("interval = \"1/30\""));
+
+        // Loadable as profile
+        assert_eq!(Profile::parse(&sidecar).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let folder = tempfile::tempdir().unwrap();
+        let first = unique_path(folder.path(), "still", "png");
+        assert_eq!(first, folder.path().join("still.png"));
+
+        fs::write(&first, b"").unwrap();
+        assert_eq!(unique_path(folder.path(), "still", "png"), folder.path().join("still-2.png"));
+    }
+}
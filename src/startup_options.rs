@@ -0,0 +1,61 @@
+use gtk::{gio, gio::prelude::*, glib};
+
+/// Options given on the command-line, either to start the app or forwarded to
+/// an already running instance.
+#[derive(Debug, Default, Clone)]
+pub struct StartupOptions {
+    /// Path or stable id of the camera to select
+    pub device: Option<String>,
+    /// Name or file of the profile to apply
+    pub profile: Option<String>,
+    pub no_preview: bool,
+    /// Streams the preview directly from the device instead of PipeWire
+    pub native_preview: bool,
+}
+
+impl StartupOptions {
+    /// Registers the supported options on the application.
+    pub fn register(app: &impl IsA<gio::Application>) {
+        app.add_main_option(
+            "device",
+            glib::Char::from(b'd'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::String,
+            "Camera to select, by path (/dev/video0) or stable id (/dev/v4l/by-id)",
+            Some("DEVICE"),
+        );
+        app.add_main_option(
+            "profile",
+            glib::Char::from(b'p'),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::String,
+            "Profile to apply to the camera, by name or file",
+            Some("PROFILE"),
+        );
+        app.add_main_option(
+            "no-preview",
+            glib::Char::from(0),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::None,
+            "Do not show the camera preview",
+            None,
+        );
+        app.add_main_option(
+            "native-preview",
+            glib::Char::from(0),
+            glib::OptionFlags::NONE,
+            glib::OptionArg::None,
+            "Stream the preview directly from the camera instead of through PipeWire",
+            None,
+        );
+    }
+
+    pub fn from_dict(dict: &glib::VariantDict) -> Self {
+        StartupOptions {
+            device: dict.lookup::<String>("device").ok().flatten(),
+            profile: dict.lookup::<String>("profile").ok().flatten(),
+            no_preview: dict.contains("no-preview"),
+            native_preview: dict.contains("native-preview"),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+use aperture::Viewfinder;
+use gtk::{gdk, gdk_pixbuf, gio, prelude::*};
+
+use crate::{
+    capture_settings::{CaptureSettings, StillFormat},
+    preview_frame::download_rgb,
+    sidecar::{create_capture_path, write_sidecar},
+};
+
+const FILE_NAME_PREFIX: &str = "still";
+const JPEG_QUALITY: &str = "95";
+
+/// Starts taking a picture with the viewfinder, with a sidecar file containing
+/// the controls and the capture mode of the device. The picture is saved,
+/// when `picture-done` is emitted, see `finish_still`.
+pub fn capture_still(view: &Viewfinder, device_path: &str, settings: &CaptureSettings) -> Result<PathBuf, String> {
+    let (path, captured) = create_capture_path(&settings.folder, FILE_NAME_PREFIX, settings.still_format.extension())?;
+    view.take_picture(&path).map_err(|e| e.to_string())?;
+    write_sidecar(&path, device_path, &captured)?;
+
+    Ok(path)
+}
+
+/// Converts a picture of the viewfinder to the format of its name, as the
+/// viewfinder encodes JPEG.
+pub fn finish_still(file: &gio::File) -> Result<(), String> {
+    let path = file.path().ok_or("The picture has no path")?;
+    let format = match path.extension().and_then(|e| e.to_str()) {
+        Some("png") => StillFormat::Png,
+        _ => return Ok(()),
+    };
+
+    let texture = gdk::Texture::from_file(file).map_err(|e| format!("Error loading {}: {}", path.display(), e))?;
+    save_texture(&texture, &path, format)
+}
+
+/// Saves a frame of the direct preview with a sidecar file, like
+/// `capture_still`. Returns the path of the image.
+pub fn save_still(texture: &gdk::Texture, device_path: &str, settings: &CaptureSettings) -> Result<PathBuf, String> {
+    let (path, captured) = create_capture_path(&settings.folder, FILE_NAME_PREFIX, settings.still_format.extension())?;
+    save_texture(texture, &path, settings.still_format)?;
+    write_sidecar(&path, device_path, &captured)?;
+
+    Ok(path)
+}
+
+fn save_texture(texture: &gdk::Texture, path: &Path, format: StillFormat) -> Result<(), String> {
+    let result = match format {
+        StillFormat::Png => texture.save_to_png(path).map_err(|e| e.to_string()),
+        StillFormat::Jpeg => {
+            // GDK only writes PNG and TIFF
+            let (bytes, stride) = download_rgb(texture);
+
+            let pixbuf = gdk_pixbuf::Pixbuf::from_bytes(
+                &bytes,
+                gdk_pixbuf::Colorspace::Rgb,
+                false,
+                8,
+                texture.width(),
+                texture.height(),
+                stride as i32,
+            );
+            pixbuf
+                .savev(path, "jpeg", &[("quality", JPEG_QUALITY)])
+                .map_err(|e| e.to_string())
+        }
+    };
+
+    result.map_err(|e| format!("Error saving {}: {}", path.display(), e))
+}
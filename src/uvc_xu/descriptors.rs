@@ -0,0 +1,148 @@
+use std::{fs, path::Path};
+
+const DESCRIPTOR_INTERFACE: u8 = 0x04;
+const DESCRIPTOR_CS_INTERFACE: u8 = 0x24;
+const VC_EXTENSION_UNIT: u8 = 0x06;
+const CLASS_VIDEO: u8 = 0x0e;
+const SUBCLASS_VIDEO_CONTROL: u8 = 0x01;
+
+/// An extension unit of a UVC camera, as described in its USB configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionUnit {
+    pub unit_id: u8,
+    /// GUID in the notation of `lsusb -v`
+    pub guid: String,
+    pub num_controls: u8,
+    /// Bitmap of the available controls, bit 0 is selector 1
+    pub controls: Vec<u8>,
+}
+
+impl ExtensionUnit {
+    pub fn has_selector(&self, selector: u8) -> bool {
+        if selector == 0 {
+            return false;
+        }
+
+        let bit = (selector - 1) as usize;
+        match self.controls.get(bit / 8) {
+            Some(byte) => byte & (1 << (bit % 8)) != 0,
+            None => false,
+        }
+    }
+
+    /// Selectors are limited to 255, also for larger bitmaps.
+    pub fn selectors(&self) -> Vec<u8> {
+        (1..=self.controls.len() * 8)
+            .filter_map(|s| u8::try_from(s).ok())
+            .filter(|s| self.has_selector(*s))
+            .collect()
+    }
+}
+
+/// Reads the extension units from the raw descriptors of the USB device.
+pub fn read_extension_units(usb_device_dir: &Path) -> Vec<ExtensionUnit> {
+    match fs::read(usb_device_dir.join("descriptors")) {
+        Ok(descriptors) => parse_extension_units(&descriptors),
+        Err(_) => vec![],
+    }
+}
+
+pub fn parse_extension_units(descriptors: &[u8]) -> Vec<ExtensionUnit> {
+    let mut units = vec![];
+    let mut in_video_control = false;
+    let mut i = 0;
+
+    while i + 2 <= descriptors.len() {
+        let length = descriptors[i] as usize;
+        if length < 2 || i + length > descriptors.len() {
+            break;
+        }
+
+        let descriptor = &descriptors[i..i + length];
+        match descriptor[1] {
+            DESCRIPTOR_INTERFACE if length >= 7 => {
+                in_video_control = descriptor[5] == CLASS_VIDEO && descriptor[6] == SUBCLASS_VIDEO_CONTROL;
+            }
+            DESCRIPTOR_CS_INTERFACE if in_video_control && length > 2 && descriptor[2] == VC_EXTENSION_UNIT => {
+                if let Some(unit) = parse_extension_unit(descriptor) {
+                    units.push(unit);
+                }
+            }
+            _ => {}
+        };
+
+        i += length;
+    }
+
+    units
+}
+
+fn parse_extension_unit(descriptor: &[u8]) -> Option<ExtensionUnit> {
+    let num_in_pins = *descriptor.get(21)? as usize;
+    let control_size = *descriptor.get(22 + num_in_pins)? as usize;
+    let controls_start = 23 + num_in_pins;
+
+    Some(ExtensionUnit {
+        unit_id: descriptor[3],
+        guid: format_guid(descriptor.get(4..20)?),
+        num_controls: descriptor[20],
+        controls: descriptor.get(controls_start..controls_start + control_size)?.to_vec(),
+    })
+}
+
+/// Formats the GUID like lsusb, with the first three fields little-endian.
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{}-{}",
+        bytes[3], bytes[2], bytes[1], bytes[0],
+        bytes[5], bytes[4],
+        bytes[7], bytes[6],
+        bytes[8..10].iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+        bytes[10..16].iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_extension_units() {
+        let mut descriptors = vec![
+            // Video control interface
+            9, DESCRIPTOR_INTERFACE, 0, 0, 1, CLASS_VIDEO, SUBCLASS_VIDEO_CONTROL, 0, 0,
+        ];
+        // Extension unit 6 with one input pin and 2 bytes of controls
+        descriptors.extend_from_slice(&[27, DESCRIPTOR_CS_INTERFACE, VC_EXTENSION_UNIT, 6]);
+        descriptors.extend_from_slice(&[
+            0x82, 0x06, 0x61, 0x63, 0x70, 0x50, 0xab, 0x49, 0xb8, 0xcc, 0xb3, 0x85, 0x5e, 0x8d, 0x22, 0x1d,
+        ]);
+        descriptors.extend_from_slice(&[3, 1, 2, 2, 0b0000_0101, 0b0000_0001, 0]);
+        // Streaming interface, extension units are ignored there
+        descriptors.extend_from_slice(&[9, DESCRIPTOR_INTERFACE, 1, 0, 0, CLASS_VIDEO, 0x02, 0, 0]);
+        descriptors.extend_from_slice(&[5, DESCRIPTOR_CS_INTERFACE, VC_EXTENSION_UNIT, 9, 0]);
+
+        let units = parse_extension_units(&descriptors);
+
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].unit_id, 6);
+        assert_eq!(units[0].guid, "63610682-5070-49ab-b8cc-b3855e8d221d");
+        assert_eq!(units[0].selectors(), vec![1, 3, 9]);
+        assert!(!units[0].has_selector(2));
+    }
+
+    #[test]
+    fn test_selectors_of_large_bitmap() {
+        let unit = ExtensionUnit {
+            unit_id: 4,
+            guid: String::new(),
+            num_controls: 255,
+            controls: vec![0xff; 33],
+        };
+
+        let selectors = unit.selectors();
+
+        assert_eq!(selectors.len(), 255);
+        assert_eq!(selectors.last(), Some(&255));
+    }
+}
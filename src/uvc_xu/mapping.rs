@@ -0,0 +1,196 @@
+use std::{fs, io, path::PathBuf};
+
+use toml::{Table, Value};
+
+use crate::files::get_config_dir;
+
+const MAPPING_FILE_NAME: &str = "xu-controls.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XuControlType {
+    Integer,
+    Boolean,
+    Menu(Vec<(i64, String)>),
+}
+
+/// Describes a known control of an extension unit, so it can be shown like
+/// the controls of the driver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XuMapping {
+    pub name: String,
+    /// GUID of the extension unit, as shown by `lsusb -v`
+    pub guid: String,
+    pub selector: u8,
+    /// Byte offset of the value within the control
+    pub offset: usize,
+    /// Length of the value in bytes, the rest of the control if not set
+    pub length: Option<usize>,
+    /// Values are two's complement, e.g. for negative minimums
+    pub signed: bool,
+    pub control_type: XuControlType,
+}
+
+impl XuMapping {
+    pub fn matches_guid(&self, guid: &str) -> bool {
+        normalize_guid(&self.guid) == normalize_guid(guid)
+    }
+}
+
+fn normalize_guid(guid: &str) -> String {
+    guid.trim_matches(|c| c == '{' || c == '}').to_lowercase()
+}
+
+/// Path of the user-editable file with the mappings.
+pub fn get_mapping_file() -> PathBuf {
+    get_config_dir().join(MAPPING_FILE_NAME)
+}
+
+/// Loads the mappings of the user. Invalid entries are skipped.
+pub fn load_mappings() -> Vec<XuMapping> {
+    let path = get_mapping_file();
+
+    match fs::read_to_string(&path) {
+        Ok(content) => parse_mappings(&content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            vec![]
+        }
+    }
+}
+
+/// Parses mappings like
+///
+/// ```toml
+/// [[control]]
+/// name = "LED Mode"
+/// guid = "49e40215-f434-47fe-b158-0e885023e51b"
+/// selector = 1
+/// length = 1
+/// signed = false
+/// type = "menu"
+/// options = [{ value = 0, name = "Off" }, { value = 1, name = "On" }]
+/// ```
+pub fn parse_mappings(content: &str) -> Vec<XuMapping> {
+    let table = match content.parse::<Table>() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Invalid extension unit mappings: {}", e);
+            return vec![];
+        }
+    };
+
+    let controls = match table.get("control").and_then(|c| c.as_array()) {
+        Some(c) => c,
+        None => return vec![],
+    };
+
+    controls
+        .iter()
+        .filter_map(|c| match parse_mapping(c) {
+            Ok(m) => Some(m),
+            Err(e) => {
+                eprintln!("Ignoring extension unit mapping: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_mapping(value: &Value) -> Result<XuMapping, String> {
+    let table = value.as_table().ok_or("Entry is not a table")?;
+
+    let name = table
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or("Missing name")?
+        .to_string();
+
+    let get_str = |key: &str| table.get(key).and_then(|v| v.as_str());
+    let get_int = |key: &str| table.get(key).and_then(|v| v.as_integer());
+
+    let guid = get_str("guid").ok_or(format!("Missing guid of {}", name))?.to_string();
+
+    let selector = get_int("selector")
+        .filter(|s| (1..=255).contains(s))
+        .ok_or(format!("Missing or invalid selector of {}", name))? as u8;
+
+    let offset = get_int("offset").unwrap_or(0).max(0) as usize;
+    let length = get_int("length").filter(|l| (1..=8).contains(l)).map(|l| l as usize);
+    let signed = table.get("signed").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let control_type = match get_str("type").unwrap_or("integer") {
+        "integer" => XuControlType::Integer,
+        "boolean" => XuControlType::Boolean,
+        "menu" => XuControlType::Menu(parse_options(table.get("options"))),
+        t => return Err(format!("Unknown type {} of {}", t, name)),
+    };
+
+    Ok(XuMapping {
+        name,
+        guid,
+        selector,
+        offset,
+        length,
+        signed,
+        control_type,
+    })
+}
+
+fn parse_options(options: Option<&Value>) -> Vec<(i64, String)> {
+    options
+        .and_then(|o| o.as_array())
+        .map(|o| {
+            o.iter()
+                .filter_map(|option| {
+                    let value = option.get("value")?.as_integer()?;
+                    let name = option.get("name")?.as_str()?;
+                    Some((value, name.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mappings() {
+        let mappings = parse_mappings(
+            r#"
+            [[control]]
+            name = "LED Mode"
+            guid = "{49E40215-F434-47FE-B158-0E885023E51B}"
+            selector = 1
+            length = 1
+            type = "menu"
+            options = [{ value = 0, name = "Off" }, { value = 1, name = "On" }]
+
+            [[control]]
+            name = "Missing selector"
+            guid = "49e40215-f434-47fe-b158-0e885023e51b"
+
+            [[control]]
+            name = "Gain"
+            guid = "49e40215-f434-47fe-b158-0e885023e51b"
+            selector = 2
+            offset = 1
+            signed = true
+            "#,
+        );
+
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(
+            mappings[0].control_type,
+            XuControlType::Menu(vec![(0, "Off".to_string()), (1, "On".to_string())])
+        );
+        assert!(mappings[0].matches_guid("49e40215-f434-47fe-b158-0e885023e51b"));
+        assert_eq!(mappings[1].control_type, XuControlType::Integer);
+        assert_eq!(mappings[1].offset, 1);
+        assert_eq!(mappings[1].length, None);
+        assert!(!mappings[0].signed);
+        assert!(mappings[1].signed);
+    }
+}
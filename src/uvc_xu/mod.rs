@@ -0,0 +1,166 @@
+mod descriptors;
+pub use self::descriptors::read_extension_units;
+
+mod mapping;
+pub use self::mapping::{get_mapping_file, XuControlType};
+
+mod query;
+pub use self::query::{UVC_GET_DEF, UVC_GET_MAX, UVC_GET_MIN, UVC_GET_RES};
+
+use std::{io, os::raw::c_int};
+
+use crate::device_discovery::get_usb_device_dir;
+
+use self::mapping::{load_mappings, XuMapping};
+use self::query::{
+    extract_value, insert_value, query_xu, query_xu_access, query_xu_enabled, query_xu_length, UVC_GET_CUR, UVC_SET_CUR,
+};
+
+// Ids of extension unit controls in the controls panel, outside the range of
+// the V4L2 control ids
+const XU_CONTROL_ID_BASE: u32 = 0xf000_0000;
+// Mapped values of one control are numbered below the unit and selector
+const XU_VALUE_BITS: u32 = 12;
+
+/// A mapped control of an extension unit, present on the device.
+#[derive(Debug, Clone)]
+pub struct XuControl {
+    pub unit: u8,
+    pub mapping: XuMapping,
+    pub writable: bool,
+    size: usize,
+    // Position among the mapped values of the same control
+    index: u32,
+}
+
+impl XuControl {
+    pub fn id(&self) -> u32 {
+        control_id(self.unit, self.mapping.selector, self.index)
+    }
+
+    /// Whether the value can be set now. Cameras disable some controls,
+    /// while they are in an automatic mode.
+    pub fn is_enabled(&self, fd: c_int) -> io::Result<bool> {
+        Ok(self.writable && query_xu_enabled(fd, self.unit, self.mapping.selector)?)
+    }
+
+    fn value_length(&self) -> usize {
+        self.mapping.length.unwrap_or(self.size - self.mapping.offset)
+    }
+
+    /// Reads the value with one of the `UVC_GET_*` queries.
+    pub fn query(&self, fd: c_int, query: u8) -> io::Result<i64> {
+        let mut data = vec![0u8; self.size];
+        query_xu(fd, self.unit, self.mapping.selector, query, &mut data)?;
+        Ok(extract_value(&data, self.mapping.offset, self.value_length(), self.mapping.signed))
+    }
+
+    /// Sets the value, keeping the rest of the control unchanged.
+    pub fn set(&self, fd: c_int, value: i64) -> io::Result<()> {
+        let mut data = vec![0u8; self.size];
+        query_xu(fd, self.unit, self.mapping.selector, UVC_GET_CUR, &mut data)?;
+        insert_value(&mut data, self.mapping.offset, self.value_length(), value);
+        query_xu(fd, self.unit, self.mapping.selector, UVC_SET_CUR, &mut data)
+    }
+
+    pub fn get(&self, fd: c_int) -> io::Result<i64> {
+        self.query(fd, UVC_GET_CUR)
+    }
+}
+
+/// Finds the controls of the user's mappings, which are provided by the
+/// extension units of the device.
+pub fn find_xu_controls(device_path: &str, fd: c_int) -> Vec<XuControl> {
+    let mappings = load_mappings();
+    if mappings.is_empty() {
+        return vec![];
+    }
+
+    let units = match get_usb_device_dir(device_path) {
+        Some(dir) => read_extension_units(&dir),
+        None => return vec![],
+    };
+
+    let mut controls = vec![];
+
+    for mapping in mappings {
+        let unit = units
+            .iter()
+            .find(|u| mapping.matches_guid(&u.guid) && u.has_selector(mapping.selector));
+
+        let unit = match unit {
+            Some(u) => u.unit_id,
+            None => continue,
+        };
+
+        let size = match query_xu_length(fd, unit, mapping.selector) {
+            Ok(s) => s as usize,
+            Err(e) => {
+                eprintln!("Error querying length of {}: {}", mapping.name, e);
+                continue;
+            }
+        };
+
+        if mapping.offset + mapping.length.unwrap_or(1) > size {
+            eprintln!("Mapping of {} exceeds the control size of {} bytes", mapping.name, size);
+            continue;
+        }
+
+        let (readable, writable) = match query_xu_access(fd, unit, mapping.selector) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("Error querying access of {}: {}", mapping.name, e);
+                continue;
+            }
+        };
+
+        let index = controls
+            .iter()
+            .filter(|c: &&XuControl| c.unit == unit && c.mapping.selector == mapping.selector)
+            .count() as u32;
+
+        if index >= 1 << XU_VALUE_BITS {
+            eprintln!("Too many values are mapped in the control of {}", mapping.name);
+            continue;
+        }
+
+        if readable {
+            controls.push(XuControl {
+                unit,
+                mapping,
+                writable,
+                size,
+                index,
+            });
+        }
+    }
+
+    controls
+}
+
+/// Id of a mapped value in the controls panel, from the unit and selector of
+/// its control and its position among the values mapped in that control.
+fn control_id(unit: u8, selector: u8, index: u32) -> u32 {
+    XU_CONTROL_ID_BASE | (unit as u32) << (XU_VALUE_BITS + 8) | (selector as u32) << XU_VALUE_BITS | index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_id() {
+        let ids = [
+            control_id(1, 2, 0),
+            control_id(1, 2, 1),
+            control_id(2, 1, 0),
+            control_id(1, 3, 0),
+            control_id(255, 255, (1 << XU_VALUE_BITS) - 1),
+        ];
+
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id & XU_CONTROL_ID_BASE, XU_CONTROL_ID_BASE);
+            assert!(!ids[i + 1..].contains(id));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{io, mem, os::raw::c_int};
+
+use v4l::v4l2::{self, vidioc::_IOC_TYPE};
+
+// Not provided by the v4l-crate, see linux/uvcvideo.h
+const UVCIOC_CTRL_QUERY: _IOC_TYPE =
+    ((3 << 30) | ((mem::size_of::<uvc_xu_control_query>() as u32) << 16) | ((b'u' as u32) << 8) | 0x21) as _IOC_TYPE;
+
+pub const UVC_SET_CUR: u8 = 0x01;
+pub const UVC_GET_CUR: u8 = 0x81;
+pub const UVC_GET_MIN: u8 = 0x82;
+pub const UVC_GET_MAX: u8 = 0x83;
+pub const UVC_GET_RES: u8 = 0x84;
+const UVC_GET_LEN: u8 = 0x85;
+const UVC_GET_INFO: u8 = 0x86;
+pub const UVC_GET_DEF: u8 = 0x87;
+
+const INFO_SUPPORTS_GET: u8 = 1 << 0;
+const INFO_SUPPORTS_SET: u8 = 1 << 1;
+const INFO_DISABLED_BY_AUTO: u8 = 1 << 2;
+
+#[repr(C)]
+#[allow(non_camel_case_types, dead_code)]
+struct uvc_xu_control_query {
+    unit: u8,
+    selector: u8,
+    query: u8,
+    size: u16,
+    data: *mut u8,
+}
+
+/// Sends a query for a control of an extension unit, `data` must have the
+/// size of the control.
+pub fn query_xu(fd: c_int, unit: u8, selector: u8, query: u8, data: &mut [u8]) -> io::Result<()> {
+    let mut request = uvc_xu_control_query {
+        unit,
+        selector,
+        query,
+        size: data.len() as u16,
+        data: data.as_mut_ptr(),
+    };
+
+    unsafe {
+        v4l2::ioctl(
+            fd,
+            UVCIOC_CTRL_QUERY,
+            &mut request as *mut _ as *mut std::os::raw::c_void,
+        )
+    }
+}
+
+/// Size of the control in bytes, as reported by the camera.
+pub fn query_xu_length(fd: c_int, unit: u8, selector: u8) -> io::Result<u16> {
+    let mut data = [0u8; 2];
+    query_xu(fd, unit, selector, UVC_GET_LEN, &mut data)?;
+    Ok(u16::from_le_bytes(data))
+}
+
+/// Whether the control can be read and written.
+pub fn query_xu_access(fd: c_int, unit: u8, selector: u8) -> io::Result<(bool, bool)> {
+    let mut data = [0u8; 1];
+    query_xu(fd, unit, selector, UVC_GET_INFO, &mut data)?;
+    Ok((data[0] & INFO_SUPPORTS_GET != 0, data[0] & INFO_SUPPORTS_SET != 0))
+}
+
+/// Whether the control is not disabled by an automatic mode of the camera.
+pub fn query_xu_enabled(fd: c_int, unit: u8, selector: u8) -> io::Result<bool> {
+    let mut data = [0u8; 1];
+    query_xu(fd, unit, selector, UVC_GET_INFO, &mut data)?;
+    Ok(data[0] & INFO_DISABLED_BY_AUTO == 0)
+}
+
+/// Reads a little-endian value of `length` bytes at `offset`. Signed values
+/// are sign-extended from their length.
+pub fn extract_value(data: &[u8], offset: usize, length: usize, signed: bool) -> i64 {
+    let bytes: Vec<u8> = data.iter().skip(offset).take(length.min(8)).copied().collect();
+    let value = bytes
+        .iter()
+        .enumerate()
+        .fold(0, |value, (i, byte)| value | (*byte as i64) << (8 * i));
+
+    match bytes.len() {
+        len @ 1..=7 if signed => {
+            let shift = 64 - 8 * len;
+            (value << shift) >> shift
+        }
+        _ => value,
+    }
+}
+
+/// Writes `value` little-endian into `length` bytes at `offset`.
+pub fn insert_value(data: &mut [u8], offset: usize, length: usize, value: i64) {
+    for (i, byte) in data.iter_mut().skip(offset).take(length.min(8)).enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_extract_value() {
+        let mut data = [0xaa, 0, 0, 0xbb];
+        insert_value(&mut data, 1, 2, 0x1234);
+
+        assert_eq!(data, [0xaa, 0x34, 0x12, 0xbb]);
+        assert_eq!(extract_value(&data, 1, 2, false), 0x1234);
+        assert_eq!(extract_value(&data, 3, 4, false), 0xbb);
+
+        insert_value(&mut data, 1, 2, -2);
+        assert_eq!(data, [0xaa, 0xfe, 0xff, 0xbb]);
+        assert_eq!(extract_value(&data, 1, 2, true), -2);
+        assert_eq!(extract_value(&data, 1, 2, false), 0xfffe);
+        assert_eq!(extract_value(&data, 3, 4, true), -0x45);
+        assert_eq!(mem::size_of::<uvc_xu_control_query>(), 16);
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use aperture::Viewfinder;
+
+use crate::{
+    capture_settings::CaptureSettings,
+    sidecar::{create_capture_path, write_sidecar},
+};
+
+const FILE_NAME_PREFIX: &str = "clip";
+// The container of the encoding profile used by aperture
+const EXTENSION: &str = "webm";
+
+/// Starts recording the preview stream into the capture folder. Returns the
+/// path of the video, which is complete with the `recording-done` signal.
+pub fn start_recording(view: &Viewfinder, device_path: &str, settings: &CaptureSettings) -> Result<PathBuf, String> {
+    let (path, captured) = create_capture_path(&settings.folder, FILE_NAME_PREFIX, EXTENSION)?;
+    view.start_recording(&path).map_err(|e| e.to_string())?;
+
+    // Written at the start, controls changed while recording are not included
+    if let Err(e) = write_sidecar(&path, device_path, &captured) {
+        eprintln!("Error writing sidecar of {}: {}", path.display(), e);
+    }
+
+    Ok(path)
+}
+
+/// Formats a duration like "0:07" or "1:02:03".
+pub fn format_elapsed(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    match hours {
+        0 => format!("{}:{:02}", minutes, seconds),
+        _ => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(7), "0:07");
+        assert_eq!(format_elapsed(754), "12:34");
+        assert_eq!(format_elapsed(3723), "1:02:03");
+    }
+}
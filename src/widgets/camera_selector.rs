@@ -0,0 +1,319 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use adw::prelude::*;
+use aperture::DeviceProvider;
+use gtk::{gio, glib, Align, Box, DropDown, Label, StringList};
+
+use crate::{
+    camera::{find_camera_device, list_camera_groups, CameraDevice, CameraGroup, CameraSource},
+    components::create_hbox,
+    device_discovery::{get_node_label, watch_devices},
+    files::get_device_id,
+};
+
+// udev creates the stable ids of new device-nodes later, so the selected
+// camera is looked for again, while listed cameras have none
+const STABLE_ID_RETRY_DELAY: Duration = Duration::from_millis(500);
+const STABLE_ID_RETRIES: u32 = 10;
+
+/// Title-widget to select a camera. Follows cameras being plugged in and out:
+/// When the selected camera disappears, `on_unplugged` is called with its
+/// name, and when it comes back, it is selected again. Without a selected
+/// camera, the first one to appear is selected.
+///
+/// Cameras with several capture nodes, e.g. capture cards, are listed once,
+/// with a second dropdown for their nodes.
+pub struct CameraSelector {
+    source: CameraSource,
+    container: Box,
+    prefix_label: Label,
+    name_label: Label,
+    dropdown: DropDown,
+    names: StringList,
+    node_dropdown: DropDown,
+    node_labels: StringList,
+    groups: RefCell<Vec<CameraGroup>>,
+    // The nodes of all groups
+    cameras: RefCell<Vec<CameraDevice>>,
+    // Stable id of the selected camera, kept while it is unplugged. Empty,
+    // while no camera was selected.
+    selected_id: RefCell<String>,
+    selected_name: RefCell<String>,
+    unplugged: Cell<bool>,
+    // Set while the dropdown is updated to the listed cameras
+    updating: Cell<bool>,
+    stable_id_retries: Cell<u32>,
+    device_monitor: RefCell<Option<gio::FileMonitor>>,
+    on_selected: Box<dyn Fn(&CameraDevice)>,
+    on_unplugged: Box<dyn Fn(&str)>,
+}
+
+impl CameraSelector {
+    pub fn new(
+        source: CameraSource,
+        selected_camera: Option<&CameraDevice>,
+        on_selected: Box<dyn Fn(&CameraDevice)>,
+        on_unplugged: Box<dyn Fn(&str)>,
+    ) -> Rc<Self> {
+        let container = create_hbox();
+
+        let prefix_label = Label::new(Some("Select camera: "));
+        let name_label = Label::builder()
+            .halign(Align::Center)
+            .xalign(0.5)
+            .build();
+
+        let names = StringList::new(&[]);
+        let dropdown = DropDown::builder()
+            .model(&names)
+            .show_arrow(true)
+            .build();
+
+        let node_labels = StringList::new(&[]);
+        let node_dropdown = DropDown::builder()
+            .margin_start(6)
+            .model(&node_labels)
+            .show_arrow(true)
+            .tooltip_text("Video node of the camera")
+            .visible(false)
+            .build();
+
+        container.append(&name_label);
+        container.append(&prefix_label);
+        container.append(&dropdown);
+        container.append(&node_dropdown);
+
+        let selector = Rc::new(CameraSelector {
+            source,
+            container,
+            prefix_label,
+            name_label,
+            dropdown,
+            names,
+            node_dropdown,
+            node_labels,
+            groups: RefCell::new(vec![]),
+            cameras: RefCell::new(vec![]),
+            selected_id: RefCell::new(selected_camera.map(|c| get_device_id(&c.path)).unwrap_or_default()),
+            selected_name: RefCell::new(selected_camera.map(|c| c.name.clone()).unwrap_or_default()),
+            unplugged: Cell::new(selected_camera.is_none()),
+            updating: Cell::new(false),
+            stable_id_retries: Cell::new(0),
+            device_monitor: RefCell::new(None),
+            on_selected,
+            on_unplugged,
+        });
+
+        let weak_selector: Weak<CameraSelector> = Rc::downgrade(&selector);
+        selector.dropdown.connect_selected_notify(move |dropdown| {
+            let selector = match weak_selector.upgrade() {
+                Some(s) => s,
+                None => return,
+            };
+
+            if selector.updating.get() {
+                return;
+            }
+
+            // Keeps the selected node, when its camera is chosen again
+            let group = selector.groups.borrow().get(dropdown.selected() as usize).cloned();
+            if let Some(group) = group {
+                let selected_id = selector.selected_id.borrow().clone();
+                if !group.nodes.iter().any(|n| get_device_id(&n.path) == selected_id) {
+                    selector.select(&group.nodes[0]);
+                }
+            }
+        });
+
+        let weak_selector: Weak<CameraSelector> = Rc::downgrade(&selector);
+        selector.node_dropdown.connect_selected_notify(move |node_dropdown| {
+            let selector = match weak_selector.upgrade() {
+                Some(s) => s,
+                None => return,
+            };
+
+            if selector.updating.get() {
+                return;
+            }
+
+            let camera = selector
+                .groups
+                .borrow()
+                .get(selector.dropdown.selected() as usize)
+                .and_then(|g| g.nodes.get(node_dropdown.selected() as usize).cloned());
+            if let Some(camera) = camera {
+                selector.select(&camera);
+            }
+        });
+
+        let weak_selector: Weak<CameraSelector> = Rc::downgrade(&selector);
+        let on_changed = move || {
+            if let Some(selector) = weak_selector.upgrade() {
+                selector.update();
+            }
+        };
+
+        match source {
+            CameraSource::DeviceProvider => {
+                DeviceProvider::instance().connect_items_changed(move |_, _, _, _| on_changed());
+            }
+            CameraSource::DeviceNodes => {
+                selector.device_monitor.replace(watch_devices(move |_| on_changed()));
+            }
+        };
+
+        selector.update();
+
+        selector
+    }
+
+    pub fn get_widget(&self) -> &Box {
+        &self.container
+    }
+
+    /// Selects the camera and reports it to `on_selected`.
+    pub fn select(&self, camera: &CameraDevice) {
+        self.selected_id.replace(get_device_id(&camera.path));
+        self.selected_name.replace(camera.name.clone());
+        self.unplugged.set(false);
+
+        (self.on_selected)(camera);
+
+        self.sync_selection();
+        self.update_visibility();
+    }
+
+    /// Selects the camera by its device path or stable id. Returns false, if
+    /// it is not listed.
+    pub fn select_device(&self, device: &str) -> bool {
+        let camera = find_camera_device(&self.cameras.borrow(), device);
+        match camera {
+            Some(c) => {
+                self.select(&c);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Updates the dropdown to the listed cameras and follows the selected
+    /// camera being unplugged or coming back.
+    fn update(self: &Rc<Self>) {
+        self.stable_id_retries.set(0);
+        self.refresh();
+    }
+
+    fn refresh(self: &Rc<Self>) {
+        let groups = list_camera_groups(self.source);
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+
+        self.updating.set(true);
+        self.names.splice(0, self.names.n_items(), &names);
+        self.updating.set(false);
+
+        self.cameras.replace(groups.iter().flat_map(|g| g.nodes.clone()).collect());
+        self.groups.replace(groups);
+
+        let camera = self.sync_selection();
+
+        let first_camera = self.cameras.borrow().first().cloned();
+
+        match camera {
+            Some(c) if self.unplugged.get() => {
+                self.unplugged.set(false);
+                (self.on_selected)(&c);
+            }
+            None if self.selected_id.borrow().is_empty() => {
+                if let Some(c) = first_camera {
+                    self.select(&c);
+                }
+            }
+            None if !self.unplugged.get() => {
+                self.unplugged.set(true);
+                (self.on_unplugged)(&self.selected_name.borrow());
+            }
+            _ => {}
+        };
+
+        self.update_visibility();
+
+        if self.unplugged.get() && !self.selected_id.borrow().is_empty() {
+            self.retry_without_stable_ids();
+        }
+    }
+
+    /// Refreshes again later, while a listed camera has no stable id yet.
+    fn retry_without_stable_ids(self: &Rc<Self>) {
+        let missing_id = self
+            .cameras
+            .borrow()
+            .iter()
+            .any(|c| get_device_id(&c.path) == c.path);
+
+        let retries = self.stable_id_retries.get();
+        if !missing_id || retries >= STABLE_ID_RETRIES {
+            return;
+        }
+        self.stable_id_retries.set(retries + 1);
+
+        let weak_selector: Weak<CameraSelector> = Rc::downgrade(self);
+        glib::timeout_add_local_once(STABLE_ID_RETRY_DELAY, move || {
+            if let Some(selector) = weak_selector.upgrade() {
+                selector.refresh();
+            }
+        });
+    }
+
+    /// Selects the selected camera and its node in the dropdowns, and returns
+    /// it, if listed.
+    fn sync_selection(&self) -> Option<CameraDevice> {
+        let groups = self.groups.borrow();
+        let position = {
+            let selected_id = self.selected_id.borrow();
+            groups.iter().enumerate().find_map(|(g, group)| {
+                let n = group.nodes.iter().position(|c| get_device_id(&c.path) == *selected_id)?;
+                Some((g, n))
+            })
+        };
+
+        let labels: Vec<String> = match position {
+            Some((g, _)) => groups[g].nodes.iter().map(|n| get_node_label(&n.path)).collect(),
+            None => vec![],
+        };
+        let labels: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+
+        self.updating.set(true);
+        self.dropdown.set_selected(match position {
+            Some((g, _)) => g as u32,
+            None => gtk::INVALID_LIST_POSITION,
+        });
+        self.node_labels.splice(0, self.node_labels.n_items(), &labels);
+        self.node_dropdown.set_selected(match position {
+            Some((_, n)) => n as u32,
+            None => gtk::INVALID_LIST_POSITION,
+        });
+        self.updating.set(false);
+
+        self.node_dropdown.set_visible(labels.len() > 1);
+
+        position.map(|(g, n)| groups[g].nodes[n].clone())
+    }
+
+    /// Shows the dropdown of the cameras only, if there is something to choose
+    /// from.
+    fn update_visibility(&self) {
+        let groups = self.groups.borrow();
+        let show_dropdown = groups.len() > 1 || (self.unplugged.get() && !groups.is_empty());
+
+        self.prefix_label.set_visible(show_dropdown);
+        self.dropdown.set_visible(show_dropdown);
+        self.name_label.set_visible(!show_dropdown);
+
+        match groups.first() {
+            Some(group) => self.name_label.set_label(&group.name),
+            None => self.name_label.set_label("No camera detected"),
+        };
+    }
+}
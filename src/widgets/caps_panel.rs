@@ -1,10 +1,15 @@
 use std::rc::Rc;
 
-use adw::{prelude::*, PreferencesGroup, PreferencesPage};
-use aperture::Camera;
-use v4l::{format::Description, video::{capture::Parameters, Capture}, Device};
+use adw::{prelude::*, ActionRow, PreferencesGroup, PreferencesPage, PreferencesRow};
+use gtk::{FlowBox, Image, Label, Orientation, SelectionMode};
+use v4l::Device;
 
-use crate::{camera::get_path, components::create_info_row};
+use crate::{
+    capability_flags::Flag,
+    components::create_info_row,
+    device_info::query_device_info,
+    mode_matrix::{matrix_to_json, matrix_to_text, read_current_format, read_mode_matrix},
+};
 
 pub struct CapsPanel {
     page: Rc<PreferencesPage>,
@@ -12,12 +17,12 @@ pub struct CapsPanel {
 }
 
 impl CapsPanel {
-    pub fn new(for_camera: &Camera) -> Self {
+    pub fn new(device_path: &str) -> Self {
         let page = PreferencesPage::builder().width_request(300).build();
 
         let mut groups: Vec<PreferencesGroup> = vec![];
 
-        CapsPanel::fill_page(&page, &mut groups, for_camera);
+        CapsPanel::fill_page(&page, &mut groups, device_path);
 
         CapsPanel {
             page: Rc::new(page),
@@ -25,112 +30,141 @@ impl CapsPanel {
         }
     }
 
-    pub fn update(&mut self, for_camera: &Camera) {
+    pub fn update(&mut self, device_path: &str) {
         for group in &self.groups {
             self.page.remove(group);
         }
 
         let _ = &self.groups.clear();
 
-        CapsPanel::fill_page(&self.page, &mut self.groups, for_camera);
+        CapsPanel::fill_page(&self.page, &mut self.groups, device_path);
     }
 
     pub fn get_panel(&self) -> Rc<PreferencesPage> {
         self.page.clone()
     }
 
-    fn fill_page(page: &PreferencesPage, groups: &mut Vec<PreferencesGroup>, for_camera: &Camera) {
-        let caps_group = PreferencesGroup::builder().title("About").build();
+    fn fill_page(page: &PreferencesPage, groups: &mut Vec<PreferencesGroup>, device_path: &str) {
+        for section in query_device_info(device_path) {
+            let group = PreferencesGroup::builder().title(section.title).build();
 
-        page.add(&caps_group);
-
-        let device_path = get_path(&for_camera);
-        let device = match Device::with_path(device_path) {
-            Ok(d) => d,
-            Err(e) => {
-                let error_msg = create_info_row(
-                    "Error getting device for capabilities".to_string(),
-                    e.to_string(),
-                );
-                caps_group.add(&error_msg);
-                groups.push(caps_group);
-                return;
+            for (label, value) in section.entries {
+                group.add(&create_info_row(label, value));
             }
-        };
 
-        let caps = match device.query_caps() {
-            Ok(caps) => caps,
-            Err(e) => {
-                let error_msg =
-                    create_info_row("Error querying capabilities".to_string(), e.to_string());
-                caps_group.add(&error_msg);
-                groups.push(caps_group);
-                return;
+            for (label, flags) in section.flags {
+                group.add(&CapsPanel::create_flags_row(&label, &flags));
             }
-        };
 
-        let (major, minor, patch) = caps.version;
-        let version_string = format!("{}.{}.{}", major, minor, patch);
-        caps_group.add(&create_info_row("Bus".to_string(), caps.bus.clone()));
-        caps_group.add(&create_info_row("Card".to_string(), caps.card.clone()));
-        caps_group.add(&create_info_row("Driver".to_string(), caps.driver.clone()));
-        caps_group.add(&create_info_row("Version".to_string(), version_string));
-        caps_group.add(&create_info_row(
-            "Capabilities".to_string(),
-            caps.capabilities.to_string(),
-        ));
-
-        groups.push(caps_group);
-
-        match device.params() {
-            Ok(params) => {
-                let group = CapsPanel::create_params_group(params);
-                page.add(&group);
-                groups.push(group);
-            },
-            Err(e) => {
-                let error_msg =
-                    create_info_row("Error querying params".to_string(), e.to_string());
-                let panel = PreferencesGroup::builder().title("Parameters").build();
-                panel.add(&error_msg);
-                groups.push(panel);
-            }
-        };
+            page.add(&group);
+            groups.push(group);
+        }
 
-        match device.enum_formats() {
-            Ok(formats) => {
-                let group = CapsPanel::create_formats_group(formats);
-                page.add(&group);
-                groups.push(group);
-            }
-            Err(e) => {
-                let error_msg =
-                    create_info_row("Error querying formats".to_string(), e.to_string());
-                let panel = PreferencesGroup::builder().title("Formats").build();
-                panel.add(&error_msg);
-                groups.push(panel);
+        let export_group = CapsPanel::create_export_group(device_path);
+        page.add(&export_group);
+        groups.push(export_group);
+    }
+
+    /// Shows the flags as chips, explained in their tooltips.
+    fn create_flags_row(label: &str, flags: &[Flag]) -> PreferencesRow {
+        let title = Label::builder()
+            .css_classes(["caption", "dim-label"])
+            .halign(gtk::Align::Start)
+            .label(label)
+            .build();
+
+        let chips = FlowBox::builder()
+            .column_spacing(6)
+            .row_spacing(6)
+            .selection_mode(SelectionMode::None)
+            .build();
+
+        for flag in flags {
+            let chip = Label::builder()
+                .css_classes(["caption", "flag-chip"])
+                .label(flag.name.as_str())
+                .tooltip_text(flag.description.as_str())
+                .build();
+            if flag.highlight {
+                chip.add_css_class("highlight");
             }
-        };
+            chips.append(&chip);
+        }
+
+        if flags.is_empty() {
+            chips.append(&Label::builder().css_classes(["dim-label"]).label("None").build());
+        }
+
+        let rowbox = gtk::Box::builder()
+            .margin_bottom(8)
+            .margin_end(12)
+            .margin_start(12)
+            .margin_top(8)
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .build();
+        rowbox.append(&title);
+        rowbox.append(&chips);
+
+        let row = PreferencesRow::builder().activatable(false).build();
+        row.set_child(Some(&rowbox));
+        row
     }
 
-    fn create_params_group(params: Parameters) -> PreferencesGroup {
-        let group = PreferencesGroup::builder().title("Parameters").build();
+    /// Copies the current format and all modes, e.g. for integration work.
+    fn create_export_group(device_path: &str) -> PreferencesGroup {
+        let group = PreferencesGroup::builder().title("Export Modes").build();
+
+        let text_row = CapsPanel::create_copy_row("Copy as text");
+        let device_path_for_text = device_path.to_string();
+        text_row.connect_activated(move |row| {
+            CapsPanel::copy_modes(row, &device_path_for_text, false);
+        });
 
-        group.add(&create_info_row("Capabilities".to_string(), params.capabilities.to_string()));
-        group.add(&create_info_row("Interval".to_string(), params.interval.to_string()));
-        group.add(&create_info_row("Modes".to_string(), params.modes.to_string()));
+        let json_row = CapsPanel::create_copy_row("Copy as JSON");
+        let device_path_for_json = device_path.to_string();
+        json_row.connect_activated(move |row| {
+            CapsPanel::copy_modes(row, &device_path_for_json, true);
+        });
 
+        group.add(&text_row);
+        group.add(&json_row);
         group
     }
 
-    fn create_formats_group(descriptions: Vec<Description>) -> PreferencesGroup {
-        let group = PreferencesGroup::builder().title("Formats").build();
+    fn create_copy_row(title: &str) -> ActionRow {
+        let row = ActionRow::builder()
+            .activatable(true)
+            .title(title)
+            .build();
+        row.add_suffix(&Image::from_icon_name("edit-copy-symbolic"));
+        row
+    }
 
-        for desc in descriptions {
-            let format = format!("FourCC: {}\nFlags: {}", desc.fourcc, desc.flags);
-            group.add(&create_info_row(desc.description, format));
-        }
+    fn copy_modes(row: &ActionRow, device_path: &str, as_json: bool) {
+        let device = match Device::with_path(device_path) {
+            Ok(d) => d,
+            Err(e) => {
+                row.set_subtitle(&format!("Error opening device: {}", e));
+                return;
+            }
+        };
 
-        group
+        let matrix = match read_mode_matrix(&device) {
+            Ok(m) => m,
+            Err(e) => {
+                row.set_subtitle(&format!("Error enumerating modes: {}", e));
+                return;
+            }
+        };
+
+        let current = read_current_format(&device).ok();
+        let content = match as_json {
+            true => matrix_to_json(&matrix, current.as_ref()).to_string(),
+            false => matrix_to_text(&matrix, current.as_ref()),
+        };
+
+        row.clipboard().set_text(&content);
+        row.set_subtitle("Copied to the clipboard");
     }
 }
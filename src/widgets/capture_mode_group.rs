@@ -0,0 +1,293 @@
+use std::cell::Cell;
+use std::rc::{Rc, Weak};
+use std::{io, time::Duration};
+
+use adw::{prelude::*, ActionRow, ComboRow, PreferencesGroup};
+use gtk::{glib, StringList};
+use v4l::Device;
+
+use crate::{
+    capture_mode::{get_capture_mode, list_capture_modes, set_capture_mode, CaptureMode, FormatModes},
+    components::create_info_row,
+    device_usage::is_busy_error,
+};
+
+/// How often setting the mode is tried, while the stopped preview still
+/// holds the buffers of the device
+const BUSY_RETRIES: u32 = 10;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Selects pixel format, frame size and frame rate of the camera. The preview
+/// is stopped with `set_preview_streaming` while the mode is changed.
+pub struct CaptureModeGroup {
+    device_path: String,
+    formats: Vec<FormatModes>,
+    group: PreferencesGroup,
+    format_row: ComboRow,
+    size_row: ComboRow,
+    rate_row: ComboRow,
+    active_row: ActionRow,
+    // Set while the rows are updated to the active mode
+    updating: Cell<bool>,
+    set_preview_streaming: Rc<Box<dyn Fn(bool)>>,
+}
+
+impl CaptureModeGroup {
+    /// Returns `None`, if the device has no capture formats.
+    pub fn new(device: &Device, device_path: &str, set_preview_streaming: Rc<Box<dyn Fn(bool)>>) -> Option<Rc<Self>> {
+        let formats = match list_capture_modes(device) {
+            Ok(f) if !f.is_empty() => f,
+            Ok(_) => return None,
+            Err(e) => {
+                eprintln!("Error listing capture modes: {}", e);
+                return None;
+            }
+        };
+
+        let format_names: Vec<String> = formats
+            .iter()
+            .map(|f| format!("{} ({})", f.description, f.fourcc))
+            .collect();
+        let format_names: Vec<&str> = format_names.iter().map(|n| n.as_str()).collect();
+
+        let format_row = ComboRow::builder()
+            .model(&StringList::new(&format_names))
+            .title("Format")
+            .build();
+
+        let size_row = ComboRow::builder()
+            .model(&StringList::new(&[]))
+            .title("Resolution")
+            .build();
+
+        let rate_row = ComboRow::builder()
+            .model(&StringList::new(&[]))
+            .title("Frame rate")
+            .build();
+
+        let active_row = create_info_row("Active mode".to_string(), String::new());
+
+        let group = PreferencesGroup::builder()
+            .title("Capture Mode")
+            .build();
+        group.add(&format_row);
+        group.add(&size_row);
+        group.add(&rate_row);
+        group.add(&active_row);
+
+        let capture_mode = Rc::new(CaptureModeGroup {
+            device_path: device_path.to_string(),
+            formats,
+            group,
+            format_row,
+            size_row,
+            rate_row,
+            active_row,
+            updating: Cell::new(false),
+            set_preview_streaming,
+        });
+
+        capture_mode.show_device_mode(device);
+
+        let weak_group: Weak<CaptureModeGroup> = Rc::downgrade(&capture_mode);
+        capture_mode.format_row.connect_selected_notify(move |_| {
+            if let Some(group) = weak_group.upgrade() {
+                if !group.updating.get() {
+                    group.update_sizes();
+                    group.update_rates();
+                    group.apply();
+                }
+            }
+        });
+
+        let weak_group: Weak<CaptureModeGroup> = Rc::downgrade(&capture_mode);
+        capture_mode.size_row.connect_selected_notify(move |_| {
+            if let Some(group) = weak_group.upgrade() {
+                if !group.updating.get() {
+                    group.update_rates();
+                    group.apply();
+                }
+            }
+        });
+
+        let weak_group: Weak<CaptureModeGroup> = Rc::downgrade(&capture_mode);
+        capture_mode.rate_row.connect_selected_notify(move |_| {
+            if let Some(group) = weak_group.upgrade() {
+                if !group.updating.get() {
+                    group.apply();
+                }
+            }
+        });
+
+        Some(capture_mode)
+    }
+
+    pub fn get_group(&self) -> &PreferencesGroup {
+        &self.group
+    }
+
+    /// Keeps the active mode shown, but prevents changing it.
+    pub fn set_unavailable(&self, reason: &str) {
+        self.group.set_description(Some(reason));
+        self.group.set_sensitive(false);
+    }
+
+    /// Reads the active mode again, e.g. after another application changed it.
+    pub fn refresh(&self) {
+        match Device::with_path(&self.device_path) {
+            Ok(device) => self.show_device_mode(&device),
+            Err(e) => eprintln!("Error opening device for capture mode: {}", e),
+        };
+    }
+
+    fn show_device_mode(&self, device: &Device) {
+        match get_capture_mode(device) {
+            Ok(mode) => self.show_active_mode(&mode, None),
+            Err(e) => self.active_row.set_subtitle(&format!("Unknown: {}", e)),
+        };
+    }
+
+    fn selected_format(&self) -> Option<&FormatModes> {
+        self.formats.get(self.format_row.selected() as usize)
+    }
+
+    /// Lists the sizes of the selected format, keeping the selected size if
+    /// available.
+    fn update_sizes(&self) {
+        let previous = self.size_row.selected_item().and_downcast::<gtk::StringObject>().map(|s| s.string());
+
+        let labels: Vec<String> = match self.selected_format() {
+            Some(f) => f.sizes.iter().map(|s| s.resolution.to_string()).collect(),
+            None => vec![],
+        };
+
+        let position = previous
+            .and_then(|p| labels.iter().position(|l| *l == p.as_str()))
+            .unwrap_or(0);
+
+        self.set_items(&self.size_row, &labels, position as u32);
+    }
+
+    /// Lists the frame rates of the selected size, keeping the selected rate
+    /// if available.
+    fn update_rates(&self) {
+        let previous = self.rate_row.selected_item().and_downcast::<gtk::StringObject>().map(|s| s.string());
+
+        let labels: Vec<String> = match self
+            .selected_format()
+            .and_then(|f| f.sizes.get(self.size_row.selected() as usize))
+        {
+            Some(s) => s.intervals.iter().map(|i| i.to_string()).collect(),
+            None => vec![],
+        };
+
+        let position = previous
+            .and_then(|p| labels.iter().position(|l| *l == p.as_str()))
+            .unwrap_or(0);
+
+        self.rate_row.set_sensitive(!labels.is_empty());
+        self.set_items(&self.rate_row, &labels, position as u32);
+    }
+
+    fn set_items(&self, row: &ComboRow, labels: &[String], selected: u32) {
+        let labels: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+
+        self.updating.set(true);
+        row.set_model(Some(&StringList::new(&labels)));
+        row.set_selected(selected);
+        self.updating.set(false);
+    }
+
+    fn selected_mode(&self) -> Option<CaptureMode> {
+        let format = self.selected_format()?;
+        let size = format.sizes.get(self.size_row.selected() as usize)?;
+
+        Some(CaptureMode {
+            fourcc: format.fourcc,
+            resolution: size.resolution,
+            interval: size.intervals.get(self.rate_row.selected() as usize).copied(),
+        })
+    }
+
+    /// Sets the selected mode with the preview stopped, as the format cannot
+    /// be changed while streaming. Shows the mode accepted by the driver.
+    fn apply(self: &Rc<Self>) {
+        let mode = match self.selected_mode() {
+            Some(m) => m,
+            None => return,
+        };
+
+        // Changed again only after the preview was restarted
+        self.group.set_sensitive(false);
+        (self.set_preview_streaming)(false);
+
+        self.set_mode_when_released(mode, 0);
+    }
+
+    /// Sets the mode, retrying while the device is busy, as a stopped stream
+    /// may release it with a delay. Restarts the preview afterwards.
+    fn set_mode_when_released(self: &Rc<Self>, mode: CaptureMode, retries: u32) {
+        let result = Device::with_path(&self.device_path).and_then(|device| set_capture_mode(&device, &mode));
+
+        match result {
+            Err(e) if is_busy_error(&e) && retries < BUSY_RETRIES => {
+                let weak_group: Weak<CaptureModeGroup> = Rc::downgrade(self);
+                glib::timeout_add_local_once(BUSY_RETRY_DELAY, move || {
+                    if let Some(group) = weak_group.upgrade() {
+                        group.set_mode_when_released(mode, retries + 1);
+                    }
+                });
+            }
+            result => {
+                (self.set_preview_streaming)(true);
+                self.group.set_sensitive(true);
+                self.show_result(&mode, result);
+            }
+        };
+    }
+
+    fn show_result(&self, mode: &CaptureMode, result: io::Result<CaptureMode>) {
+        match result {
+            Ok(_) => match Device::with_path(&self.device_path).and_then(|d| get_capture_mode(&d)) {
+                Ok(active) => self.show_active_mode(&active, Some(mode)),
+                Err(e) => self.active_row.set_subtitle(&format!("Unknown: {}", e)),
+            },
+            Err(e) => {
+                eprintln!("Error setting capture mode {}: {}", mode, e);
+                self.active_row.set_subtitle(&format!("Not applied: {}", e));
+            }
+        };
+    }
+
+    /// Shows the mode accepted by the driver and selects it in the rows.
+    fn show_active_mode(&self, mode: &CaptureMode, requested: Option<&CaptureMode>) {
+        let subtitle = match requested {
+            Some(r) if r != mode => format!("{} (adjusted by the driver)", mode),
+            _ => mode.to_string(),
+        };
+        self.active_row.set_subtitle(&subtitle);
+
+        let format_position = self.formats.iter().position(|f| f.fourcc == mode.fourcc);
+        let size_position = format_position
+            .and_then(|f| self.formats[f].sizes.iter().position(|s| s.resolution == mode.resolution));
+
+        self.updating.set(true);
+        self.format_row.set_selected(format_position.unwrap_or(0) as u32);
+        self.updating.set(false);
+        self.update_sizes();
+
+        self.updating.set(true);
+        self.size_row.set_selected(size_position.unwrap_or(0) as u32);
+        self.updating.set(false);
+        self.update_rates();
+
+        let rate_position = format_position.zip(size_position).and_then(|(f, s)| {
+            let intervals = &self.formats[f].sizes[s].intervals;
+            intervals.iter().position(|i| Some(*i) == mode.interval)
+        });
+
+        self.updating.set(true);
+        self.rate_row.set_selected(rate_position.unwrap_or(0) as u32);
+        self.updating.set(false);
+    }
+}
@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use adw::{prelude::*, ActionRow, ComboRow, PreferencesGroup};
+use gtk::{gio, Button, FileDialog, Popover, StringList};
+
+use crate::capture_settings::{CaptureSettings, StillFormat};
+
+/// Edits where and how captures are saved. Changes are stored right away.
+pub fn create_capture_settings_popover(settings: Rc<RefCell<CaptureSettings>>) -> Popover {
+    let folder_button = Button::builder()
+        .css_classes(["flat"])
+        .icon_name("folder-open-symbolic")
+        .tooltip_text("Choose folder")
+        .valign(gtk::Align::Center)
+        .build();
+
+    let folder_row = ActionRow::builder()
+        .css_classes(["property"])
+        .subtitle(settings.borrow().folder.display().to_string())
+        .title("Folder")
+        .build();
+    folder_row.add_suffix(&folder_button);
+
+    let labels: Vec<&str> = StillFormat::ALL.iter().map(|f| f.label()).collect();
+    let format_row = ComboRow::builder()
+        .model(&StringList::new(&labels))
+        .selected(StillFormat::ALL.iter().position(|f| *f == settings.borrow().still_format).unwrap_or(0) as u32)
+        .title("Still format")
+        .build();
+
+    let group = PreferencesGroup::builder()
+        .title("Captures")
+        .width_request(320)
+        .build();
+    group.add(&folder_row);
+    group.add(&format_row);
+
+    let popover = Popover::builder().child(&group).build();
+
+    let settings_for_format = settings.clone();
+    format_row.connect_selected_notify(move |row| {
+        if let Some(format) = StillFormat::ALL.get(row.selected() as usize) {
+            let mut settings = settings_for_format.borrow_mut();
+            settings.still_format = *format;
+            settings.save();
+        }
+    });
+
+    let popover_for_folder = popover.clone();
+    folder_button.connect_clicked(move |_| {
+        let dialog = FileDialog::builder()
+            .initial_folder(&gio::File::for_path(&settings.borrow().folder))
+            .title("Capture Folder")
+            .build();
+
+        let settings_for_choice = settings.clone();
+        let folder_row_for_choice = folder_row.clone();
+        let parent = popover_for_folder.root().and_downcast::<gtk::Window>();
+        popover_for_folder.popdown();
+
+        dialog.select_folder(parent.as_ref(), gio::Cancellable::NONE, move |result| {
+            // Dismissing the dialog is reported as error as well
+            let folder = match result.ok().and_then(|f| f.path()) {
+                Some(f) => f,
+                None => return,
+            };
+
+            folder_row_for_choice.set_subtitle(&folder.display().to_string());
+            let mut settings = settings_for_choice.borrow_mut();
+            settings.folder = folder;
+            settings.save();
+        });
+    });
+
+    popover
+}
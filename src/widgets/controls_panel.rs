@@ -1,10 +1,17 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use adw::{prelude::*, PreferencesGroup};
+use adw::{prelude::*, PreferencesGroup, PreferencesPage};
 use gtk::{Align, Label};
 use v4l::Device;
 
-use crate::{components::create_pref_row_with_box_and_label, controls::{BooleanControl, ButtonControl, ControlUi, IntegerControl, MenuControl}};
+use crate::{
+    components::{create_info_row, create_pref_row_with_box_and_label},
+    controls::{BooleanControl, ButtonControl, ControlUi, ExtensionUnitControl, IntegerControl, MenuControl},
+    device_usage::{find_device_holders, find_streaming_holders, format_holders, is_busy_error, DeviceHolder},
+    quirks::DeviceQuirks,
+    uvc_xu::{find_xu_controls, get_mapping_file},
+    widgets::CaptureModeGroup,
+};
 
 pub struct ControlsPanel {
     // WTF!? I just want to use this in a closure, re-used in event handlers of the controls
@@ -16,35 +23,67 @@ pub struct ControlsPanel {
     //  5. Box: In Heap, since...
     //  6. dyn ControlUi: ...it's a trait, which size is not known at build time
     control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>,
+    device_path: String,
+    page: Rc<PreferencesPage>,
     pref_groups: Vec<PreferencesGroup>,
+    quirks: DeviceQuirks,
+    capture_mode: Option<Rc<CaptureModeGroup>>,
+    set_preview_streaming: Rc<Box<dyn Fn(bool)>>,
+}
+
+/// Groups of the page for a device, with what they depend on.
+struct DeviceGroups {
+    groups: Vec<PreferencesGroup>,
+    quirks: DeviceQuirks,
+    capture_mode: Option<Rc<CaptureModeGroup>>,
 }
 
 impl ControlsPanel {
-    pub fn new(device_path: String) -> Self {
-        let device = Device::with_path(device_path);
+    /// `set_preview_streaming` stops and restarts the preview, e.g. to change
+    /// the capture mode.
+    pub fn new(device_path: String, set_preview_streaming: Rc<Box<dyn Fn(bool)>>) -> Self {
         let control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>> = Rc::new(RefCell::new(HashMap::new()));
 
-        let pref_groups = match device {
-            Ok(d) => create_controls_for_device(Rc::new(d), control_uis.clone()),
-            Err(e) => create_group_with_error(e.to_string()),
-        };
+        let device_groups = create_groups(&device_path, control_uis.clone(), set_preview_streaming.clone());
+
+        let page = PreferencesPage::builder()
+            .height_request(800)
+            .hexpand(false)
+            .vexpand(true)
+            .width_request(400)
+            .build();
+
+        for group in device_groups.groups.iter() {
+            page.add(group);
+        }
 
         ControlsPanel {
             control_uis,
-            pref_groups,
+            device_path,
+            page: Rc::new(page),
+            pref_groups: device_groups.groups,
+            quirks: device_groups.quirks,
+            capture_mode: device_groups.capture_mode,
+            set_preview_streaming,
         }
     }
 
     pub fn switch_device(&mut self, device_path: String) {
-        let device = Device::with_path(device_path);
-
-        let control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>> = Rc::new(RefCell::new(HashMap::new()));
         self.control_uis.as_ref().borrow_mut().clear();
 
-        let mut pref_groups = match device {
-            Ok(d) => create_controls_for_device(Rc::new(d), control_uis.clone()),
-            Err(e) => create_group_with_error(e.to_string()),
-        };
+        let device_groups = create_groups(&device_path, self.control_uis.clone(), self.set_preview_streaming.clone());
+        let mut pref_groups = device_groups.groups;
+        self.device_path = device_path;
+        self.quirks = device_groups.quirks;
+        self.capture_mode = device_groups.capture_mode;
+
+        for group in self.pref_groups.iter() {
+            self.page.remove(group);
+        }
+
+        for group in pref_groups.iter() {
+            self.page.add(group);
+        }
 
         self.pref_groups.clear();
         self.pref_groups.append(&mut pref_groups);
@@ -52,24 +91,146 @@ impl ControlsPanel {
 
     pub fn reset_defaults(&self) {
         let controls = self.control_uis.as_ref().borrow();
-        for control in controls.values() {
-            control.reset_default();
-        } 
+
+        let mut ids: Vec<u32> = controls.keys().copied().collect();
+        ids.sort();
+        ids.sort_by_key(|id| self.quirks.write_position(*id));
+
+        for id in ids {
+            controls[&id].reset_default();
+        }
+    }
+
+    /// Reads the capture mode and all values and states of the controls
+    /// again from the device.
+    pub fn refresh(&self) {
+        if let Some(capture_mode) = &self.capture_mode {
+            capture_mode.refresh();
+        }
+
+        match Device::with_path(&self.device_path) {
+            Ok(d) => update_controls(Rc::new(d), &self.quirks, self.control_uis.clone()),
+            Err(e) => eprintln!("Error opening device for refresh: {}", e),
+        };
     }
 
-    pub fn get_pref_groups(&self) -> Vec<PreferencesGroup> {
-        self.pref_groups.clone()
+    pub fn get_device_path(&self) -> String {
+        self.device_path.clone()
+    }
+
+    pub fn get_panel(&self) -> Rc<PreferencesPage> {
+        self.page.clone()
     }
 }
 
-fn create_controls_for_device(device: Rc<Device>, control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>) -> Vec<PreferencesGroup> {
+/// Creates the capture mode and the groups of controls for the device, with
+/// the quirks applied. Devices streaming to another application get an
+/// explanation on top.
+fn create_groups(
+    device_path: &str,
+    control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>,
+    set_preview_streaming: Rc<Box<dyn Fn(bool)>>,
+) -> DeviceGroups {
+    let device = match Device::with_path(device_path) {
+        Ok(d) => d,
+        Err(e) => {
+            let holders = match is_busy_error(&e) {
+                true => find_device_holders(device_path),
+                false => vec![],
+            };
+            let message = match holders.is_empty() {
+                true => e.to_string(),
+                false => format!("{}\nUsed by: {}", e, format_holders(&holders)),
+            };
+
+            return DeviceGroups {
+                groups: create_group_with_error(message),
+                quirks: DeviceQuirks::default(),
+                capture_mode: None,
+            };
+        }
+    };
+
+    let quirks = DeviceQuirks::for_device(device_path, &device);
+
+    let mut groups = vec![];
+
+    let holders = find_streaming_holders(&device, device_path);
+    let busy = !holders.is_empty();
+
+    if busy {
+        groups.push(create_busy_group(&holders));
+    }
+
+    let capture_mode = CaptureModeGroup::new(&device, device_path, set_preview_streaming);
+    if let Some(capture_mode) = &capture_mode {
+        if busy {
+            capture_mode.set_unavailable("Unavailable while another application streams");
+        }
+        groups.push(capture_mode.get_group().clone());
+    }
+
+    let device = Rc::new(device);
+    groups.append(&mut create_controls_for_device(device.clone(), &quirks, control_uis.clone()));
+
+    if let Some(group) = create_extension_unit_group(device_path, device, control_uis) {
+        groups.push(group);
+    }
+
+    return DeviceGroups {
+        groups,
+        quirks,
+        capture_mode,
+    };
+}
+
+/// Creates the group of mapped UVC extension unit controls, if the device has any.
+fn create_extension_unit_group(
+    device_path: &str,
+    device: Rc<Device>,
+    control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>,
+) -> Option<PreferencesGroup> {
+    let xu_controls = find_xu_controls(device_path, device.handle().fd());
+    if xu_controls.is_empty() {
+        return None;
+    }
+
+    let group = PreferencesGroup::builder()
+        .title("Extension Unit Controls")
+        .description(format!("Mapped in {}", get_mapping_file().display()))
+        .build();
+
+    for xu_control in xu_controls {
+        let id = xu_control.id();
+        let ctrl_ui: Rc<Box<dyn ControlUi>> = Rc::new(Box::new(ExtensionUnitControl::new(device.clone(), xu_control)));
+
+        group.add(ctrl_ui.preference_row().as_ref());
+        control_uis.as_ref().borrow_mut().insert(id, ctrl_ui);
+    }
+
+    return Some(group);
+}
+
+fn create_busy_group(holders: &[DeviceHolder]) -> PreferencesGroup {
+    let group = PreferencesGroup::builder()
+        .title("Camera in use")
+        .description("Another application is streaming from this camera. Controls can still be changed, but format changes and the preview are unavailable until it stops.")
+        .build();
+
+    group.add(&create_info_row("Used by".to_string(), format_holders(holders)));
+
+    return group;
+}
+
+fn create_controls_for_device(device: Rc<Device>, quirks: &DeviceQuirks, control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>) -> Vec<PreferencesGroup> {
     let mut groups = vec![];
 
     // Closure for the change handlers to update all controls
     let device_copy = device.clone();
+    let quirks_copy = quirks.clone();
     let control_uis_copy = control_uis.clone();
     let update_controls_fn: Rc<Box<dyn Fn() + 'static>> = Rc::new(Box::new(move || {
-        update_controls(device_copy.clone(), control_uis_copy.clone())
+        update_controls(device_copy.clone(), &quirks_copy, control_uis_copy.clone())
     }));
 
     // Create a group for each control class
@@ -82,15 +243,21 @@ fn create_controls_for_device(device: Rc<Device>, control_uis: Rc<RefCell<HashMa
         return groups;
     }
 
-    let ctrls = ctrls_result.unwrap();
+    let mut ctrls = ctrls_result.unwrap();
 
-    for ctrl_desc in ctrls.iter() {
+    for ctrl_desc in ctrls.iter_mut() {
         // Ignore disabled controls
         if ctrl_desc.flags.contains(v4l::control::Flags::DISABLED) {
             println!("Ignoring disabled control {}", ctrl_desc.name);
             continue;
         }
 
+        // Ignore controls hidden by quirks
+        if !quirks.apply(ctrl_desc) {
+            println!("Ignoring control {} hidden by quirks", ctrl_desc.name);
+            continue;
+        }
+
         if groups.is_empty() && ctrl_desc.typ != v4l::control::Type::CtrlClass {
             let new_group = PreferencesGroup::builder().title("Controls").build();
             groups.push(new_group);
@@ -187,7 +354,9 @@ fn create_controls_for_device(device: Rc<Device>, control_uis: Rc<RefCell<HashMa
 }
 
 
-fn update_controls(device: Rc<Device>, control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>) {
+/// Updates the controls with their descriptions, with the overrides of the
+/// quirks, as they were created.
+fn update_controls(device: Rc<Device>, quirks: &DeviceQuirks, control_uis: Rc<RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>>>) {
     let descriptions = match device.query_controls() {
         Ok(d) => d,
         Err(e) => {
@@ -198,15 +367,23 @@ fn update_controls(device: Rc<Device>, control_uis: Rc<RefCell<HashMap<u32, Rc<B
 
     let cuis_cell: &RefCell<HashMap<u32, Rc<Box<dyn ControlUi>>>> = control_uis.as_ref();
     let cuis_map = cuis_cell.borrow();
-    for desc in descriptions {
+    for mut desc in descriptions {
         let ctrl_ui = match cuis_map.get(&desc.id) {
             Some(c) => c,
             None => continue,
         };
 
+        if !quirks.apply(&mut desc) {
+            continue;
+        }
+
         ctrl_ui.update_state(&desc);
         ctrl_ui.update_value(&desc);
     }
+
+    for ctrl_ui in cuis_map.values() {
+        ctrl_ui.update_from_device();
+    }
 }
 
 fn create_group_with_error(msg: String) -> Vec<PreferencesGroup> {
@@ -0,0 +1,259 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use adw::{prelude::*, ActionRow, ComboRow, PreferencesGroup};
+use gtk::{
+    cairo, gdk, glib, Adjustment, Button, ContentFit, DrawingArea, MenuButton, Orientation, Overlay, Picture, Popover,
+    Scale, StringList,
+};
+
+use crate::{framing_guides::frame_rect, preview_frame::render_preview_frame};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ComparisonMode {
+    Off,
+    /// The reference next to the live preview
+    Split,
+    /// The reference over the live preview, up to the slider
+    Wipe,
+}
+
+impl ComparisonMode {
+    const ALL: [ComparisonMode; 3] = [ComparisonMode::Off, ComparisonMode::Split, ComparisonMode::Wipe];
+
+    fn label(&self) -> &'static str {
+        match self {
+            ComparisonMode::Off => "Off",
+            ComparisonMode::Split => "Side by side",
+            ComparisonMode::Wipe => "Wipe",
+        }
+    }
+}
+
+/// Compares the live preview with a reference frame, to judge control
+/// changes.
+pub struct FrameComparison {
+    widget: gtk::Box,
+    preview: gtk::Widget,
+    reference_picture: Picture,
+    wipe_area: DrawingArea,
+    wipe_scale: Scale,
+    mode: Cell<ComparisonMode>,
+    /// The frame in the format of cairo, for the wipe
+    reference: Rc<RefCell<Option<cairo::ImageSurface>>>,
+}
+
+impl FrameComparison {
+    /// The live widget shows the preview, possibly with overlays.
+    pub fn new(preview: gtk::Widget, live: gtk::Widget) -> Rc<Self> {
+        let reference: Rc<RefCell<Option<cairo::ImageSurface>>> = Rc::new(RefCell::new(None));
+
+        let reference_picture = Picture::builder()
+            .content_fit(ContentFit::Contain)
+            .hexpand(true)
+            .tooltip_text("Reference frame")
+            .vexpand(true)
+            .visible(false)
+            .build();
+
+        let adjustment = Adjustment::builder()
+            .lower(0.0)
+            .upper(1.0)
+            .step_increment(0.01)
+            .value(0.5)
+            .build();
+
+        let wipe_scale = Scale::builder()
+            .adjustment(&adjustment)
+            .draw_value(false)
+            .margin_bottom(12)
+            .margin_end(24)
+            .margin_start(24)
+            .orientation(Orientation::Horizontal)
+            .tooltip_text("Reference left, live right")
+            .valign(gtk::Align::End)
+            .visible(false)
+            .build();
+
+        let wipe_area = DrawingArea::builder()
+            .can_target(false)
+            .visible(false)
+            .build();
+
+        let reference_for_draw = reference.clone();
+        let scale_for_draw = wipe_scale.clone();
+        wipe_area.set_draw_func(move |_, cr, width, height| {
+            if let Some(surface) = reference_for_draw.borrow().as_ref() {
+                draw_wipe(cr, surface, width as f64, height as f64, scale_for_draw.value());
+            }
+        });
+
+        let wipe_area_for_scale = wipe_area.clone();
+        wipe_scale.connect_value_changed(move |_| {
+            wipe_area_for_scale.queue_draw();
+        });
+
+        let live_overlay = Overlay::builder()
+            .child(&live)
+            .hexpand(true)
+            .build();
+        live_overlay.add_overlay(&wipe_area);
+        live_overlay.add_overlay(&wipe_scale);
+
+        let widget = gtk::Box::builder()
+            .hexpand(true)
+            .homogeneous(true)
+            .orientation(Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        widget.append(&reference_picture);
+        widget.append(&live_overlay);
+
+        Rc::new(FrameComparison {
+            widget,
+            preview,
+            reference_picture,
+            wipe_area,
+            wipe_scale,
+            mode: Cell::new(ComparisonMode::Off),
+            reference,
+        })
+    }
+
+    pub fn get_widget(&self) -> &gtk::Box {
+        &self.widget
+    }
+
+    /// A button with a popover to capture the reference and choose the
+    /// comparison.
+    pub fn create_menu_button(self: &Rc<Self>) -> MenuButton {
+        let capture_button = Button::builder()
+            .label("Capture")
+            .valign(gtk::Align::Center)
+            .build();
+
+        let reference_row = ActionRow::builder()
+            .css_classes(["property"])
+            .subtitle("None")
+            .title("Reference frame")
+            .build();
+        reference_row.add_suffix(&capture_button);
+
+        let labels: Vec<&str> = ComparisonMode::ALL.iter().map(|m| m.label()).collect();
+        let mode_row = ComboRow::builder()
+            .model(&StringList::new(&labels))
+            .sensitive(false)
+            .title("Comparison")
+            .build();
+
+        let group = PreferencesGroup::builder()
+            .title("Compare")
+            .width_request(320)
+            .build();
+        group.add(&reference_row);
+        group.add(&mode_row);
+
+        let weak_comparison: Weak<FrameComparison> = Rc::downgrade(self);
+        let mode_row_for_capture = mode_row.clone();
+        capture_button.connect_clicked(move |_| {
+            let comparison = match weak_comparison.upgrade() {
+                Some(c) => c,
+                None => return,
+            };
+
+            match comparison.capture_reference() {
+                Ok(()) => {
+                    let captured = glib::DateTime::now_local()
+                        .and_then(|now| now.format("%X"))
+                        .map(|t| format!("Captured at {}", t))
+                        .unwrap_or_else(|_| "Captured".to_string());
+                    reference_row.set_subtitle(&captured);
+
+                    mode_row_for_capture.set_sensitive(true);
+                    if comparison.mode.get() == ComparisonMode::Off {
+                        // Shows the new reference right away
+                        let wipe = ComparisonMode::ALL.iter().position(|m| *m == ComparisonMode::Wipe);
+                        mode_row_for_capture.set_selected(wipe.unwrap_or(0) as u32);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error capturing reference frame: {}", e);
+                    reference_row.set_subtitle(&e);
+                }
+            }
+        });
+
+        let weak_comparison: Weak<FrameComparison> = Rc::downgrade(self);
+        mode_row.connect_selected_notify(move |row| {
+            if let Some(comparison) = weak_comparison.upgrade() {
+                let mode = ComparisonMode::ALL.get(row.selected() as usize);
+                comparison.set_mode(mode.copied().unwrap_or(ComparisonMode::Off));
+            }
+        });
+
+        MenuButton::builder()
+            .icon_name("object-flip-horizontal-symbolic")
+            .popover(&Popover::builder().child(&group).build())
+            .tooltip_text("Compare with a reference frame")
+            .build()
+    }
+
+    fn capture_reference(&self) -> Result<(), String> {
+        let texture = render_preview_frame(&self.preview, None)?;
+        let surface = texture_to_surface(&texture)?;
+
+        self.reference_picture.set_paintable(Some(&texture));
+        self.reference.replace(Some(surface));
+        self.wipe_area.queue_draw();
+
+        Ok(())
+    }
+
+    fn set_mode(&self, mode: ComparisonMode) {
+        self.mode.set(mode);
+
+        self.reference_picture.set_visible(mode == ComparisonMode::Split);
+        self.wipe_area.set_visible(mode == ComparisonMode::Wipe);
+        self.wipe_scale.set_visible(mode == ComparisonMode::Wipe);
+    }
+}
+
+/// The default format of downloads is the same as the one of cairo.
+fn texture_to_surface(texture: &gdk::Texture) -> Result<cairo::ImageSurface, String> {
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, texture.width(), texture.height())
+        .map_err(|e| format!("Error creating surface: {}", e))?;
+    let stride = surface.stride() as usize;
+
+    {
+        let mut data = surface.data().map_err(|e| format!("Error accessing surface: {}", e))?;
+        texture.download(&mut data, stride);
+    }
+
+    Ok(surface)
+}
+
+/// Draws the reference over the frame, which is fit into the preview, left of
+/// the position, with a line at the position.
+fn draw_wipe(cr: &cairo::Context, surface: &cairo::ImageSurface, width: f64, height: f64, position: f64) {
+    let (surface_width, surface_height) = (surface.width() as f64, surface.height() as f64);
+    let frame = match frame_rect(width, height, surface_width, surface_height) {
+        Some(f) => f,
+        None => return,
+    };
+    let x = frame.x + frame.width * position;
+
+    let _ = cr.save();
+    cr.rectangle(frame.x, frame.y, x - frame.x, frame.height);
+    cr.clip();
+    cr.translate(frame.x, frame.y);
+    cr.scale(frame.width / surface_width, frame.height / surface_height);
+    let _ = cr.set_source_surface(surface, 0.0, 0.0);
+    let _ = cr.paint();
+    let _ = cr.restore();
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+    cr.set_line_width(2.0);
+    cr.move_to(x, frame.y);
+    cr.line_to(x, frame.y + frame.height);
+    let _ = cr.stroke();
+}
@@ -0,0 +1,317 @@
+use std::{cell::RefCell, collections::HashMap, f64::consts::PI, rc::{Rc, Weak}};
+
+use adw::{prelude::*, Dialog, HeaderBar, PreferencesGroup, PreferencesPage, StatusPage, SwitchRow, ToolbarView};
+use gtk::{cairo, DrawingArea, GestureClick, ScrolledWindow};
+
+use crate::{
+    components::create_info_row,
+    media_controller::{
+        entity_function_label, find_media_device, layout_entities, read_topology, setup_link, MediaLink,
+        MediaTopology,
+    },
+};
+
+const BOX_WIDTH: f64 = 180.0;
+const BOX_HEIGHT: f64 = 56.0;
+const COLUMN_WIDTH: f64 = 260.0;
+const ROW_HEIGHT: f64 = 84.0;
+const MARGIN: f64 = 16.0;
+// Radius of the handle to toggle mutable links
+const HANDLE_RADIUS: f64 = 6.0;
+
+struct TopologyView {
+    topology: RefCell<MediaTopology>,
+    area: DrawingArea,
+    page: PreferencesPage,
+    links_group: RefCell<Option<PreferencesGroup>>,
+}
+
+/// Shows the media controller graph of the camera, with its entities, links
+/// and device-nodes. Mutable links can be enabled and disabled.
+pub fn present_media_topology_dialog(parent: &impl IsA<gtk::Widget>, device_path: String) {
+    let toolbar_view = ToolbarView::new();
+    toolbar_view.add_top_bar(&HeaderBar::new());
+
+    let view = find_media_device(&device_path).map(TopologyView::new);
+    match &view {
+        Some(view) => toolbar_view.set_content(Some(&view.page)),
+        None => {
+            let status_page = StatusPage::builder()
+                .description(format!("{} is not part of a media controller device", device_path))
+                .icon_name("dialog-information-symbolic")
+                .title("No Media Topology")
+                .build();
+            toolbar_view.set_content(Some(&status_page));
+        }
+    };
+
+    let dialog = Dialog::builder()
+        .child(&toolbar_view)
+        .content_height(700)
+        .content_width(900)
+        .title("Media Topology")
+        .build();
+
+    // The widgets of the view refer to it weakly, so the dialog keeps it
+    let view = RefCell::new(view);
+    dialog.connect_closed(move |_| {
+        view.take();
+    });
+
+    dialog.present(Some(parent));
+}
+
+impl TopologyView {
+    fn new(topology: MediaTopology) -> Rc<Self> {
+        let page = PreferencesPage::new();
+
+        let info_group = PreferencesGroup::builder().title("Media Device").build();
+        info_group.add(&create_info_row("Device".to_string(), topology.media_path.clone()));
+        info_group.add(&create_info_row("Driver".to_string(), topology.driver.clone()));
+        info_group.add(&create_info_row("Model".to_string(), topology.model.clone()));
+        info_group.add(&create_info_row("Bus".to_string(), topology.bus_info.clone()));
+        page.add(&info_group);
+
+        let area = DrawingArea::new();
+        let graph_window = ScrolledWindow::builder()
+            .child(&area)
+            .min_content_height(300)
+            .build();
+
+        let graph_group = PreferencesGroup::builder()
+            .title("Graph")
+            .description("Click the handle of a link to enable or disable it")
+            .build();
+        graph_group.add(&graph_window);
+        page.add(&graph_group);
+
+        let view = Rc::new(TopologyView {
+            topology: RefCell::new(topology),
+            area,
+            page,
+            links_group: RefCell::new(None),
+        });
+
+        let view_for_draw: Weak<TopologyView> = Rc::downgrade(&view);
+        view.area.set_draw_func(move |area, cr, _, _| {
+            let view = match view_for_draw.upgrade() {
+                Some(v) => v,
+                None => return,
+            };
+
+            let color = area.color();
+            cr.set_source_rgba(
+                color.red() as f64,
+                color.green() as f64,
+                color.blue() as f64,
+                color.alpha() as f64,
+            );
+
+            if let Err(e) = draw_topology(cr, &view.topology.borrow()) {
+                eprintln!("Error drawing media topology: {}", e);
+            }
+        });
+
+        let click = GestureClick::new();
+        let view_for_click: Weak<TopologyView> = Rc::downgrade(&view);
+        click.connect_pressed(move |_, _, x, y| {
+            let view = match view_for_click.upgrade() {
+                Some(v) => v,
+                None => return,
+            };
+
+            let link = {
+                let topology = view.topology.borrow();
+                find_link_at(&topology, x, y)
+            };
+
+            if let Some(link) = link {
+                view.set_link_enabled(&link, !link.is_enabled());
+            }
+        });
+        view.area.add_controller(click);
+
+        view.update();
+
+        view
+    }
+
+    fn set_link_enabled(self: &Rc<Self>, link: &MediaLink, enabled: bool) {
+        if !link.is_mutable() {
+            return;
+        }
+
+        if let Err(e) = setup_link(&self.topology.borrow(), link, enabled) {
+            eprintln!("Error changing link {}: {}", link.id, e);
+        }
+
+        // Read again, as the driver may change other links as well
+        let media_path = self.topology.borrow().media_path.clone();
+        match read_topology(&media_path) {
+            Ok(t) => {
+                self.topology.replace(t);
+            }
+            Err(e) => eprintln!("Error reading topology of {}: {}", media_path, e),
+        };
+
+        self.update();
+    }
+
+    /// Sizes and redraws the graph and lists the links.
+    fn update(self: &Rc<Self>) {
+        let topology = self.topology.borrow();
+
+        let positions = layout_entities(&topology);
+        let columns = positions.values().map(|(c, _)| c + 1).max().unwrap_or(0);
+        let rows = positions.values().map(|(_, r)| r + 1).max().unwrap_or(0);
+
+        self.area.set_content_width((2.0 * MARGIN + (columns as f64 - 1.0).max(0.0) * COLUMN_WIDTH + BOX_WIDTH) as i32);
+        self.area.set_content_height((2.0 * MARGIN + (rows as f64 - 1.0).max(0.0) * ROW_HEIGHT + BOX_HEIGHT) as i32);
+        self.area.queue_draw();
+
+        if let Some(group) = self.links_group.take() {
+            self.page.remove(&group);
+        }
+
+        let group = PreferencesGroup::builder().title("Links").build();
+
+        for link in topology.data_links() {
+            let subtitle = match link.is_mutable() {
+                true => "",
+                false => "Immutable",
+            };
+
+            let row = SwitchRow::builder()
+                .active(link.is_enabled())
+                .sensitive(link.is_mutable())
+                .subtitle(subtitle)
+                .title(topology.describe_link(link))
+                .build();
+
+            let view: Weak<TopologyView> = Rc::downgrade(self);
+            let link = link.clone();
+            row.connect_active_notify(move |row| {
+                if row.is_active() != link.is_enabled() {
+                    // Changing the rows while they emit signals is deferred
+                    let view = view.clone();
+                    let link = link.clone();
+                    let enabled = row.is_active();
+                    gtk::glib::idle_add_local_once(move || {
+                        if let Some(view) = view.upgrade() {
+                            view.set_link_enabled(&link, enabled);
+                        }
+                    });
+                }
+            });
+
+            group.add(&row);
+        }
+
+        self.page.add(&group);
+        self.links_group.replace(Some(group));
+    }
+}
+
+fn get_box_origin(position: (usize, usize)) -> (f64, f64) {
+    (
+        MARGIN + position.0 as f64 * COLUMN_WIDTH,
+        MARGIN + position.1 as f64 * ROW_HEIGHT,
+    )
+}
+
+/// Start and end of the drawn link, from the right of the source to the left
+/// of the sink.
+fn get_link_ends(
+    topology: &MediaTopology,
+    positions: &HashMap<u32, (usize, usize)>,
+    link: &MediaLink,
+) -> Option<((f64, f64), (f64, f64))> {
+    let (source, sink) = topology.link_entities(link)?;
+    let (source_x, source_y) = get_box_origin(*positions.get(&source.id)?);
+    let (sink_x, sink_y) = get_box_origin(*positions.get(&sink.id)?);
+
+    Some((
+        (source_x + BOX_WIDTH, source_y + BOX_HEIGHT / 2.0),
+        (sink_x, sink_y + BOX_HEIGHT / 2.0),
+    ))
+}
+
+fn find_link_at(topology: &MediaTopology, x: f64, y: f64) -> Option<MediaLink> {
+    let positions = layout_entities(topology);
+
+    topology
+        .data_links()
+        .into_iter()
+        .filter(|l| l.is_mutable())
+        .find(|l| match get_link_ends(topology, &positions, l) {
+            Some((start, end)) => {
+                // The middle of the symmetric curve lies between its ends
+                let (mid_x, mid_y) = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+                (x - mid_x).hypot(y - mid_y) <= HANDLE_RADIUS * 2.0
+            }
+            None => false,
+        })
+        .cloned()
+}
+
+fn draw_topology(cr: &cairo::Context, topology: &MediaTopology) -> Result<(), cairo::Error> {
+    let positions = layout_entities(topology);
+
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+    cr.set_line_width(1.5);
+
+    for entity in &topology.entities {
+        let (x, y) = match positions.get(&entity.id) {
+            Some(p) => get_box_origin(*p),
+            None => continue,
+        };
+
+        cr.rectangle(x, y, BOX_WIDTH, BOX_HEIGHT);
+        cr.stroke()?;
+
+        cr.set_font_size(12.0);
+        cr.move_to(x + 8.0, y + 18.0);
+        cr.show_text(&entity.name)?;
+
+        let devnodes = topology.devnodes_of_entity(entity.id);
+        let details = match devnodes.is_empty() {
+            true => entity_function_label(entity.function),
+            false => devnodes.join(", "),
+        };
+
+        cr.set_font_size(10.0);
+        cr.move_to(x + 8.0, y + 38.0);
+        cr.show_text(&details)?;
+    }
+
+    for link in topology.data_links() {
+        let (start, end) = match get_link_ends(topology, &positions, link) {
+            Some(e) => e,
+            None => continue,
+        };
+
+        match link.is_enabled() {
+            true => cr.set_dash(&[], 0.0),
+            false => cr.set_dash(&[4.0, 4.0], 0.0),
+        };
+
+        let offset = ((end.0 - start.0) / 2.0).abs().max(24.0);
+        cr.move_to(start.0, start.1);
+        cr.curve_to(start.0 + offset, start.1, end.0 - offset, end.1, end.0, end.1);
+        cr.stroke()?;
+
+        if link.is_mutable() {
+            cr.set_dash(&[], 0.0);
+            let (mid_x, mid_y) = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+            cr.arc(mid_x, mid_y, HANDLE_RADIUS, 0.0, 2.0 * PI);
+
+            match link.is_enabled() {
+                true => cr.fill()?,
+                false => cr.stroke()?,
+            };
+        }
+    }
+
+    cr.set_dash(&[], 0.0);
+    Ok(())
+}
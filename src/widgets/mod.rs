@@ -1,5 +1,41 @@
 mod caps_panel;
 pub use self::caps_panel::CapsPanel;
 
+mod capture_mode_group;
+pub use self::capture_mode_group::CaptureModeGroup;
+
+mod capture_settings_popover;
+pub use self::capture_settings_popover::create_capture_settings_popover;
+
+mod camera_selector;
+pub use self::camera_selector::CameraSelector;
+
 mod controls_panel;
 pub use self::controls_panel::ControlsPanel;
+
+mod frame_comparison;
+pub use self::frame_comparison::FrameComparison;
+
+mod media_topology_dialog;
+pub use self::media_topology_dialog::present_media_topology_dialog;
+
+mod native_preview;
+pub use self::native_preview::NativePreview;
+
+mod preview_overlay;
+pub use self::preview_overlay::PreviewOverlay;
+
+mod profile_diff_dialog;
+pub use self::profile_diff_dialog::present_profile_diff_dialog;
+
+mod recording_indicator;
+pub use self::recording_indicator::RecordingIndicator;
+
+mod save_profile_dialog;
+pub use self::save_profile_dialog::present_save_profile_dialog;
+
+mod scopes_panel;
+pub use self::scopes_panel::ScopesPanel;
+
+mod statistics_panel;
+pub use self::statistics_panel::StatisticsPanel;
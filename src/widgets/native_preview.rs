@@ -0,0 +1,220 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use adw::StatusPage;
+use gtk::{gdk, glib, prelude::*, subclass::prelude::*, Picture, Stack};
+
+use crate::native_stream::{LatestFrame, NativeStream};
+
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+mod imp {
+    use super::*;
+
+    /// Shows the latest texture, keeping the same paintable for all frames.
+    #[derive(Default)]
+    pub struct FramePaintable {
+        pub texture: RefCell<Option<gdk::Texture>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for FramePaintable {
+        const NAME: &'static str = "FramePaintable";
+        type Type = super::FramePaintable;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for FramePaintable {}
+
+    impl PaintableImpl for FramePaintable {
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            if let Some(texture) = self.texture.borrow().as_ref() {
+                texture.snapshot(snapshot, width, height);
+            }
+        }
+
+        fn intrinsic_width(&self) -> i32 {
+            self.texture.borrow().as_ref().map_or(0, |t| t.width())
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.texture.borrow().as_ref().map_or(0, |t| t.height())
+        }
+
+        fn current_image(&self) -> gdk::Paintable {
+            match self.texture.borrow().as_ref() {
+                Some(t) => t.clone().upcast(),
+                None => gdk::Paintable::new_empty(0, 0),
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct FramePaintable(ObjectSubclass<imp::FramePaintable>)
+        @implements gdk::Paintable;
+}
+
+impl FramePaintable {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn set_texture(&self, texture: Option<gdk::Texture>) {
+        let size = texture.as_ref().map(|t| (t.width(), t.height()));
+        let old_size = self.imp().texture.replace(texture).map(|t| (t.width(), t.height()));
+
+        if size != old_size {
+            self.invalidate_size();
+        }
+        self.invalidate_contents();
+    }
+}
+
+/// Preview streaming directly from the V4L2 device, for when PipeWire is
+/// missing or misbehaving. Streams only after `start_stream`.
+pub struct NativePreview {
+    widget: Stack,
+    paintable: FramePaintable,
+    device_path: RefCell<String>,
+    latest: LatestFrame,
+    // Kept after stopping, until the next start, to tell when it is released
+    stream: RefCell<Option<NativeStream>>,
+    streaming: Cell<bool>,
+}
+
+impl NativePreview {
+    pub fn new(device_path: &str) -> Rc<Self> {
+        let paintable = FramePaintable::new();
+
+        let picture = Picture::builder()
+            .hexpand(true)
+            .paintable(&paintable)
+            .vexpand(true)
+            .build();
+
+        let status_page = StatusPage::builder()
+            .icon_name("camera-disabled-symbolic")
+            .title("Preview stopped")
+            .build();
+
+        let widget = Stack::builder()
+            .hexpand(true)
+            .build();
+        widget.add_named(&picture, Some("picture"));
+        widget.add_named(&status_page, Some("error"));
+
+        let latest: LatestFrame = Arc::new(Mutex::new(None));
+
+        // Frames are taken once per frame of the window, while shown
+        let latest_for_tick = latest.clone();
+        let paintable_for_tick = paintable.clone();
+        let widget_for_tick = widget.clone();
+        picture.add_tick_callback(move |_, _| {
+            let frame = latest_for_tick.lock().ok().and_then(|mut f| f.take());
+
+            match frame {
+                Some(Ok(image)) => {
+                    let bytes = glib::Bytes::from_owned(image.data);
+                    let texture = gdk::MemoryTexture::new(
+                        image.width as i32,
+                        image.height as i32,
+                        gdk::MemoryFormat::R8g8b8a8,
+                        &bytes,
+                        image.stride(),
+                    );
+                    paintable_for_tick.set_texture(Some(texture.upcast()));
+                }
+                Some(Err(e)) => {
+                    status_page.set_description(Some(&e));
+                    widget_for_tick.set_visible_child_name("error");
+                }
+                None => {}
+            }
+
+            glib::ControlFlow::Continue
+        });
+
+        Rc::new(NativePreview {
+            widget,
+            paintable,
+            device_path: RefCell::new(device_path.to_string()),
+            latest,
+            stream: RefCell::new(None),
+            streaming: Cell::new(false),
+        })
+    }
+
+    pub fn get_widget(&self) -> &Stack {
+        &self.widget
+    }
+
+    /// The frame shown, if any.
+    pub fn current_frame(&self) -> Option<gdk::Texture> {
+        self.paintable.imp().texture.borrow().clone()
+    }
+
+    /// Streams from the device, if streaming.
+    pub fn set_device(&self, device_path: &str) {
+        let streaming = self.streaming.get();
+        self.stop_stream();
+        self.device_path.replace(device_path.to_string());
+        if streaming {
+            self.start_stream();
+        }
+    }
+
+    pub fn start_stream(&self) {
+        self.stop_stream();
+        self.streaming.set(true);
+
+        if let Ok(mut frame) = self.latest.lock() {
+            *frame = None;
+        }
+        self.paintable.set_texture(None);
+        self.widget.set_visible_child_name("picture");
+
+        // No camera connected yet
+        if self.device_path.borrow().is_empty() {
+            return;
+        }
+
+        let stream = NativeStream::start(&self.device_path.borrow(), self.latest.clone());
+        self.stream.replace(Some(stream));
+    }
+
+    /// Returns right away, the device is released shortly after, see
+    /// `when_released`.
+    pub fn stop_stream(&self) {
+        self.streaming.set(false);
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            stream.stop();
+        }
+    }
+
+    /// Calls `on_released`, once the stopped stream released the device, e.g.
+    /// to start another stream from it. Not called, if the stream is started
+    /// again meanwhile.
+    pub fn when_released(self: &Rc<Self>, on_released: impl FnOnce() + 'static) {
+        let mut on_released = Some(on_released);
+        let weak_preview: Weak<NativePreview> = Rc::downgrade(self);
+        glib::timeout_add_local(RELEASE_POLL_INTERVAL, move || {
+            let preview = match weak_preview.upgrade() {
+                Some(p) if !p.streaming.get() => p,
+                _ => return glib::ControlFlow::Break,
+            };
+
+            let released = preview.stream.borrow().as_ref().map_or(true, |s| s.is_released());
+            if !released {
+                return glib::ControlFlow::Continue;
+            }
+
+            if let Some(on_released) = on_released.take() {
+                on_released();
+            }
+            glib::ControlFlow::Break
+        });
+    }
+}
@@ -0,0 +1,433 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use adw::{prelude::*, ComboRow, PreferencesGroup, SpinRow, SwitchRow};
+use gtk::{cairo, DrawingArea, MenuButton, Orientation, Overlay, Popover, StringList};
+
+use crate::{
+    framing_guides::{frame_rect, AspectRatio, Rect, ACTION_SAFE, TITLE_SAFE},
+    preview_frame::FrameSampler,
+    preview_overlays::{luma_plane, peaking_mask, zebra_mask},
+    scopes::RgbFrame,
+};
+
+const DEFAULT_ZEBRA_PERCENT: f64 = 95.0;
+const ZEBRA_STRIPE_WIDTH: f64 = 6.0;
+const CENTER_CROSS_SIZE: f64 = 24.0;
+
+struct OverlayMasks {
+    width: usize,
+    height: usize,
+    zebra: Option<Vec<u8>>,
+    peaking: Option<Vec<u8>>,
+}
+
+/// Guides drawn over the frame, without changing the stream.
+#[derive(Clone, Copy, Default)]
+struct FramingGuides {
+    thirds: bool,
+    center: bool,
+    /// Darkens the frame outside of the aspect ratio
+    aspect: Option<AspectRatio>,
+    safe_areas: bool,
+}
+
+impl FramingGuides {
+    fn any(&self) -> bool {
+        self.thirds || self.center || self.aspect.is_some() || self.safe_areas
+    }
+}
+
+/// Draws zebra stripes, focus peaking and framing guides over the preview.
+pub struct PreviewOverlay {
+    overlay: Overlay,
+    sampler: Rc<FrameSampler>,
+    area: DrawingArea,
+    zebra: Cell<bool>,
+    /// Luma in 0..=255, from which zebra stripes are shown
+    zebra_threshold: Cell<u8>,
+    peaking: Cell<bool>,
+    guides: Rc<Cell<FramingGuides>>,
+    masks: Rc<RefCell<Option<OverlayMasks>>>,
+    subscription: RefCell<Option<u32>>,
+}
+
+impl PreviewOverlay {
+    /// Shows the overlays over `preview`, whose frames are analyzed with
+    /// `sampler`.
+    pub fn new(preview: gtk::Widget, sampler: Rc<FrameSampler>) -> Rc<Self> {
+        let masks: Rc<RefCell<Option<OverlayMasks>>> = Rc::new(RefCell::new(None));
+
+        let area = DrawingArea::builder()
+            .can_target(false)
+            .build();
+
+        let guides: Rc<Cell<FramingGuides>> = Rc::new(Cell::new(FramingGuides::default()));
+
+        let masks_for_draw = masks.clone();
+        let guides_for_draw = guides.clone();
+        let sampler_for_draw = sampler.clone();
+        area.set_draw_func(move |_, cr, width, height| {
+            let masks = masks_for_draw.borrow();
+
+            // The size of the stream, or of the masks, while it is unknown
+            let frame_size = sampler_for_draw
+                .paintable()
+                .map(|p| (p.intrinsic_width() as f64, p.intrinsic_height() as f64))
+                .filter(|(w, h)| *w > 0.0 && *h > 0.0)
+                .or_else(|| masks.as_ref().map(|m| (m.width as f64, m.height as f64)));
+            let frame = match frame_size.and_then(|(w, h)| frame_rect(width as f64, height as f64, w, h)) {
+                Some(f) => f,
+                None => return,
+            };
+
+            if let Some(masks) = masks.as_ref() {
+                draw_masks(cr, masks, &frame);
+            }
+            draw_guides(cr, &guides_for_draw.get(), &frame);
+        });
+
+        let overlay = Overlay::builder()
+            .child(&preview)
+            .hexpand(true)
+            .build();
+        overlay.add_overlay(&area);
+
+        Rc::new(PreviewOverlay {
+            overlay,
+            sampler,
+            area,
+            zebra: Cell::new(false),
+            zebra_threshold: Cell::new(percent_to_luma(DEFAULT_ZEBRA_PERCENT)),
+            peaking: Cell::new(false),
+            guides,
+            masks,
+            subscription: RefCell::new(None),
+        })
+    }
+
+    pub fn get_widget(&self) -> &Overlay {
+        &self.overlay
+    }
+
+    /// A button with a popover to toggle the overlays.
+    pub fn create_menu_button(self: &Rc<Self>) -> MenuButton {
+        let zebra_row = SwitchRow::builder()
+            .subtitle("Marks bright areas")
+            .title("Zebra stripes")
+            .build();
+
+        let threshold_row = SpinRow::with_range(50.0, 100.0, 1.0);
+        threshold_row.set_title("Zebra threshold");
+        threshold_row.set_subtitle("Luma in percent");
+        threshold_row.set_value(DEFAULT_ZEBRA_PERCENT);
+
+        let peaking_row = SwitchRow::builder()
+            .subtitle("Marks sharp edges")
+            .title("Focus peaking")
+            .build();
+
+        let group = PreferencesGroup::builder()
+            .title("Overlays")
+            .width_request(320)
+            .build();
+        group.add(&zebra_row);
+        group.add(&threshold_row);
+        group.add(&peaking_row);
+
+        let thirds_row = SwitchRow::builder()
+            .title("Rule of thirds")
+            .build();
+
+        let center_row = SwitchRow::builder()
+            .title("Center cross")
+            .build();
+
+        let aspect_labels: Vec<&str> = std::iter::once("None")
+            .chain(AspectRatio::ALL.iter().map(|a| a.label()))
+            .collect();
+        let aspect_row = ComboRow::builder()
+            .model(&StringList::new(&aspect_labels))
+            .subtitle("Darkens the frame outside")
+            .title("Aspect ratio")
+            .build();
+
+        let safe_areas_row = SwitchRow::builder()
+            .subtitle(format!(
+                "Action {:.0} %, title {:.0} %",
+                ACTION_SAFE * 100.0,
+                TITLE_SAFE * 100.0
+            ))
+            .title("Safe areas")
+            .build();
+
+        let guides_group = PreferencesGroup::builder()
+            .title("Guides")
+            .width_request(320)
+            .build();
+        guides_group.add(&thirds_row);
+        guides_group.add(&center_row);
+        guides_group.add(&aspect_row);
+        guides_group.add(&safe_areas_row);
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        thirds_row.connect_active_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.update_guides(|g| g.thirds = row.is_active());
+            }
+        });
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        center_row.connect_active_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.update_guides(|g| g.center = row.is_active());
+            }
+        });
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        aspect_row.connect_selected_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                // The first item is for none
+                let aspect = (row.selected() as usize)
+                    .checked_sub(1)
+                    .and_then(|i| AspectRatio::ALL.get(i).copied());
+                overlay.update_guides(|g| g.aspect = aspect);
+            }
+        });
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        safe_areas_row.connect_active_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.update_guides(|g| g.safe_areas = row.is_active());
+            }
+        });
+
+        let content = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(12)
+            .build();
+        content.append(&group);
+        content.append(&guides_group);
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        zebra_row.connect_active_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.zebra.set(row.is_active());
+                overlay.update_subscription();
+            }
+        });
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        threshold_row.connect_value_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.zebra_threshold.set(percent_to_luma(row.value()));
+            }
+        });
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        peaking_row.connect_active_notify(move |row| {
+            if let Some(overlay) = weak_overlay.upgrade() {
+                overlay.peaking.set(row.is_active());
+                overlay.update_subscription();
+            }
+        });
+
+        MenuButton::builder()
+            .icon_name("view-reveal-symbolic")
+            .popover(&Popover::builder().child(&content).build())
+            .tooltip_text("Preview overlays")
+            .build()
+    }
+
+    fn update_guides(self: &Rc<Self>, change: impl FnOnce(&mut FramingGuides)) {
+        let mut guides = self.guides.get();
+        change(&mut guides);
+        self.guides.set(guides);
+
+        self.update_subscription();
+    }
+
+    /// Analyzes frames, while an overlay is enabled. Guides are redrawn on
+    /// each sample, to follow changes of the frame size.
+    fn update_subscription(self: &Rc<Self>) {
+        if let Some(id) = self.subscription.take() {
+            self.sampler.unsubscribe(id);
+        }
+
+        let analyzed = self.zebra.get() || self.peaking.get();
+        if !analyzed {
+            self.masks.replace(None);
+        }
+        self.area.queue_draw();
+
+        let weak_overlay: Weak<PreviewOverlay> = Rc::downgrade(self);
+        let id = if analyzed {
+            self.sampler.subscribe_frames(move |frame| {
+                if let Some(overlay) = weak_overlay.upgrade() {
+                    overlay.update_masks(frame.ok());
+                }
+            })
+        } else if self.guides.get().any() {
+            self.sampler.subscribe_ticks(move || {
+                if let Some(overlay) = weak_overlay.upgrade() {
+                    overlay.area.queue_draw();
+                }
+            })
+        } else {
+            return;
+        };
+        self.subscription.replace(Some(id));
+    }
+
+    /// Computes the enabled masks of the frame. Without a frame, e.g. while
+    /// the stream restarts, the masks are cleared.
+    fn update_masks(&self, frame: Option<&RgbFrame>) {
+        let frame = match frame {
+            Some(f) => f,
+            None => {
+                self.masks.replace(None);
+                self.area.queue_draw();
+                return;
+            }
+        };
+        let plane = luma_plane(frame);
+
+        self.masks.replace(Some(OverlayMasks {
+            width: frame.width,
+            height: frame.height,
+            zebra: self.zebra.get().then(|| zebra_mask(&plane, self.zebra_threshold.get())),
+            peaking: self.peaking.get().then(|| peaking_mask(&plane, frame.width, frame.height)),
+        }));
+        self.area.queue_draw();
+    }
+}
+
+fn percent_to_luma(percent: f64) -> u8 {
+    (percent / 100.0 * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Draws the masks over the frame, where it is shown in the preview.
+fn draw_masks(cr: &cairo::Context, masks: &OverlayMasks, frame: &Rect) {
+    let scale = frame.width / masks.width as f64;
+    let (offset_x, offset_y) = (frame.x, frame.y);
+
+    if let Some(zebra) = &masks.zebra {
+        if let Some(surface) = create_mask_surface(zebra, masks.width, masks.height) {
+            let _ = cr.save();
+            set_stripes_source(cr);
+            mask_scaled(cr, &surface, offset_x, offset_y, scale);
+            let _ = cr.restore();
+        }
+    }
+
+    if let Some(peaking) = &masks.peaking {
+        if let Some(surface) = create_mask_surface(peaking, masks.width, masks.height) {
+            let _ = cr.save();
+            cr.set_source_rgb(1.0, 0.1, 0.1);
+            mask_scaled(cr, &surface, offset_x, offset_y, scale);
+            let _ = cr.restore();
+        }
+    }
+}
+
+/// Diagonal black and white stripes.
+fn set_stripes_source(cr: &cairo::Context) {
+    let period = ZEBRA_STRIPE_WIDTH * 2.0;
+    let gradient = cairo::LinearGradient::new(0.0, 0.0, period, period);
+    gradient.add_color_stop_rgba(0.0, 0.0, 0.0, 0.0, 0.8);
+    gradient.add_color_stop_rgba(0.5, 0.0, 0.0, 0.0, 0.8);
+    gradient.add_color_stop_rgba(0.5, 1.0, 1.0, 1.0, 0.8);
+    gradient.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 0.8);
+    gradient.set_extend(cairo::Extend::Repeat);
+
+    let _ = cr.set_source(&gradient);
+}
+
+fn create_mask_surface(mask: &[u8], width: usize, height: usize) -> Option<cairo::ImageSurface> {
+    let mut surface = cairo::ImageSurface::create(cairo::Format::A8, width as i32, height as i32).ok()?;
+    let stride = surface.stride() as usize;
+
+    {
+        let mut pixels = surface.data().ok()?;
+        for (y, row) in mask.chunks(width).enumerate().take(height) {
+            pixels[y * stride..y * stride + width].copy_from_slice(row);
+        }
+    }
+
+    Some(surface)
+}
+
+/// Paints the source through the mask, which is scaled to the frame.
+fn mask_scaled(cr: &cairo::Context, surface: &cairo::ImageSurface, offset_x: f64, offset_y: f64, scale: f64) {
+    let pattern = cairo::SurfacePattern::create(surface);
+    pattern.set_filter(cairo::Filter::Nearest);
+
+    let mut matrix = cairo::Matrix::identity();
+    matrix.translate(offset_x, offset_y);
+    matrix.scale(scale, scale);
+    matrix.invert();
+    pattern.set_matrix(matrix);
+
+    let _ = cr.mask(&pattern);
+}
+
+/// Draws the guides within the aspect ratio, if any, else within the frame.
+fn draw_guides(cr: &cairo::Context, guides: &FramingGuides, frame: &Rect) {
+    let target = match guides.aspect {
+        Some(aspect) => {
+            let target = frame.fit(aspect.ratio());
+
+            // Darkens between the edges of the frame and the target
+            let _ = cr.save();
+            cr.set_fill_rule(cairo::FillRule::EvenOdd);
+            cr.rectangle(frame.x, frame.y, frame.width, frame.height);
+            cr.rectangle(target.x, target.y, target.width, target.height);
+            cr.set_source_rgba(0.0, 0.0, 0.0, 0.6);
+            let _ = cr.fill();
+            let _ = cr.restore();
+
+            target
+        }
+        None => *frame,
+    };
+
+    let _ = cr.save();
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.7);
+    cr.set_line_width(1.0);
+
+    if guides.thirds {
+        let (xs, ys) = target.thirds();
+        for x in xs {
+            cr.move_to(x, target.y);
+            cr.line_to(x, target.y + target.height);
+        }
+        for y in ys {
+            cr.move_to(target.x, y);
+            cr.line_to(target.x + target.width, y);
+        }
+        let _ = cr.stroke();
+    }
+
+    if guides.center {
+        let (x, y) = target.center();
+        let size = CENTER_CROSS_SIZE / 2.0;
+        cr.move_to(x - size, y);
+        cr.line_to(x + size, y);
+        cr.move_to(x, y - size);
+        cr.line_to(x, y + size);
+        let _ = cr.stroke();
+    }
+
+    if guides.safe_areas {
+        let action = target.scale(ACTION_SAFE);
+        cr.rectangle(action.x, action.y, action.width, action.height);
+        let _ = cr.stroke();
+
+        let title = target.scale(TITLE_SAFE);
+        cr.set_dash(&[6.0, 4.0], 0.0);
+        cr.rectangle(title.x, title.y, title.width, title.height);
+        let _ = cr.stroke();
+    }
+
+    let _ = cr.restore();
+}
@@ -0,0 +1,276 @@
+use std::{cell::RefCell, rc::Rc};
+
+use adw::{prelude::*, ActionRow, Dialog, HeaderBar, PreferencesGroup, PreferencesPage, ToolbarView};
+use gtk::{Align, Button, CheckButton, DropDown, Label, Orientation, StringList};
+use v4l::Device;
+
+use crate::{
+    camera::{list_cameras, CameraSource},
+    components::create_info_row,
+    profiles::{diff_profile, list_profiles, ControlDiff, DiffStatus, LiveControl, Profile, ProfileControl},
+    quirks::DeviceQuirks,
+};
+
+/// What the current state of the device is compared with.
+enum DiffSource {
+    Profile(Profile),
+    Camera { name: String, path: String },
+}
+
+impl DiffSource {
+    fn label(&self) -> String {
+        match self {
+            DiffSource::Profile(p) => format!("Profile: {}", p.name),
+            DiffSource::Camera { name, path } => format!("Camera: {} ({})", name, path),
+        }
+    }
+
+    /// Describes the pair of values shown in the rows.
+    fn value_description(&self) -> &'static str {
+        match self {
+            DiffSource::Profile(_) => "Value of the profile → value of this camera",
+            DiffSource::Camera { .. } => "Value of the other camera → value of this camera",
+        }
+    }
+
+    fn to_profile(&self) -> Result<Profile, String> {
+        match self {
+            DiffSource::Profile(p) => Ok(p.clone()),
+            DiffSource::Camera { name, path } => {
+                let device = Device::with_path(path).map_err(|e| e.to_string())?;
+                Profile::from_device(name.clone(), None, &device).map_err(|e| e.message)
+            }
+        }
+    }
+}
+
+struct DiffView {
+    device_path: String,
+    page: PreferencesPage,
+    group: RefCell<Option<PreferencesGroup>>,
+    // Checkboxes of the applicable rows
+    selection: RefCell<Vec<(CheckButton, ProfileControl)>>,
+}
+
+/// Shows the differences of a profile or another camera to the current values
+/// of the device. Selected values can be applied to the device.
+///
+/// Other cameras are listed from `camera_source`, as in the camera selector.
+/// `on_applied` is called after values were set on the device.
+pub fn present_profile_diff_dialog(
+    parent: &impl IsA<gtk::Widget>,
+    device_path: String,
+    camera_source: CameraSource,
+    on_applied: Rc<Box<dyn Fn() + 'static>>,
+) {
+    let sources = collect_sources(&device_path, camera_source);
+
+    let view = Rc::new(DiffView {
+        device_path,
+        page: PreferencesPage::new(),
+        group: RefCell::new(None),
+        selection: RefCell::new(vec![]),
+    });
+
+    let content = gtk::Box::builder()
+        .orientation(Orientation::Vertical)
+        .build();
+
+    if sources.is_empty() {
+        let group = PreferencesGroup::new();
+        group.add(&create_info_row(
+            "Nothing to compare".to_string(),
+            "Save a profile or connect another camera".to_string(),
+        ));
+        view.page.add(&group);
+    }
+
+    let labels: Vec<String> = sources.iter().map(|s| s.label()).collect();
+    let label_refs: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
+    let source_dropdown = DropDown::builder()
+        .margin_end(12)
+        .margin_start(12)
+        .margin_top(6)
+        .model(&StringList::new(&label_refs))
+        .build();
+
+    let sources = Rc::new(sources);
+    let view_for_source = view.clone();
+    let sources_for_source = sources.clone();
+    source_dropdown.connect_selected_notify(move |dropdown| {
+        if let Some(source) = sources_for_source.get(dropdown.selected() as usize) {
+            view_for_source.show_diff(source);
+        }
+    });
+
+    if let Some(source) = sources.first() {
+        view.show_diff(source);
+    }
+
+    content.append(&source_dropdown);
+    content.append(&view.page);
+
+    let apply_button = Button::builder()
+        .css_classes(["suggested-action", "pill"])
+        .halign(Align::Center)
+        .label("Apply Selected")
+        .margin_bottom(12)
+        .margin_top(12)
+        .sensitive(!sources.is_empty())
+        .build();
+
+    let view_for_apply = view.clone();
+    apply_button.connect_clicked(move |_| {
+        view_for_apply.apply_selected();
+        on_applied();
+
+        if let Some(source) = sources.get(source_dropdown.selected() as usize) {
+            view_for_apply.show_diff(source);
+        }
+    });
+
+    let toolbar = ToolbarView::new();
+    toolbar.add_top_bar(&HeaderBar::new());
+    toolbar.set_content(Some(&content));
+    toolbar.add_bottom_bar(&apply_button);
+
+    let dialog = Dialog::builder()
+        .child(&toolbar)
+        .content_height(700)
+        .content_width(640)
+        .title("Compare Settings")
+        .build();
+
+    dialog.present(Some(parent));
+}
+
+impl DiffView {
+    fn show_diff(&self, source: &DiffSource) {
+        if let Some(old_group) = self.group.borrow_mut().take() {
+            self.page.remove(&old_group);
+        }
+        self.selection.borrow_mut().clear();
+
+        let group = PreferencesGroup::builder()
+            .title("Differences")
+            .description(source.value_description())
+            .build();
+
+        match self.compare(source) {
+            Ok(diffs) => self.add_rows(&group, diffs),
+            Err(e) => group.add(&create_info_row("Error comparing".to_string(), e)),
+        };
+
+        self.page.add(&group);
+        self.group.replace(Some(group));
+    }
+
+    fn compare(&self, source: &DiffSource) -> Result<Vec<ControlDiff>, String> {
+        let profile = source.to_profile()?;
+        let device = Device::with_path(&self.device_path).map_err(|e| e.to_string())?;
+        let live_controls = LiveControl::from_device(&device).map_err(|e| e.message)?;
+
+        Ok(diff_profile(&profile, &live_controls))
+    }
+
+    fn add_rows(&self, group: &PreferencesGroup, diffs: Vec<ControlDiff>) {
+        let unchanged = diffs.iter().filter(|d| d.status == DiffStatus::Equal).count();
+        if unchanged == diffs.len() {
+            group.add(&create_info_row(
+                "No differences".to_string(),
+                format!("All {} controls are equal", unchanged),
+            ));
+            return;
+        }
+
+        for diff in diffs.into_iter().filter(|d| d.status != DiffStatus::Equal) {
+            let row = ActionRow::builder()
+                .title(diff.name.clone())
+                .subtitle(diff.status.label())
+                .build();
+
+            let applicable = diff.status.is_applicable();
+            let check = CheckButton::builder()
+                .active(applicable)
+                .sensitive(applicable)
+                .valign(Align::Center)
+                .build();
+            row.add_prefix(&check);
+
+            let values = gtk::Box::builder()
+                .orientation(Orientation::Horizontal)
+                .spacing(6)
+                .build();
+            values.append(&create_value_label(diff.profile_value, diff.status));
+            values.append(&Label::new(Some("→")));
+            values.append(&create_value_label(diff.device_value, diff.status));
+            row.add_suffix(&values);
+
+            if let (true, Some(control)) = (applicable, diff.control) {
+                row.set_activatable_widget(Some(&check));
+                self.selection.borrow_mut().push((check, control));
+            }
+
+            group.add(&row);
+        }
+    }
+
+    fn apply_selected(&self) {
+        let mut profile = Profile::new("Selection".to_string(), None);
+        profile.controls = self
+            .selection
+            .borrow()
+            .iter()
+            .filter(|(check, _)| check.is_active())
+            .map(|(_, control)| control.clone())
+            .collect();
+
+        let device = match Device::with_path(&self.device_path) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Error opening device to apply values: {}", e);
+                return;
+            }
+        };
+
+        let quirks = DeviceQuirks::for_device(&self.device_path, &device);
+        for error in profile.apply(&device, &quirks) {
+            eprintln!("{}", error);
+        }
+    }
+}
+
+fn create_value_label(value: Option<String>, status: DiffStatus) -> Label {
+    let label = Label::builder()
+        .label(value.unwrap_or("—".to_string()))
+        .width_chars(10)
+        .xalign(1.0)
+        .build();
+
+    match status {
+        DiffStatus::OutOfRange | DiffStatus::MissingOnDevice => label.add_css_class("error"),
+        DiffStatus::ReadOnly | DiffStatus::MissingInProfile => label.add_css_class("dim-label"),
+        _ => {}
+    };
+
+    label
+}
+
+/// Saved profiles and all other cameras can be compared with the device.
+fn collect_sources(device_path: &str, camera_source: CameraSource) -> Vec<DiffSource> {
+    let mut sources: Vec<DiffSource> = list_profiles()
+        .into_iter()
+        .map(DiffSource::Profile)
+        .collect();
+
+    for camera in list_cameras(camera_source) {
+        if camera.path != device_path {
+            sources.push(DiffSource::Camera {
+                name: camera.name,
+                path: camera.path,
+            });
+        }
+    }
+
+    sources
+}
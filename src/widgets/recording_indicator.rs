@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use gtk::{glib, prelude::*, Image, Label, Orientation};
+
+use crate::video_recording::format_elapsed;
+
+/// Shows, that a recording is running and for how long.
+pub struct RecordingIndicator {
+    widget: gtk::Box,
+    elapsed_label: Label,
+    timer: RefCell<Option<glib::SourceId>>,
+}
+
+impl RecordingIndicator {
+    pub fn new() -> Rc<Self> {
+        let icon = Image::builder()
+            .css_classes(["error"])
+            .icon_name("media-record-symbolic")
+            .build();
+
+        let elapsed_label = Label::builder()
+            .css_classes(["numeric"])
+            .build();
+
+        let widget = gtk::Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(6)
+            .tooltip_text("Recording")
+            .visible(false)
+            .build();
+        widget.append(&icon);
+        widget.append(&elapsed_label);
+
+        Rc::new(RecordingIndicator {
+            widget,
+            elapsed_label,
+            timer: RefCell::new(None),
+        })
+    }
+
+    pub fn get_widget(&self) -> &gtk::Box {
+        &self.widget
+    }
+
+    pub fn start(&self) {
+        self.stop();
+
+        let started = Instant::now();
+        self.elapsed_label.set_label(&format_elapsed(0));
+        self.widget.set_visible(true);
+
+        let label = self.elapsed_label.clone();
+        let timer = glib::timeout_add_seconds_local(1, move || {
+            label.set_label(&format_elapsed(started.elapsed().as_secs()));
+            glib::ControlFlow::Continue
+        });
+        self.timer.replace(Some(timer));
+    }
+
+    pub fn stop(&self) {
+        if let Some(timer) = self.timer.take() {
+            timer.remove();
+        }
+
+        self.widget.set_visible(false);
+    }
+}
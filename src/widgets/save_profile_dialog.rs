@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use adw::{prelude::*, AlertDialog, EntryRow, ExpanderRow, PreferencesGroup, ResponseAppearance, SwitchRow};
+use v4l::Device;
+
+use crate::{files::get_device_id, profiles::{is_storable, save_profile, Profile}};
+
+/// Asks for a name and saves the current control-values of the device as
+/// profile.
+pub fn present_save_profile_dialog(parent: &impl IsA<gtk::Widget>, device_path: String) {
+    let dialog = AlertDialog::new(
+        Some("Save Profile"),
+        Some("Saves the current values of all controls"),
+    );
+
+    let name_row = EntryRow::builder().title("Name").build();
+    let bind_row = SwitchRow::builder()
+        .title("Apply automatically")
+        .subtitle("Bind profile to this camera")
+        .build();
+
+    // Locked controls are only enforced by the daemon for bound profiles
+    let lock_row = ExpanderRow::builder()
+        .sensitive(false)
+        .subtitle("Restored by the daemon with --enforce")
+        .title("Lock controls")
+        .build();
+    let lock_row_for_bind = lock_row.clone();
+    bind_row.connect_active_notify(move |row| {
+        lock_row_for_bind.set_sensitive(row.is_active());
+    });
+
+    let descriptions = match Device::with_path(&device_path).and_then(|d| d.query_controls()) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error querying controls for profile: {}", e);
+            vec![]
+        }
+    };
+
+    let lock_switches: Vec<(u32, SwitchRow)> = descriptions
+        .iter()
+        .filter(|d| is_storable(d))
+        .map(|d| {
+            let row = SwitchRow::builder().title(d.name.as_str()).build();
+            lock_row.add_row(&row);
+            (d.id, row)
+        })
+        .collect();
+
+    let group = PreferencesGroup::new();
+    group.add(&name_row);
+    group.add(&bind_row);
+    group.add(&lock_row);
+    dialog.set_extra_child(Some(&group));
+
+    dialog.add_responses(&[("cancel", "Cancel"), ("save", "Save")]);
+    dialog.set_response_appearance("save", ResponseAppearance::Suggested);
+    dialog.set_response_enabled("save", false);
+    dialog.set_default_response(Some("save"));
+    dialog.set_close_response("cancel");
+
+    let dialog_for_name = dialog.clone();
+    name_row.connect_changed(move |row| {
+        dialog_for_name.set_response_enabled("save", !row.text().trim().is_empty());
+    });
+
+    dialog.connect_response(Some("save"), move |_, _| {
+        let device = match Device::with_path(&device_path) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Error opening device for profile: {}", e);
+                return;
+            }
+        };
+
+        let device_id = if bind_row.is_active() {
+            Some(get_device_id(&device_path))
+        } else {
+            None
+        };
+
+        let name = name_row.text().trim().to_string();
+        let bound = device_id.is_some();
+        let mut profile = match Profile::from_device(name, device_id, &device) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Error creating profile: {}", e);
+                return;
+            }
+        };
+
+        if bound {
+            let locked: HashSet<u32> = lock_switches
+                .iter()
+                .filter(|(_, row)| row.is_active())
+                .map(|(id, _)| *id)
+                .collect();
+            for control in &mut profile.controls {
+                control.locked = locked.contains(&control.id);
+            }
+        }
+
+        match save_profile(&profile) {
+            Ok(path) => println!("Saved profile to {}", path.display()),
+            Err(e) => eprintln!("Error saving profile: {}", e),
+        };
+    });
+
+    dialog.present(Some(parent));
+}
@@ -0,0 +1,295 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use gtk::{cairo, prelude::*, DrawingArea, Label, Orientation};
+
+use crate::{
+    components::create_heading,
+    preview_frame::FrameSampler,
+    scopes::{analyze, Histogram, RgbFrame, ScopeData, CLIPPING_WARNING_SHARE},
+};
+
+const WAVEFORM_COLUMNS: usize = 160;
+const VECTORSCOPE_SIZE: usize = 129;
+
+/// Luma and RGB histograms, a waveform monitor and a vectorscope of the
+/// preview, with warnings for clipped highlights and shadows.
+pub struct ScopesPanel {
+    widget: gtk::Box,
+    sampler: Rc<FrameSampler>,
+    areas: Vec<DrawingArea>,
+    status_label: Label,
+    data: Rc<RefCell<Option<ScopeData>>>,
+    subscription: RefCell<Option<u32>>,
+}
+
+impl ScopesPanel {
+    pub fn new(sampler: Rc<FrameSampler>) -> Rc<Self> {
+        let data: Rc<RefCell<Option<ScopeData>>> = Rc::new(RefCell::new(None));
+
+        let histogram_area = create_scope_area(120);
+        let data_for_histogram = data.clone();
+        histogram_area.set_draw_func(move |_, cr, width, height| {
+            draw_background(cr);
+            if let Some(data) = data_for_histogram.borrow().as_ref() {
+                draw_histograms(cr, data, width as f64, height as f64);
+            }
+        });
+
+        let waveform_area = create_scope_area(120);
+        let data_for_waveform = data.clone();
+        waveform_area.set_draw_func(move |_, cr, width, height| {
+            draw_background(cr);
+            if let Some(data) = data_for_waveform.borrow().as_ref() {
+                draw_waveform(cr, data, width as f64, height as f64);
+            }
+        });
+
+        let vectorscope_area = create_scope_area(240);
+        let data_for_vectorscope = data.clone();
+        vectorscope_area.set_draw_func(move |_, cr, width, height| {
+            draw_background(cr);
+            if let Some(data) = data_for_vectorscope.borrow().as_ref() {
+                draw_vectorscope(cr, data, width as f64, height as f64);
+            }
+        });
+
+        let status_label = Label::builder()
+            .css_classes(["caption"])
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .build();
+
+        let widget = gtk::Box::builder()
+            .margin_start(12)
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .visible(false)
+            .width_request(280)
+            .build();
+
+        widget.append(&create_heading("Histogram"));
+        widget.append(&histogram_area);
+        widget.append(&create_heading("Waveform"));
+        widget.append(&waveform_area);
+        widget.append(&create_heading("Vectorscope"));
+        widget.append(&vectorscope_area);
+        widget.append(&status_label);
+
+        Rc::new(ScopesPanel {
+            widget,
+            sampler,
+            areas: vec![histogram_area, waveform_area, vectorscope_area],
+            status_label,
+            data,
+            subscription: RefCell::new(None),
+        })
+    }
+
+    pub fn get_widget(&self) -> &gtk::Box {
+        &self.widget
+    }
+
+    /// Shows the scopes and analyzes frames, while active.
+    pub fn set_active(self: &Rc<Self>, active: bool) {
+        self.widget.set_visible(active);
+
+        if let Some(id) = self.subscription.take() {
+            self.sampler.unsubscribe(id);
+        }
+
+        if !active {
+            self.data.replace(None);
+            return;
+        }
+
+        let weak_panel: Weak<ScopesPanel> = Rc::downgrade(self);
+        let id = self.sampler.subscribe_frames(move |frame| {
+            if let Some(panel) = weak_panel.upgrade() {
+                panel.update(frame);
+            }
+        });
+        self.subscription.replace(Some(id));
+    }
+
+    fn update(&self, frame: Result<&RgbFrame, &str>) {
+        let frame = match frame {
+            Ok(f) => f,
+            Err(e) => {
+                self.status_label.remove_css_class("error");
+                self.status_label.set_label(e);
+                return;
+            }
+        };
+
+        let data = analyze(frame, WAVEFORM_COLUMNS.min(frame.width), VECTORSCOPE_SIZE);
+
+        let warnings = data.clipping.warnings();
+        if warnings.is_empty() {
+            self.status_label.remove_css_class("error");
+            self.status_label.set_label("No clipping");
+        } else {
+            self.status_label.add_css_class("error");
+            self.status_label.set_label(&warnings.join("\n"));
+        }
+
+        self.data.replace(Some(data));
+        for area in &self.areas {
+            area.queue_draw();
+        }
+    }
+}
+
+fn create_scope_area(height: i32) -> DrawingArea {
+    DrawingArea::builder()
+        .content_height(height)
+        .hexpand(true)
+        .build()
+}
+
+fn draw_background(cr: &cairo::Context) {
+    cr.set_source_rgb(0.1, 0.1, 0.1);
+    let _ = cr.paint();
+}
+
+/// RGB histograms blended additively, with the luma histogram as outline.
+/// Clipped levels at both ends are marked.
+fn draw_histograms(cr: &cairo::Context, data: &ScopeData, width: f64, height: f64) {
+    // The extremes are left out, as clipping would flatten everything else
+    let max = [&data.red, &data.green, &data.blue, &data.luma]
+        .iter()
+        .flat_map(|h| h[1..255].iter())
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1) as f64;
+
+    let histogram_path = |histogram: &Histogram| {
+        cr.move_to(0.0, height);
+        for (level, count) in histogram.iter().enumerate() {
+            let value = (*count as f64 / max).min(1.0);
+            cr.line_to(level as f64 * width / 255.0, height - value * height);
+        }
+        cr.line_to(width, height);
+        cr.close_path();
+    };
+
+    cr.set_operator(cairo::Operator::Add);
+    for (histogram, (r, g, b)) in [(&data.red, (0.8, 0.2, 0.2)), (&data.green, (0.2, 0.8, 0.2)), (&data.blue, (0.2, 0.2, 0.8))] {
+        histogram_path(histogram);
+        cr.set_source_rgba(r, g, b, 0.7);
+        let _ = cr.fill();
+    }
+    cr.set_operator(cairo::Operator::Over);
+
+    histogram_path(&data.luma);
+    cr.set_source_rgb(0.9, 0.9, 0.9);
+    cr.set_line_width(1.0);
+    let _ = cr.stroke();
+
+    if data.clipping.shadows >= CLIPPING_WARNING_SHARE {
+        draw_clipping_marker(cr, 0.0, height);
+    }
+    if data.clipping.highlights >= CLIPPING_WARNING_SHARE {
+        draw_clipping_marker(cr, width - 3.0, height);
+    }
+}
+
+fn draw_clipping_marker(cr: &cairo::Context, x: f64, height: f64) {
+    cr.set_source_rgb(0.9, 0.1, 0.1);
+    cr.rectangle(x, 0.0, 3.0, height);
+    let _ = cr.fill();
+}
+
+/// Luma levels per column, brighter for more pixels, with lines at 0, 50
+/// and 100 %.
+fn draw_waveform(cr: &cairo::Context, data: &ScopeData, width: f64, height: f64) {
+    let waveform = &data.waveform;
+    let max = waveform.counts.iter().max().copied().unwrap_or(0).max(1) as f64;
+
+    let surface = create_intensity_surface(waveform.columns, 256, (0.4, 1.0, 0.4), |x, y| {
+        (waveform.count(x, 255 - y) as f64 / max).sqrt()
+    });
+    if let Some(surface) = surface {
+        paint_scaled(cr, &surface, width / waveform.columns as f64, height / 256.0);
+    }
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.25);
+    cr.set_line_width(1.0);
+    for level in [0.0, 0.5, 1.0] {
+        let y = (height - level * height).clamp(0.5, height - 0.5);
+        cr.move_to(0.0, y);
+        cr.line_to(width, y);
+    }
+    let _ = cr.stroke();
+}
+
+/// Chroma distribution in a centered square, with a graticule for the
+/// maximum saturation.
+fn draw_vectorscope(cr: &cairo::Context, data: &ScopeData, width: f64, height: f64) {
+    let vectorscope = &data.vectorscope;
+    let size = width.min(height);
+    let (offset_x, offset_y) = ((width - size) / 2.0, (height - size) / 2.0);
+
+    let max = vectorscope.counts.iter().max().copied().unwrap_or(0).max(1) as f64;
+    let surface = create_intensity_surface(vectorscope.size, vectorscope.size, (0.9, 0.9, 0.9), |x, y| {
+        (vectorscope.count(x, y) as f64 / max).sqrt()
+    });
+
+    let _ = cr.save();
+    cr.translate(offset_x, offset_y);
+    if let Some(surface) = surface {
+        paint_scaled(cr, &surface, size / vectorscope.size as f64, size / vectorscope.size as f64);
+    }
+
+    let center = size / 2.0;
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.25);
+    cr.set_line_width(1.0);
+    cr.arc(center, center, center - 1.0, 0.0, std::f64::consts::TAU);
+    cr.move_to(center, 0.0);
+    cr.line_to(center, size);
+    cr.move_to(0.0, center);
+    cr.line_to(size, center);
+    let _ = cr.stroke();
+    let _ = cr.restore();
+}
+
+/// A surface with the color in the intensity, given per pixel in 0..1.
+fn create_intensity_surface(
+    width: usize,
+    height: usize,
+    (r, g, b): (f64, f64, f64),
+    intensity: impl Fn(usize, usize) -> f64,
+) -> Option<cairo::ImageSurface> {
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width as i32, height as i32).ok()?;
+    let stride = surface.stride() as usize;
+
+    {
+        let mut pixels = surface.data().ok()?;
+        for y in 0..height {
+            for x in 0..width {
+                let alpha = intensity(x, y).clamp(0.0, 1.0);
+                // Premultiplied and in native byte order
+                let argb = u32::from_be_bytes([
+                    (alpha * 255.0) as u8,
+                    (r * alpha * 255.0) as u8,
+                    (g * alpha * 255.0) as u8,
+                    (b * alpha * 255.0) as u8,
+                ]);
+                let offset = y * stride + x * 4;
+                pixels[offset..offset + 4].copy_from_slice(&argb.to_ne_bytes());
+            }
+        }
+    }
+
+    Some(surface)
+}
+
+fn paint_scaled(cr: &cairo::Context, surface: &cairo::ImageSurface, scale_x: f64, scale_y: f64) {
+    let _ = cr.save();
+    cr.scale(scale_x, scale_y);
+    if cr.set_source_surface(surface, 0.0, 0.0).is_ok() {
+        let _ = cr.paint();
+    }
+    let _ = cr.restore();
+}
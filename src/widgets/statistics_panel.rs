@@ -0,0 +1,489 @@
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use gtk::{cairo, gdk, glib, prelude::*, Button, DrawingArea, Grid, Label, Orientation};
+use v4l::Device;
+
+use crate::{
+    capture_mode::{get_capture_mode, CaptureMode},
+    components::create_heading,
+    control_events::{dequeue_event, subscribe_control},
+    frame_statistics::{describe_changes, FrameTimes, Timeline, HISTORY_SECONDS},
+    preview_frame::FrameSampler,
+    profiles::LiveControl,
+    video_recording::format_elapsed,
+};
+
+// Listed changes, the graph shows all within the history
+const LISTED_CHANGES: usize = 8;
+// Microseconds, over which the frame rate is measured
+const SAMPLE_INTERVAL: i64 = 1_000_000;
+
+/// The device kept open for its control events, with the format and the
+/// controls as last known, to describe changes.
+struct WatchedDevice {
+    device_path: String,
+    device: Device,
+    mode: Option<CaptureMode>,
+    controls: Vec<LiveControl>,
+    source: glib::SourceId,
+}
+
+/// Measured frame rate, jitter and dropped frames of the preview over time,
+/// with changes of controls and the format marked.
+///
+/// Frames are counted, as the preview receives them. The sequence numbers of
+/// the driver are not available through PipeWire, so dropped frames are
+/// estimated from gaps between frames.
+///
+/// Changes of controls are reported by the driver, the format is read again,
+/// when the stream restarts.
+pub struct StatisticsPanel {
+    widget: gtk::Box,
+    sampler: Rc<FrameSampler>,
+    device_path: Box<dyn Fn() -> String>,
+    graph: DrawingArea,
+    fps_label: Label,
+    jitter_label: Label,
+    dropped_label: Label,
+    format_label: Label,
+    changes_box: gtk::Box,
+    frames: Rc<RefCell<FrameTimes>>,
+    timeline: Rc<RefCell<Timeline>>,
+    nominal_fps: Rc<Cell<Option<f64>>>,
+    watched_device: RefCell<Option<WatchedDevice>>,
+    started: Cell<i64>,
+    last_sample: Cell<i64>,
+    frame_handler: RefCell<Option<(gdk::Paintable, glib::SignalHandlerId)>>,
+    subscription: RefCell<Option<u32>>,
+}
+
+impl StatisticsPanel {
+    /// `device_path` returns the path of the selected camera.
+    pub fn new(sampler: Rc<FrameSampler>, device_path: Box<dyn Fn() -> String>) -> Rc<Self> {
+        let timeline: Rc<RefCell<Timeline>> = Rc::new(RefCell::new(Timeline::default()));
+        let nominal_fps: Rc<Cell<Option<f64>>> = Rc::new(Cell::new(None));
+
+        let graph = DrawingArea::builder()
+            .content_height(140)
+            .hexpand(true)
+            .build();
+
+        let timeline_for_graph = timeline.clone();
+        let nominal_fps_for_graph = nominal_fps.clone();
+        graph.set_draw_func(move |_, cr, width, height| {
+            draw_graph(cr, &timeline_for_graph.borrow(), nominal_fps_for_graph.get(), width as f64, height as f64);
+        });
+
+        let values = Grid::builder()
+            .column_spacing(12)
+            .row_spacing(3)
+            .build();
+
+        let fps_label = attach_value_row(&values, 0, "Frame rate");
+        let jitter_label = attach_value_row(&values, 1, "Interval jitter");
+        let dropped_label = attach_value_row(&values, 2, "Dropped frames");
+        let format_label = attach_value_row(&values, 3, "Format");
+
+        let changes_box = gtk::Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(3)
+            .build();
+
+        let reset_button = Button::builder()
+            .css_classes(["flat"])
+            .halign(gtk::Align::Start)
+            .label("Reset")
+            .build();
+
+        let widget = gtk::Box::builder()
+            .margin_start(12)
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .visible(false)
+            .width_request(280)
+            .build();
+
+        widget.append(&create_heading("Frame Rate"));
+        widget.append(&graph);
+        widget.append(&values);
+        widget.append(&create_heading("Changes"));
+        widget.append(&changes_box);
+        widget.append(&reset_button);
+
+        let panel = Rc::new(StatisticsPanel {
+            widget,
+            sampler,
+            device_path,
+            graph,
+            fps_label,
+            jitter_label,
+            dropped_label,
+            format_label,
+            changes_box,
+            frames: Rc::new(RefCell::new(FrameTimes::default())),
+            timeline,
+            nominal_fps,
+            watched_device: RefCell::new(None),
+            started: Cell::new(0),
+            last_sample: Cell::new(0),
+            frame_handler: RefCell::new(None),
+            subscription: RefCell::new(None),
+        });
+
+        let weak_panel: Weak<StatisticsPanel> = Rc::downgrade(&panel);
+        reset_button.connect_clicked(move |_| {
+            if let Some(panel) = weak_panel.upgrade() {
+                panel.reset();
+            }
+        });
+
+        panel
+    }
+
+    pub fn get_widget(&self) -> &gtk::Box {
+        &self.widget
+    }
+
+    /// Shows the statistics and records them, while active.
+    pub fn set_active(self: &Rc<Self>, active: bool) {
+        self.widget.set_visible(active);
+
+        if let Some(id) = self.subscription.take() {
+            self.sampler.unsubscribe(id);
+        }
+        if let Some((paintable, handler)) = self.frame_handler.take() {
+            paintable.disconnect(handler);
+        }
+        self.unwatch_device();
+
+        if !active {
+            return;
+        }
+
+        self.reset();
+
+        let weak_panel: Weak<StatisticsPanel> = Rc::downgrade(self);
+        let id = self.sampler.subscribe_ticks(move || {
+            if let Some(panel) = weak_panel.upgrade() {
+                if glib::monotonic_time() - panel.last_sample.get() >= SAMPLE_INTERVAL {
+                    panel.update();
+                }
+            }
+        });
+        self.subscription.replace(Some(id));
+    }
+
+    fn reset(self: &Rc<Self>) {
+        let now = glib::monotonic_time();
+        self.started.set(now);
+        self.last_sample.set(now);
+        self.frames.replace(FrameTimes::default());
+        self.timeline.replace(Timeline::default());
+
+        self.connect_frames();
+        self.watch_device();
+        self.update_labels();
+    }
+
+    /// Counts the frames of the paintable, which is replaced, when the
+    /// stream restarts. Returns true, if it was replaced.
+    fn connect_frames(&self) -> bool {
+        let paintable = match self.sampler.paintable() {
+            Some(p) => p,
+            None => return false,
+        };
+
+        if self.frame_handler.borrow().as_ref().is_some_and(|(p, _)| *p == paintable) {
+            return false;
+        }
+
+        if let Some((old, handler)) = self.frame_handler.take() {
+            old.disconnect(handler);
+        }
+
+        let frames = self.frames.clone();
+        let handler = paintable.connect_invalidate_contents(move |_| {
+            frames.borrow_mut().add_frame(glib::monotonic_time());
+        });
+        self.frame_handler.replace(Some((paintable, handler)));
+        true
+    }
+
+    fn update(self: &Rc<Self>) {
+        let restarted = self.connect_frames();
+
+        let now = glib::monotonic_time();
+        let nominal_interval = self.nominal_fps.get().filter(|f| *f > 0.0).map(|f| 1_000_000.0 / f);
+        let sample = self.frames.borrow_mut().take_sample(now - self.last_sample.get(), nominal_interval);
+        self.last_sample.set(now);
+
+        self.timeline.borrow_mut().add_sample(self.elapsed(now), sample);
+
+        let device_path = (self.device_path)();
+        let watched = self.watched_device.borrow().as_ref().map(|w| w.device_path == device_path);
+        match watched {
+            Some(true) if restarted => self.read_mode(),
+            Some(true) => {}
+            // Another camera was selected, or the device is not open yet
+            _ => self.watch_device(),
+        };
+
+        self.update_labels();
+    }
+
+    /// Opens the selected camera, reads its format and controls and watches
+    /// the controls for changes.
+    fn watch_device(self: &Rc<Self>) {
+        self.unwatch_device();
+
+        let device_path = (self.device_path)();
+        let device = match Device::with_path(&device_path) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Error opening device for statistics: {}", e);
+                return;
+            }
+        };
+
+        let mode = get_capture_mode(&device).ok();
+        let controls = LiveControl::from_device(&device).unwrap_or_default();
+
+        let fd = device.handle().fd();
+        for control in &controls {
+            if let Err(e) = subscribe_control(fd, control.id) {
+                eprintln!("Cannot watch control {} for statistics: {}", control.name, e);
+            }
+        }
+
+        let weak_panel: Weak<StatisticsPanel> = Rc::downgrade(self);
+        let source = glib::unix_fd_add_local(
+            fd,
+            glib::IOCondition::PRI | glib::IOCondition::ERR | glib::IOCondition::HUP,
+            move |_, condition| {
+                let panel = match weak_panel.upgrade() {
+                    Some(p) => p,
+                    None => return glib::ControlFlow::Break,
+                };
+
+                if condition.intersects(glib::IOCondition::ERR | glib::IOCondition::HUP) {
+                    // The source is removed by breaking
+                    panel.watched_device.replace(None);
+                    return glib::ControlFlow::Break;
+                }
+
+                panel.read_events();
+                glib::ControlFlow::Continue
+            },
+        );
+
+        self.show_mode(mode);
+        self.watched_device.replace(Some(WatchedDevice {
+            device_path,
+            device,
+            mode,
+            controls,
+            source,
+        }));
+    }
+
+    fn unwatch_device(&self) {
+        if let Some(watched) = self.watched_device.take() {
+            watched.source.remove();
+        }
+    }
+
+    /// Marks the controls changed since the last events.
+    fn read_events(&self) {
+        let mut watched = self.watched_device.borrow_mut();
+        let watched = match watched.as_mut() {
+            Some(w) => w,
+            None => return,
+        };
+
+        let mut controls = watched.controls.clone();
+        while let Ok(event) = dequeue_event(watched.device.handle().fd()) {
+            let event = match event {
+                Some(e) => e,
+                None => continue,
+            };
+
+            if let Some(control) = controls.iter_mut().find(|c| c.id == event.id) {
+                control.value = Some(event.value);
+            }
+        }
+
+        let changes = describe_changes(&watched.controls, &controls);
+        watched.controls = controls;
+        self.add_markers(changes);
+        self.update_labels();
+    }
+
+    /// Reads the format again and marks, if it changed.
+    fn read_mode(&self) {
+        let mut watched = self.watched_device.borrow_mut();
+        let watched = match watched.as_mut() {
+            Some(w) => w,
+            None => return,
+        };
+
+        let mode = get_capture_mode(&watched.device).ok();
+        if let (Some(old), Some(new)) = (watched.mode, mode) {
+            if old != new {
+                self.add_markers(vec![format!("Format: {} → {}", old, new)]);
+            }
+        }
+
+        watched.mode = mode;
+        self.show_mode(mode);
+    }
+
+    fn show_mode(&self, mode: Option<CaptureMode>) {
+        self.nominal_fps.set(mode.and_then(|m| m.interval).map(|i| i.fps()));
+        self.format_label.set_label(&match mode {
+            Some(mode) => mode.to_string(),
+            None => "Unknown".to_string(),
+        });
+    }
+
+    fn add_markers(&self, changes: Vec<String>) {
+        let time = self.elapsed(glib::monotonic_time());
+        let mut timeline = self.timeline.borrow_mut();
+        for change in changes {
+            timeline.add_marker(time, change);
+        }
+    }
+
+    fn update_labels(&self) {
+        let timeline = self.timeline.borrow();
+
+        match timeline.samples.back() {
+            Some((_, sample)) => {
+                self.fps_label.set_label(&match self.nominal_fps.get() {
+                    Some(nominal) => format!("{:.1} of {:.0} fps", sample.fps, nominal),
+                    None => format!("{:.1} fps", sample.fps),
+                });
+                self.jitter_label.set_label(&format!("{:.1} ms", sample.jitter));
+                self.dropped_label.set_label(&format!("{} ({} in total)", sample.dropped, timeline.dropped_total));
+            }
+            None => {
+                for label in [&self.fps_label, &self.jitter_label, &self.dropped_label] {
+                    label.set_label("–");
+                }
+            }
+        }
+
+        while let Some(child) = self.changes_box.first_child() {
+            self.changes_box.remove(&child);
+        }
+
+        if timeline.markers.is_empty() {
+            self.changes_box.append(
+                &Label::builder()
+                    .css_classes(["dim-label"])
+                    .halign(gtk::Align::Start)
+                    .label("No changes recorded")
+                    .build(),
+            );
+        }
+
+        for marker in timeline.markers.iter().rev().take(LISTED_CHANGES) {
+            let impact = match timeline.impact(marker) {
+                Some((before, after)) => format!("\n{:.1} → {:.1} fps", before, after),
+                None => String::new(),
+            };
+
+            self.changes_box.append(
+                &Label::builder()
+                    .css_classes(["caption"])
+                    .halign(gtk::Align::Start)
+                    .label(format!("{}  {}{}", format_elapsed(marker.time as u64), marker.label, impact))
+                    .wrap(true)
+                    .xalign(0.0)
+                    .build(),
+            );
+        }
+
+        self.graph.queue_draw();
+    }
+
+    fn elapsed(&self, now: i64) -> f64 {
+        (now - self.started.get()) as f64 / 1_000_000.0
+    }
+}
+
+/// Adds a row with a title, returning the label for the value.
+fn attach_value_row(grid: &Grid, row: i32, title: &str) -> Label {
+    let title_label = Label::builder()
+        .css_classes(["caption", "dim-label"])
+        .halign(gtk::Align::Start)
+        .label(title)
+        .build();
+
+    let value_label = Label::builder()
+        .css_classes(["caption", "numeric"])
+        .halign(gtk::Align::Start)
+        .hexpand(true)
+        .label("–")
+        .wrap(true)
+        .xalign(0.0)
+        .build();
+
+    grid.attach(&title_label, 0, row, 1, 1);
+    grid.attach(&value_label, 1, row, 1, 1);
+    value_label
+}
+
+/// Frame rate over the history, with the nominal rate dashed, dropped frames
+/// and changes marked.
+fn draw_graph(cr: &cairo::Context, timeline: &Timeline, nominal_fps: Option<f64>, width: f64, height: f64) {
+    cr.set_source_rgb(0.1, 0.1, 0.1);
+    let _ = cr.paint();
+
+    let end = timeline.samples.back().map(|(t, _)| *t).unwrap_or(0.0).max(HISTORY_SECONDS);
+    let x = |time: f64| width - (end - time) / HISTORY_SECONDS * width;
+
+    let max_fps = timeline
+        .samples
+        .iter()
+        .map(|(_, s)| s.fps)
+        .chain(nominal_fps)
+        .fold(1.0, f64::max)
+        * 1.1;
+    let y = |fps: f64| height - fps / max_fps * height;
+
+    cr.set_line_width(1.0);
+
+    cr.set_source_rgba(0.9, 0.8, 0.2, 0.6);
+    for marker in &timeline.markers {
+        cr.move_to(x(marker.time).round() + 0.5, 0.0);
+        cr.line_to(x(marker.time).round() + 0.5, height);
+    }
+    let _ = cr.stroke();
+
+    cr.set_source_rgb(0.9, 0.1, 0.1);
+    for (time, sample) in timeline.samples.iter().filter(|(_, s)| s.dropped > 0) {
+        cr.rectangle(x(*time) - 1.5, height - 4.0, 3.0, 4.0);
+    }
+    let _ = cr.fill();
+
+    if let Some(nominal) = nominal_fps {
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.4);
+        cr.set_dash(&[4.0, 4.0], 0.0);
+        cr.move_to(0.0, y(nominal).round() + 0.5);
+        cr.line_to(width, y(nominal).round() + 0.5);
+        let _ = cr.stroke();
+        cr.set_dash(&[], 0.0);
+    }
+
+    cr.set_source_rgb(0.4, 1.0, 0.4);
+    cr.set_line_width(1.5);
+    for (index, (time, sample)) in timeline.samples.iter().enumerate() {
+        if index == 0 {
+            cr.move_to(x(*time), y(sample.fps));
+        } else {
+            cr.line_to(x(*time), y(sample.fps));
+        }
+    }
+    let _ = cr.stroke();
+}
@@ -0,0 +1,137 @@
+use toml::{Table, Value};
+
+use crate::files::{load_config, parse_config, save_config};
+
+const STATE_FILE_NAME: &str = "window-state.toml";
+const DESCRIPTION: &str = "window state";
+
+pub const DEFAULT_WIDTH: i32 = 1280;
+pub const DEFAULT_HEIGHT: i32 = 720;
+
+/// State of the window, restored on the next start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowState {
+    /// Stable id of the last selected camera, see `files::get_device_id`
+    pub device: Option<String>,
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+    /// Width of the controls, as a fraction of the window
+    pub sidebar_width_fraction: f64,
+    pub info_revealed: bool,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        WindowState {
+            device: None,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            maximized: false,
+            sidebar_width_fraction: 0.25,
+            info_revealed: false,
+        }
+    }
+}
+
+impl WindowState {
+    /// Loads the stored state. Missing or invalid values are replaced by
+    /// their defaults.
+    pub fn load() -> Self {
+        load_config(STATE_FILE_NAME, DESCRIPTION).map_or_else(WindowState::default, |t| WindowState::from_table(&t))
+    }
+
+    pub fn save(&self) {
+        save_config(STATE_FILE_NAME, DESCRIPTION, &self.to_table());
+    }
+
+    pub fn parse(content: &str) -> Self {
+        parse_config(content, DESCRIPTION).map_or_else(WindowState::default, |t| WindowState::from_table(&t))
+    }
+
+    pub fn to_toml(&self) -> String {
+        self.to_table().to_string()
+    }
+
+    fn from_table(table: &Table) -> Self {
+        let defaults = WindowState::default();
+
+        let get_size = |key: &str, default: i32| {
+            table
+                .get(key)
+                .and_then(|v| v.as_integer())
+                .filter(|v| *v > 0 && *v <= i32::MAX as i64)
+                .map_or(default, |v| v as i32)
+        };
+
+        let get_fraction = |key: &str, default: f64| {
+            table
+                .get(key)
+                .and_then(|v| v.as_float())
+                .filter(|v| *v > 0.0 && *v <= 1.0)
+                .unwrap_or(default)
+        };
+
+        let get_bool = |key: &str, default: bool| {
+            table.get(key).and_then(|v| v.as_bool()).unwrap_or(default)
+        };
+
+        WindowState {
+            device: table
+                .get("device")
+                .and_then(|v| v.as_str())
+                .map(|d| d.to_string()),
+            width: get_size("width", defaults.width),
+            height: get_size("height", defaults.height),
+            maximized: get_bool("maximized", defaults.maximized),
+            sidebar_width_fraction: get_fraction("sidebar_width_fraction", defaults.sidebar_width_fraction),
+            info_revealed: get_bool("info_revealed", defaults.info_revealed),
+        }
+    }
+
+    fn to_table(&self) -> Table {
+        let mut table = Table::new();
+
+        if let Some(device) = &self.device {
+            table.insert("device".to_string(), Value::String(device.clone()));
+        }
+
+        table.insert("width".to_string(), Value::Integer(self.width as i64));
+        table.insert("height".to_string(), Value::Integer(self.height as i64));
+        table.insert("maximized".to_string(), Value::Boolean(self.maximized));
+        table.insert("sidebar_width_fraction".to_string(), Value::Float(self.sidebar_width_fraction));
+        table.insert("info_revealed".to_string(), Value::Boolean(self.info_revealed));
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_state_roundtrip() {
+        let state = WindowState {
+            device: Some("usb-Webcam_123-video-index0".to_string()),
+            width: 1600,
+            height: 900,
+            maximized: true,
+            sidebar_width_fraction: 0.4,
+            info_revealed: true,
+        };
+
+        assert_eq!(WindowState::parse(&state.to_toml()), state);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let state = WindowState::parse("width = -5\nheight = \"big\"\nsidebar_width_fraction = 1.5\ninfo_revealed = true");
+
+        assert_eq!(state.width, DEFAULT_WIDTH);
+        assert_eq!(state.height, DEFAULT_HEIGHT);
+        assert_eq!(state.sidebar_width_fraction, WindowState::default().sidebar_width_fraction);
+        assert!(state.info_revealed);
+        assert_eq!(WindowState::parse("not toml ["), WindowState::default());
+    }
+}